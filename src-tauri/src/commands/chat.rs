@@ -1,14 +1,22 @@
 use crate::error::{AppError, Result};
-use crate::models::config::{Config, EngineId};
-use crate::models::events::StreamEvent;
+use crate::models::config::{Config, EngineId, LastSession, TokenUsage};
+use crate::models::events::{is_auth_error_text, StreamEvent};
+use crate::services::attachment::{append_attachment_refs, validate_attachments};
+use crate::services::event_buffer::SessionEventRecorder;
+use crate::services::event_coalescer::{EventCoalescer, EventGate};
 use crate::services::iflow_service::IFlowService;
+use crate::services::token_summary::{self, CostAccumulator, FileTokenStats, ProjectTokenSummary, SessionDirFingerprint};
+use std::collections::{HashMap, VecDeque};
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio, Child};
-use std::sync::Arc;
-use tauri::{Emitter, Window, State};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager, Window, State};
 use uuid::Uuid;
 
+#[cfg(windows)]
+use crate::services::claude_launcher::{is_batch_script, resolve_node_and_cli};
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
@@ -16,130 +24,119 @@ use std::os::windows::process::CommandExt;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// 将启动 Claude 进程的错误转换为带有操作提示的 AppError
+fn claude_spawn_error(claude_cmd: &str, e: std::io::Error, action: &str) -> AppError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        AppError::ProcessError(format!(
+            "{}失败: 找不到 Claude CLI（{}）。请运行 `npm install -g @anthropic-ai/claude-code` 安装，或在设置中配置正确的 CLI 路径",
+            action, claude_cmd
+        ))
+    } else {
+        AppError::ProcessError(format!("{}失败: {}", action, e))
+    }
+}
+
+/// 恢复最近会话时等待真实会话 ID 的最长时间
+const SESSION_ID_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// 同一工具两次进度事件之间的最小间隔，避免频繁推送前端
+const TOOL_PROGRESS_MIN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// 进程异常退出时保留的 stderr 行数
+const STDERR_TAIL_LINES: usize = 20;
+
 /// Claude 聊天会话
 pub struct ChatSession {
     pub id: String,
     pub child: Child,
+    /// 是否发送无法解析的原始输出行
+    pub emit_raw_events: bool,
+    /// 会话进程映射，进程退出时其 PID 已被移除说明会话是被中断的
+    sessions: Option<Arc<Mutex<HashMap<String, SessionProcess>>>>,
 }
 
 impl ChatSession {
     /// 创建ChatSession实例（用于continue_chat）
     pub fn with_id_and_child(id: String, child: Child) -> Self {
-        Self { id, child }
+        Self { id, child, emit_raw_events: false, sessions: None }
     }
-}
-
-/// 从 claude.cmd 路径解析出 Node.js 和 cli.js 的路径
-///
-/// claude.cmd 通常位于: C:\Users\...\AppData\Roaming\npm\claude.cmd
-/// node.exe 通常在同一目录或系统 PATH 中
-/// cli.js 位于: node_modules\@anthropic-ai\claude-code\cli.js
-#[cfg(windows)]
-fn resolve_node_and_cli(claude_cmd_path: &str) -> Result<(String, String)> {
-    let cmd_path = Path::new(claude_cmd_path);
 
-    // 获取 .cmd 文件所在的目录（通常是 npm 目录）
-    let npm_dir = cmd_path.parent()
-        .ok_or_else(|| AppError::ProcessError("无法获取 claude.cmd 的父目录".to_string()))?;
-
-    // 查找 node.exe
-    let node_exe = find_node_exe(npm_dir)?;
-
-    // 查找 cli.js
-    let cli_js = find_cli_js(npm_dir)?;
+    /// 设置是否发送无法解析的原始输出行
+    pub fn with_raw_events(mut self, enabled: bool) -> Self {
+        self.emit_raw_events = enabled;
+        self
+    }
 
-    eprintln!("[resolve_node_and_cli] node_exe: {}", node_exe);
-    eprintln!("[resolve_node_and_cli] cli_js: {}", cli_js);
+    /// 关联会话进程映射，用于识别被中断的会话
+    pub fn with_session_registry(mut self, sessions: Arc<Mutex<HashMap<String, SessionProcess>>>) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
 
-    Ok((node_exe, cli_js))
+    /// 进程是否已被中断（PID 已从会话映射中移除）
+    fn was_interrupted(&self) -> bool {
+        let pid = self.child.id();
+        self.sessions.as_ref().is_some_and(|sessions| {
+            sessions.lock()
+                .map(|sessions| !sessions.values().any(|p| *p == SessionProcess::Running(pid)))
+                .unwrap_or(false)
+        })
+    }
 }
 
-/// 查找 node.exe 可执行文件
-#[cfg(windows)]
-fn find_node_exe(npm_dir: &Path) -> Result<String> {
-    // 1. 检查 npm 目录下是否有 node.exe
-    let local_node = npm_dir.join("node.exe");
-    if local_node.exists() {
-        return Ok(local_node.to_string_lossy().to_string());
-    }
-
-    // 2. 使用 where 命令查找系统中的 node.exe
-    let output = Command::new("where")
-        .args(["node"])
-        .output()
-        .map_err(|e| AppError::ProcessError(format!("查找 node.exe 失败: {}", e)))?;
-
-    if output.status.success() {
-        let node_path = String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .next()
-            .map(|s| s.trim().to_string());
-
-        if let Some(path) = node_path {
-            return Ok(path);
-        }
-    }
+/// Claude 会话恢复方式
+#[derive(Debug, Clone, Copy)]
+enum ClaudeResume<'a> {
+    /// 新会话
+    New,
+    /// 恢复指定会话（--resume <id>）
+    Session(&'a str),
+    /// 恢复工作目录下最近的会话（--continue）
+    Latest,
+}
 
-    // 3. 尝试常见路径
-    let common_paths = vec![
-        r"C:\Program Files\nodejs\node.exe",
-        r"C:\Program Files (x86)\nodejs\node.exe",
-    ];
+/// Claude 系统提示词
+///
+/// `replace` 对应 `--system-prompt`（替换默认提示词），`append` 对应 `--append-system-prompt`
+/// （追加到默认提示词之后）。两者同时提供时都会传给 CLI，追加内容接在替换后的提示词之后
+#[derive(Debug, Clone, Copy)]
+struct SystemPrompt<'a> {
+    replace: Option<&'a str>,
+    append: Option<&'a str>,
+}
 
-    for path in common_paths {
-        if Path::new(path).exists() {
-            return Ok(path.to_string());
+impl<'a> SystemPrompt<'a> {
+    fn new(replace: Option<&'a str>, append: Option<&'a str>) -> Self {
+        let non_empty = |p: Option<&'a str>| p.filter(|p| !p.is_empty());
+        Self {
+            replace: non_empty(replace),
+            append: non_empty(append),
         }
     }
-
-    Err(AppError::ProcessError("无法找到 node.exe".to_string()))
 }
 
-/// 查找 cli.js 文件
-#[cfg(windows)]
-fn find_cli_js(npm_dir: &Path) -> Result<String> {
-    // cli.js 通常在: npm_dir/node_modules/@anthropic-ai/claude-code/cli.js
-    let cli_js = npm_dir
-        .join("node_modules")
-        .join("@anthropic-ai")
-        .join("claude-code")
-        .join("cli.js");
-
-    if cli_js.exists() {
-        return Ok(cli_js.to_string_lossy().to_string());
-    }
-
-    // 如果不在预期位置，尝试全局 node_modules
-    if let Some(roaming_appdata) = std::env::var("APPDATA").ok() {
-        let global_cli = PathBuf::from(roaming_appdata)
-            .join("npm")
-            .join("node_modules")
-            .join("@anthropic-ai")
-            .join("claude-code")
-            .join("cli.js");
-
-        if global_cli.exists() {
-            return Ok(global_cli.to_string_lossy().to_string());
+/// 添加 Claude CLI 的公共参数
+fn apply_claude_args(cmd: &mut Command, resume: ClaudeResume<'_>, message: &str, system_prompt: SystemPrompt<'_>, model: Option<&str>) {
+    match resume {
+        ClaudeResume::New => {}
+        ClaudeResume::Session(session_id) => {
+            cmd.arg("--resume").arg(session_id);
+        }
+        ClaudeResume::Latest => {
+            cmd.arg("--continue");
         }
     }
 
-    Err(AppError::ProcessError(format!(
-        "无法找到 cli.js，预期位置: {}",
-        cli_js.display()
-    )))
-}
-
-/// 构建直接调用 Node.js 的命令
-#[cfg(windows)]
-fn build_node_command(node_exe: &str, cli_js: &str, message: &str, system_prompt: Option<&str>) -> Command {
-    let mut cmd = Command::new(node_exe);
-    cmd.arg(cli_js);
-
     // 添加 system-prompt 参数（如果有）
-    if let Some(prompt) = system_prompt {
-        if !prompt.is_empty() {
-            cmd.arg("--system-prompt").arg(prompt);
-        }
+    if let Some(prompt) = system_prompt.replace {
+        cmd.arg("--system-prompt").arg(prompt);
+    }
+    if let Some(prompt) = system_prompt.append {
+        cmd.arg("--append-system-prompt").arg(prompt);
+    }
+
+    if let Some(model) = model.filter(|m| !m.is_empty()) {
+        cmd.arg("--model").arg(model);
     }
 
     cmd.arg("--print")
@@ -149,156 +146,185 @@ fn build_node_command(node_exe: &str, cli_js: &str, message: &str, system_prompt
         .arg("--permission-mode")
         .arg("bypassPermissions")
         .arg(message);
-    cmd
 }
 
-/// 构建直接调用 Node.js 的命令（continue_chat）
+/// 构建直接调用 Node.js 的命令
 #[cfg(windows)]
-fn build_node_command_resume(node_exe: &str, cli_js: &str, session_id: &str, message: &str, system_prompt: Option<&str>) -> Command {
+fn build_node_command(node_exe: &str, cli_js: &str, resume: ClaudeResume<'_>, message: &str, system_prompt: SystemPrompt<'_>, model: Option<&str>) -> Command {
     let mut cmd = Command::new(node_exe);
-    cmd.arg(cli_js)
-        .arg("--resume")
-        .arg(session_id);
-
-    // 添加 system-prompt 参数（如果有）
-    if let Some(prompt) = system_prompt {
-        if !prompt.is_empty() {
-            cmd.arg("--system-prompt").arg(prompt);
-        }
-    }
+    cmd.arg(cli_js);
+    apply_claude_args(&mut cmd, resume, message, system_prompt, model);
+    cmd
+}
 
-    cmd.arg("--print")
-        .arg("--verbose")
-        .arg("--output-format")
-        .arg("stream-json")
-        .arg("--permission-mode")
-        .arg("bypassPermissions")
-        .arg(message);
+/// 无法解析 Node.js 启动路径时的回退命令：.cmd/.bat 经 `cmd /C` 调用，其他可执行文件直接调用
+#[cfg(windows)]
+fn build_fallback_command(claude_cmd: &str, resume: ClaudeResume<'_>, message: &str, system_prompt: SystemPrompt<'_>, model: Option<&str>) -> Command {
+    let mut cmd = if is_batch_script(claude_cmd) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(claude_cmd);
+        c
+    } else {
+        Command::new(claude_cmd)
+    };
+    apply_claude_args(&mut cmd, resume, message, system_prompt, model);
     cmd
 }
 
-impl ChatSession {
-    /// 启动新的聊天会话
-    pub fn start(config: &Config, message: &str, system_prompt: Option<&str>) -> Result<Self> {
-        eprintln!("[ChatSession::start] 启动 Claude 会话");
-        let claude_cmd = config.get_claude_cmd();
-        eprintln!("[ChatSession::start] claude_cmd: {}", claude_cmd);
-        eprintln!("[ChatSession::start] message 长度: {} 字符", message.len());
-        if let Some(prompt) = system_prompt {
-            eprintln!("[ChatSession::start] systemPrompt 长度: {} 字符", prompt.len());
+/// 按指定恢复方式启动 Claude CLI 进程
+fn spawn_claude(config: &Config, resume: ClaudeResume<'_>, message: &str, system_prompt: SystemPrompt<'_>) -> Result<Child> {
+    let claude_cmd = config.get_claude_cmd();
+    tracing::info!("[spawn_claude] claude_cmd: {}, 恢复方式: {:?}", claude_cmd, resume);
+    tracing::info!("[spawn_claude] message 长度: {} 字符", message.len());
+    let model = config.claude_code.model.as_deref();
+    if let Some(model) = model {
+        tracing::info!("[spawn_claude] 使用模型: {}", model);
+    }
+    if let Some(prompt) = system_prompt.replace {
+        tracing::info!("[spawn_claude] systemPrompt 长度: {} 字符", prompt.len());
+    }
+    if let Some(prompt) = system_prompt.append {
+        tracing::info!("[spawn_claude] appendSystemPrompt 长度: {} 字符", prompt.len());
+    }
+
+    // 根据平台构建不同的命令
+    // resolve_error 记录 Node.js 路径解析失败的原因，回退启动仍失败时一并返回
+    #[cfg(windows)]
+    let (mut cmd, resolve_error) = match resolve_node_and_cli(&claude_cmd, config.claude_code.node_path.as_deref()) {
+        // Windows: 直接调用 Node.js，绕过 cmd.exe
+        Ok((node_exe, cli_js)) => (build_node_command(&node_exe, &cli_js, resume, message, system_prompt, model), None),
+        Err(e) => {
+            tracing::warn!("[spawn_claude] 解析 Node.js 启动路径失败，回退到直接调用: {}", e);
+            (build_fallback_command(&claude_cmd, resume, message, system_prompt, model), Some(e))
         }
+    };
 
-        // 根据平台构建不同的命令
-        #[cfg(windows)]
-        let mut cmd = {
-            // Windows: 直接调用 Node.js，绕过 cmd.exe
-            let (node_exe, cli_js) = resolve_node_and_cli(&claude_cmd)?;
-            build_node_command(&node_exe, &cli_js, message, system_prompt)
-        };
+    #[cfg(not(windows))]
+    let (mut cmd, resolve_error) = {
+        // Unix/Mac: 直接使用 claude 命令
+        let mut c = Command::new(&claude_cmd);
+        apply_claude_args(&mut c, resume, message, system_prompt, model);
+        (c, None::<AppError>)
+    };
 
-        #[cfg(not(windows))]
-        let mut cmd = {
-            // Unix/Mac: 直接使用 claude 命令
-            let mut c = Command::new(&claude_cmd);
-            // 添加 system-prompt 参数（如果有）
-            if let Some(prompt) = system_prompt {
-                if !prompt.is_empty() {
-                    c.arg("--system-prompt").arg(prompt);
-                }
-            }
-            c.arg("--print")
-                .arg("--verbose")
-                .arg("--output-format")
-                .arg("stream-json")
-                .arg("--permission-mode")
-                .arg("bypassPermissions")
-                .arg(message)
-        };
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::piped());
 
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+    // Windows 上隐藏窗口
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
 
-        // Windows 上隐藏窗口
-        #[cfg(windows)]
-        cmd.creation_flags(CREATE_NO_WINDOW);
+    // 设置工作目录
+    if let Some(ref work_dir) = config.work_dir {
+        tracing::info!("[spawn_claude] work_dir: {:?}", work_dir);
+        cmd.current_dir(work_dir);
+    }
 
-        // 设置工作目录
-        if let Some(ref work_dir) = config.work_dir {
-            eprintln!("[ChatSession::start] work_dir: {:?}", work_dir);
-            cmd.current_dir(work_dir);
-        }
+    // 设置 Git Bash 环境变量 (Windows 需要)
+    if let Some(ref git_bash_path) = config.git_bin_path {
+        tracing::info!("[spawn_claude] 设置 CLAUDE_CODE_GIT_BASH_PATH: {}", git_bash_path);
+        cmd.env("CLAUDE_CODE_GIT_BASH_PATH", git_bash_path);
+    }
 
-        // 设置 Git Bash 环境变量 (Windows 需要)
-        if let Some(ref git_bash_path) = config.git_bin_path {
-            eprintln!("[ChatSession::start] 设置 CLAUDE_CODE_GIT_BASH_PATH: {}", git_bash_path);
-            cmd.env("CLAUDE_CODE_GIT_BASH_PATH", git_bash_path);
-        }
+    // 自定义环境变量（覆盖继承的环境）
+    cmd.envs(config.session_env());
+
+    // 参数中包含消息与系统提示词，环境变量可能包含令牌，均不写入日志
+    tracing::info!("[spawn_claude] 执行命令: {:?}（{} 个参数）", cmd.get_program(), cmd.get_args().count());
+
+    let action = match resume {
+        ClaudeResume::New => "启动 Claude",
+        ClaudeResume::Session(_) | ClaudeResume::Latest => "继续 Claude 会话",
+    };
+    let child = cmd.spawn()
+        .map_err(|e| match resolve_error {
+            Some(resolve) => AppError::ProcessError(format!(
+                "{}失败: {}；回退直接调用 {} 也失败: {}",
+                action, resolve, claude_cmd, e
+            )),
+            None => claude_spawn_error(&claude_cmd, e, action),
+        })?;
 
-        eprintln!("[ChatSession::start] 执行命令: {:?}", cmd);
+    tracing::info!("[spawn_claude] 进程 PID: {:?}", child.id());
 
-        let child = cmd.spawn()
-            .map_err(|e| AppError::ProcessError(format!("启动 Claude 失败: {}", e)))?;
+    Ok(child)
+}
 
-        eprintln!("[ChatSession::start] 进程 PID: {:?}", child.id());
+impl ChatSession {
+    /// 按指定恢复方式启动聊天会话（ID 为临时 ID，真实 ID 从输出流中获取）
+    fn spawn(config: &Config, resume: ClaudeResume<'_>, message: &str, system_prompt: SystemPrompt<'_>) -> Result<Self> {
+        tracing::info!("[ChatSession::spawn] 启动 Claude 会话");
+        let child = spawn_claude(config, resume, message, system_prompt)?;
 
         Ok(Self {
             id: Uuid::new_v4().to_string(),
             child,
+            emit_raw_events: config.emit_raw_events,
+            sessions: None,
         })
     }
 
     /// 读取输出并解析事件
-    pub fn read_events<F>(self, mut callback: F)
+    pub fn read_events<F>(mut self, mut callback: F)
     where
         F: FnMut(StreamEvent) + Send + 'static,
     {
-        eprintln!("[ChatSession::read_events] 开始读取输出");
+        tracing::info!("[ChatSession::read_events] 开始读取输出");
 
-        let stdout = match self.child.stdout {
+        let stdout = match self.child.stdout.take() {
             Some(stdout) => stdout,
             None => {
-                eprintln!("[ChatSession::read_events] 无法获取 stdout");
+                tracing::warn!("[ChatSession::read_events] 无法获取 stdout");
                 // 发送错误事件到前端
-                callback(StreamEvent::Error {
-                    error: "无法获取进程输出流".to_string(),
-                });
+                callback(StreamEvent::error("无法获取进程输出流"));
                 return;
             }
         };
 
-        let stderr = match self.child.stderr {
+        let stderr = match self.child.stderr.take() {
             Some(stderr) => stderr,
             None => {
-                eprintln!("[ChatSession::read_events] 无法获取 stderr");
-                callback(StreamEvent::Error {
-                    error: "无法获取进程错误流".to_string(),
-                });
+                tracing::warn!("[ChatSession::read_events] 无法获取 stderr");
+                callback(StreamEvent::error("无法获取进程错误流"));
                 return;
             }
         };
 
-        // 启动单独的线程读取 stderr
-        std::thread::spawn(move || {
-            eprintln!("[stderr_reader] 开始读取 stderr");
+        // 启动单独的线程读取 stderr，保留最后若干行用于错误提示
+        let stderr_reader = std::thread::spawn(move || {
+            tracing::info!("[stderr_reader] 开始读取 stderr");
+            let mut tail: VecDeque<String> = VecDeque::with_capacity(STDERR_TAIL_LINES);
             let reader = BufReader::new(stderr);
             for line in reader.lines() {
                 match line {
-                    Ok(l) => eprintln!("[stderr] {}", l),
+                    Ok(l) => {
+                        tracing::info!("[stderr] {}", l);
+                        if l.trim().is_empty() {
+                            continue;
+                        }
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(l);
+                    }
                     Err(_) => break,
                 }
             }
-            eprintln!("[stderr_reader] stderr 结束");
+            tracing::info!("[stderr_reader] stderr 结束");
+            tail
         });
 
         let reader = BufReader::new(stdout);
         let mut line_count = 0;
         let mut received_session_end = false;
+        let mut auth_error_reported = false;
+        let mut last_tool_progress: HashMap<String, std::time::Instant> = HashMap::new();
 
         for line in reader.lines() {
             let line = match line {
                 Ok(l) => l,
                 Err(e) => {
-                    eprintln!("[ChatSession::read_events] 读取行错误: {}", e);
+                    tracing::warn!("[ChatSession::read_events] 读取行错误: {}", e);
                     break;
                 }
             };
@@ -310,29 +336,78 @@ impl ChatSession {
                 continue;
             }
 
-            eprintln!("[ChatSession::read_events] 行 {}: {}", line_count, line_trimmed.chars().take(100).collect::<String>());
+            tracing::debug!("[ChatSession::read_events] 行 {}: {}", line_count, line_trimmed.chars().take(100).collect::<String>());
 
             // 使用 StreamEvent::parse_line 解析
             if let Some(event) = StreamEvent::parse_line(line_trimmed) {
-                eprintln!("[ChatSession::read_events] 解析成功事件: {:?}", std::mem::discriminant(&event));
+                tracing::debug!("[ChatSession::read_events] 解析成功事件: {:?}", std::mem::discriminant(&event));
 
                 // 检查是否收到 session_end 事件
                 if matches!(event, StreamEvent::SessionEnd) {
                     received_session_end = true;
                 }
 
+                // 工具进度按工具限流，间隔内的进度事件直接丢弃
+                if let StreamEvent::ToolProgress { ref tool_use_id, .. } = event {
+                    let now = std::time::Instant::now();
+                    let throttled = last_tool_progress.get(tool_use_id)
+                        .is_some_and(|last| now.duration_since(*last) < TOOL_PROGRESS_MIN_INTERVAL);
+                    if throttled {
+                        continue;
+                    }
+                    last_tool_progress.insert(tool_use_id.clone(), now);
+                }
+
+                // 识别未登录错误，额外发送带错误码的事件
+                if !auth_error_reported {
+                    if let Some(detail) = event.auth_error_detail() {
+                        tracing::warn!("[ChatSession::read_events] 检测到 Claude CLI 未登录: {}", detail);
+                        auth_error_reported = true;
+                        callback(StreamEvent::not_authenticated(&detail));
+                    }
+                }
+
                 callback(event);
             } else {
-                eprintln!("[ChatSession::read_events] 解析失败，原始内容: {}", line_trimmed.chars().take(200).collect::<String>());
+                tracing::debug!("[ChatSession::read_events] 解析失败，原始内容: {}", line_trimmed.chars().take(200).collect::<String>());
+                if self.emit_raw_events {
+                    callback(StreamEvent::Raw { line: line_trimmed.to_string() });
+                }
             }
         }
 
-        eprintln!("[ChatSession::read_events] 读取结束，共处理 {} 行", line_count);
+        tracing::info!("[ChatSession::read_events] 读取结束，共处理 {} 行", line_count);
+
+        let stderr_tail = stderr_reader.join().unwrap_or_default();
+        let exit_status = self.child.wait();
+        tracing::info!("[ChatSession::read_events] 进程退出状态: {:?}", exit_status);
+
+        // 被中断的会话由 interrupt_chat 发送 session_end，这里不再重复发送
+        if !received_session_end && self.was_interrupted() {
+            tracing::info!("[ChatSession::read_events] 会话已被中断，忽略退出码");
+            return;
+        }
 
         // 【关键修复】只有在进程没有正常发送 session_end 事件时才自动发送
         // 这样避免重复发送，同时确保异常退出时前端能收到通知
         if !received_session_end {
-            eprintln!("[ChatSession::read_events] 进程异常退出，发送 session_end 事件");
+            // 非零退出码时附带 stderr 尾部内容，说明失败原因（被信号终止时不视为错误）
+            if let Ok(Some(code)) = exit_status.map(|status| status.code()) {
+                if code != 0 {
+                    let auth_line = stderr_tail.iter().find(|l| is_auth_error_text(l)).cloned();
+                    if let Some(line) = auth_line {
+                        if !auth_error_reported {
+                            callback(StreamEvent::not_authenticated(&line));
+                        }
+                    } else if stderr_tail.is_empty() {
+                        callback(StreamEvent::error(format!("Claude 进程异常退出，退出码: {}", code)));
+                    } else {
+                        callback(StreamEvent::error(Vec::from(stderr_tail).join("\n")));
+                    }
+                }
+            }
+
+            tracing::warn!("[ChatSession::read_events] 进程异常退出，发送 session_end 事件");
             callback(StreamEvent::SessionEnd);
         }
     }
@@ -346,6 +421,7 @@ impl ChatSession {
 ///
 /// 统一接口，根据 engine_id 参数选择具体的 AI 引擎实现
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn start_chat(
     message: String,
     window: Window,
@@ -353,12 +429,29 @@ pub async fn start_chat(
     work_dir: Option<String>,
     engine_id: Option<String>,
     system_prompt: Option<String>,
+    append_system_prompt: Option<String>,
+    context_id: Option<String>,
+    attachments: Option<Vec<String>>,
+    preset_name: Option<String>,
+    model: Option<String>,
+    approval_mode: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
 ) -> Result<String> {
-    eprintln!("[start_chat] 收到消息，长度: {} 字符", message.len());
+    tracing::info!("[start_chat] 收到消息，长度: {} 字符", message.len());
     if let Some(ref prompt) = system_prompt {
-        eprintln!("[start_chat] 系统提示词长度: {} 字符", prompt.len());
+        tracing::info!("[start_chat] 系统提示词长度: {} 字符", prompt.len());
     }
 
+    // 校验附件（在启动进程前完成，避免无效附件导致会话中途失败）
+    let attachments = match attachments {
+        Some(paths) if !paths.is_empty() => {
+            tracing::info!("[start_chat] 附件数量: {}", paths.len());
+            validate_attachments(&paths)?
+        }
+        _ => Vec::new(),
+    };
+
     // 从 AppState 获取实际配置（在独立作用域中，确保 MutexGuard 在 await 前释放）
     let (config, engine) = {
         let config_store = state.config_store.lock()
@@ -368,7 +461,7 @@ pub async fn start_chat(
         // 如果传入了 work_dir 参数，优先使用它而不是配置中的
         if let Some(ref work_dir_str) = work_dir {
             let work_dir_path = PathBuf::from(work_dir_str);
-            eprintln!("[start_chat] 使用传入的工作目录: {:?}", work_dir_path);
+            tracing::info!("[start_chat] 使用传入的工作目录: {:?}", work_dir_path);
             cfg.work_dir = Some(work_dir_path);
         }
 
@@ -377,91 +470,262 @@ pub async fn start_chat(
         let engine = EngineId::from_str(&engine_id_str)
             .unwrap_or(EngineId::ClaudeCode);
 
-        eprintln!("[start_chat] 使用引擎: {:?}", engine);
+        tracing::info!("[start_chat] 使用引擎: {:?}", engine);
+
+        apply_engine_overrides(&mut cfg, engine, model, approval_mode);
+
+        apply_generation_overrides(&mut cfg, engine, temperature, max_tokens);
 
         (cfg, engine)
     }; // MutexGuard 在此处释放
 
+    // 显式传入的系统提示词优先于预设
+    let system_prompt = match (system_prompt, preset_name) {
+        (Some(prompt), _) => Some(prompt),
+        (None, Some(name)) => {
+            let preset = config.prompt_preset(&name)
+                .ok_or_else(|| AppError::ConfigError(format!("预设不存在: {}", name)))?;
+            tracing::info!("[start_chat] 使用提示词预设: {}", name);
+            Some(preset.content.clone())
+        }
+        (None, None) => None,
+    };
+
+    let slot = reserve_session_slot(&state.sessions, config.max_concurrent_sessions, None)?;
+
     match engine {
         EngineId::ClaudeCode => {
-            start_claude_chat(&config, &message, window, state, system_prompt.as_deref()).await
+            let message = append_attachment_refs(&message, &attachments);
+            let system_prompt = SystemPrompt::new(system_prompt.as_deref(), append_system_prompt.as_deref());
+            start_claude_chat(&config, ClaudeResume::New, &message, window, state, slot, system_prompt, context_id).await
         }
         EngineId::IFlow => {
-            start_iflow_chat_internal(&config, &message, window, state).await
+            if !attachments.is_empty() {
+                return Err(AppError::ProcessError("IFlow 引擎暂不支持附件".to_string()));
+            }
+            start_iflow_chat_internal(&config, &message, window, state, slot, context_id).await
+        }
+    }
+}
+
+/// 创建向前端发送 chat-event 的事件合并器（经由会话的事件出口发送）
+fn chat_event_coalescer(
+    window: Window,
+    config: &Config,
+    recorder: SessionEventRecorder,
+    gate: EventGate,
+    log_tag: &'static str,
+) -> EventCoalescer {
+    EventCoalescer::new(std::time::Duration::from_millis(config.event_coalesce_ms), move |event| {
+        let event_json = serde_json::to_string(&event)
+            .unwrap_or_else(|_| "{}".to_string());
+        let sent = gate.emit(|| {
+            recorder.record(&event_json);
+            let _ = window.emit("chat-event", &event_json);
+        });
+        if sent {
+            tracing::debug!("[{}] 发送事件: {}", log_tag, event_json);
+        } else {
+            tracing::debug!("[{}] 会话已中断，丢弃事件: {}", log_tag, event_json);
+        }
+    })
+}
+
+/// 为 Claude 的结果事件附加估算费用 `estimated_cost_usd`
+///
+/// 按结果事件 `modelUsage` 中的分模型用量和价格表计价；没有用量或存在未配置价格的模型时为 null。
+/// CLI 自带的 `total_cost_usd` 保持不变
+fn attach_estimated_cost(mut event: StreamEvent, config: &Config) -> StreamEvent {
+    if let StreamEvent::Result { extra, .. } = &mut event {
+        let mut cost = CostAccumulator::default();
+        if let Some(model_usage) = extra.get("modelUsage").and_then(|v| v.as_object()) {
+            for (model, usage) in model_usage {
+                let tokens = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+                cost.add(config.estimate_cost(model, TokenUsage {
+                    input: tokens("inputTokens"),
+                    output: tokens("outputTokens"),
+                    cache_creation: tokens("cacheCreationInputTokens"),
+                    cache_read: tokens("cacheReadInputTokens"),
+                }));
+            }
         }
+        extra.insert("estimated_cost_usd".to_string(), serde_json::json!(cost.total()));
+    }
+    event
+}
+
+/// 为会话进程登记事件出口，interrupt_chat 按 PID 找到它并关闭
+fn register_event_gate(state: &crate::AppState, pid: u32) -> EventGate {
+    let gate = EventGate::new();
+    if let Ok(mut gates) = state.event_gates.lock() {
+        gates.insert(pid, gate.clone());
+    }
+    gate
+}
+
+/// 读取线程结束（事件已全部发送）后移除进程的事件出口
+fn unregister_event_gate(gates: &Arc<Mutex<HashMap<u32, EventGate>>>, pid: u32) {
+    if let Ok(mut gates) = gates.lock() {
+        gates.remove(&pid);
     }
 }
 
+/// 创建会话事件记录器，按配置的缓冲大小缓冲该会话发送的事件
+fn session_event_recorder(state: &crate::AppState, config: &Config, session_id: &str) -> SessionEventRecorder {
+    SessionEventRecorder::new(&state.event_buffer, session_id, config.event_buffer_size)
+}
+
+/// 取出会话在后台缓冲的事件（按发送顺序），用于窗口重新打开后补取错过的事件
+///
+/// 会话 ID 可以是启动时返回的临时 ID，也可以是之后收到的真实会话 ID。取出后缓冲区被清空
+#[tauri::command]
+pub fn drain_session_events(
+    session_id: String,
+    state: State<'_, crate::AppState>,
+) -> Result<Vec<serde_json::Value>> {
+    let events = state.event_buffer.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?
+        .drain(&session_id);
+
+    tracing::info!("[drain_session_events] 会话 {} 取出 {} 个缓冲事件", session_id, events.len());
+    Ok(events.iter()
+        .filter_map(|json| serde_json::from_str(json).ok())
+        .collect())
+}
+
 /// 启动 Claude Code 聊天会话
+#[allow(clippy::too_many_arguments)]
 async fn start_claude_chat(
     config: &Config,
+    resume: ClaudeResume<'_>,
     message: &str,
     window: Window,
     state: State<'_, crate::AppState>,
-    system_prompt: Option<&str>,
+    slot: SessionSlot,
+    system_prompt: SystemPrompt<'_>,
+    context_id: Option<String>,
 ) -> Result<String> {
-    eprintln!("[start_claude_chat] 启动 Claude 会话");
+    tracing::info!("[start_claude_chat] 启动 Claude 会话");
 
     // 启动 Claude 会话
-    let session = ChatSession::start(config, message, system_prompt)?;
+    let session = ChatSession::spawn(config, resume, message, system_prompt)?
+        .with_session_registry(Arc::clone(&state.sessions));
 
     let session_id = session.id.clone();
     let window_clone = window.clone();
     let process_id = session.child.id();
 
-    eprintln!("[start_claude_chat] 临时会话 ID: {}, 进程 ID: {}", session_id, process_id);
+    tracing::info!("[start_claude_chat] 临时会话 ID: {}, 进程 ID: {}", session_id, process_id);
 
-    // 保存 PID 到全局 sessions
-    {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| crate::error::AppError::Unknown(e.to_string()))?;
-        sessions.insert(session_id.clone(), process_id);
-    }
+    // 保存 PID 到全局 sessions（替换预留的占位项）
+    slot.fill(&session_id, process_id)?;
 
     // 克隆 sessions Arc 以便在回调中使用
     let sessions_arc = Arc::clone(&state.sessions);
+    let sessions_release = Arc::clone(&state.sessions);
     let temp_session_id = session_id.clone();
+    let work_dir = config.work_dir.clone();
+    let recorder = session_event_recorder(&state, config, &session_id);
+    let alias_recorder = recorder.clone();
+    let gate = register_event_gate(&state, process_id);
+    let gates = Arc::clone(&state.event_gates);
+    let coalescer = chat_event_coalescer(window.clone(), config, recorder, gate, "start_claude_chat");
+    let events = coalescer.sender();
+    let pricing = config.clone();
+
+    // 恢复最近会话时无法预知会话 ID，需要等待输出流中的真实 ID 再返回
+    let (id_tx, id_rx) = std::sync::mpsc::channel::<String>();
+    let mut id_tx = matches!(resume, ClaudeResume::Latest).then_some(id_tx);
 
     // 在后台线程中读取输出
     std::thread::spawn(move || {
-        eprintln!("[start_claude_chat] 后台线程开始");
+        tracing::info!("[start_claude_chat] 后台线程开始");
         session.read_events(move |event| {
             // 检查是否收到真实的 session_id
             if let StreamEvent::System { extra, .. } = &event {
                 if let Some(serde_json::Value::String(real_session_id)) = extra.get("session_id") {
-                    eprintln!("[start_claude_chat] 收到真实 session_id: {}, 更新映射", real_session_id);
+                    tracing::info!("[start_claude_chat] 收到真实 session_id: {}, 更新映射", real_session_id);
 
                     if let Ok(mut sessions) = sessions_arc.lock() {
-                        if let Some(&pid) = sessions.get(&temp_session_id) {
+                        if let Some(&SessionProcess::Running(pid)) = sessions.get(&temp_session_id) {
                             sessions.remove(&temp_session_id);
-                            sessions.insert(real_session_id.clone(), pid);
-                            eprintln!("[start_claude_chat] 映射已更新: {} -> PID {}", real_session_id, pid);
+                            sessions.insert(real_session_id.clone(), SessionProcess::Running(pid));
+                            tracing::info!("[start_claude_chat] 映射已更新: {} -> PID {}", real_session_id, pid);
                         }
                     }
+                    alias_recorder.alias(real_session_id);
+
+                    remember_last_session(&window_clone, LastSession {
+                        engine_id: EngineId::ClaudeCode.as_str().to_string(),
+                        session_id: real_session_id.clone(),
+                        work_dir: work_dir.clone(),
+                        context_id: context_id.clone(),
+                    });
+
+                    if let Some(tx) = id_tx.take() {
+                        let _ = tx.send(real_session_id.clone());
+                    }
                 }
             }
 
-            let event_json = serde_json::to_string(&event)
-                .unwrap_or_else(|_| "{}".to_string());
-            eprintln!("[start_claude_chat] 发送事件: {}", event_json);
-            let _ = window_clone.emit("chat-event", event_json);
+            let _ = events.send(attach_estimated_cost(event, &pricing));
         });
-        eprintln!("[start_claude_chat] 后台线程结束");
+        coalescer.finish();
+        unregister_event_gate(&gates, process_id);
+        release_session(&sessions_release, process_id);
+        tracing::info!("[start_claude_chat] 后台线程结束");
     });
 
+    if matches!(resume, ClaudeResume::Latest) {
+        let real_session_id = tauri::async_runtime::spawn_blocking(move || id_rx.recv_timeout(SESSION_ID_WAIT_TIMEOUT))
+            .await
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+
+        return match real_session_id {
+            Ok(real_session_id) => Ok(real_session_id),
+            Err(_) => {
+                tracing::warn!("[start_claude_chat] 等待真实 session_id 超时，返回临时 ID");
+                Ok(session_id)
+            }
+        };
+    }
+
     Ok(session_id)
 }
 
+/// 在后台线程中逐行读取进程输出，连同输出流名称发送到通道
+fn spawn_line_reader<R>(reader: R, stream: &'static str, tx: std::sync::mpsc::Sender<(&'static str, String)>)
+where
+    R: std::io::Read + Send + 'static,
+{
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(std::result::Result::ok) {
+            if tx.send((stream, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// 等待 IFlow 输出会话 ID 的最长时间，超时后从会话目录检测
+const IFLOW_SESSION_ID_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// 超时后重新检测会话文件的间隔
+const IFLOW_SESSION_ID_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
 /// 启动 IFlow 聊天会话
 async fn start_iflow_chat_internal(
     config: &Config,
     message: &str,
     window: Window,
     state: State<'_, crate::AppState>,
+    slot: SessionSlot,
+    context_id: Option<String>,
 ) -> Result<String> {
-    eprintln!("[start_iflow_chat] 启动 IFlow 会话");
+    tracing::info!("[start_iflow_chat] 启动 IFlow 会话");
 
-    // 启动 IFlow 会话
+    // 启动 IFlow 会话（记录启动时间，用于兜底检测会话文件）
+    let started_at = std::time::SystemTime::now();
     let session = IFlowService::start_chat(config, message)?;
 
     let temp_session_id = session.id.clone();
@@ -469,105 +733,219 @@ async fn start_iflow_chat_internal(
     let window_clone = window.clone();
     let process_id = session.child.id();
 
-    eprintln!("[start_iflow_chat] 临时会话 ID: {}, 进程 ID: {:?}", temp_session_id, process_id);
+    tracing::info!("[start_iflow_chat] 临时会话 ID: {}, 进程 ID: {:?}", temp_session_id, process_id);
 
-    // 保存 PID 到全局 sessions
-    {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| AppError::Unknown(e.to_string()))?;
-        sessions.insert(temp_session_id.clone(), process_id);
-    }
+    // 保存 PID 到全局 sessions（替换预留的占位项）
+    slot.fill(&temp_session_id, process_id)?;
 
     let sessions_arc = Arc::clone(&state.sessions);
     let config_clone = config.clone();
+    let session_id_re = session_id_regex(config.iflow.session_id_pattern.as_deref());
+    let recorder = session_event_recorder(&state, config, &temp_session_id);
+    let gate = register_event_gate(&state, process_id);
+    let gates = Arc::clone(&state.event_gates);
+    let coalescer = chat_event_coalescer(window.clone(), config, recorder.clone(), gate.clone(), "iflow");
 
     // 启动后台线程监控进程
     std::thread::spawn(move || {
-        eprintln!("[start_iflow_chat] 后台线程开始");
+        tracing::info!("[start_iflow_chat] 后台线程开始");
 
         let temp_id = temp_session_id.clone();
         let mut session_id_found = false;
 
-        // 读取 stderr 以获取会话信息
+        // 获得真实会话 ID 后：更新映射、通知前端并开始监控 JSONL 文件
+        let handle_session_id = |id: String| {
+            tracing::info!("[start_iflow_chat] 找到 session_id: {}", id);
+
+            // 更新 sessions 映射
+            if let Ok(mut sessions) = sessions_arc.lock() {
+                sessions.remove(&temp_id);
+                sessions.insert(id.clone(), SessionProcess::Running(process_id));
+            }
+
+            remember_last_session(&window_clone, LastSession {
+                engine_id: EngineId::IFlow.as_str().to_string(),
+                session_id: id.clone(),
+                work_dir: config_clone.work_dir.clone(),
+                context_id: context_id.clone(),
+            });
+            recorder.alias(&id);
+
+            // 发送 session_id 到前端
+            // 注意：前端 chatStore 期望 event.session_id 在顶层，而不是 extra.session_id
+            let system_event = serde_json::json!({
+                "type": "system",
+                "session_id": id
+            }).to_string();
+            gate.emit(|| {
+                recorder.record(&system_event);
+                let _ = window_clone.emit("chat-event", &system_event);
+            });
+
+            // 查找 JSONL 文件并启动监控
+            match IFlowService::find_session_jsonl(&config_clone, &id) {
+                Ok(jsonl_path) => {
+                    tracing::info!("[start_iflow_chat] 找到 JSONL 文件: {:?}", jsonl_path);
+
+                    let sessions_arc_clone = Arc::clone(&sessions_arc);
+                    let id_clone = id.clone();
+                    let events = coalescer.sender();
+
+                    // 第一次启动会话，从头开始读取（start_line = 0）
+                    IFlowService::monitor_jsonl_file(
+                        jsonl_path,
+                        id_clone.clone(),
+                        move |event| {
+                            if matches!(event, StreamEvent::SessionEnd) {
+                                if let Ok(mut sessions) = sessions_arc_clone.lock() {
+                                    sessions.remove(&id_clone);
+                                }
+                            }
+
+                            let _ = events.send(event);
+                        },
+                        0, // start_line: 从头开始
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!("[start_iflow_chat] 查找 JSONL 文件失败: {:?}", e);
+                }
+            }
+        };
+
+        // 在独立线程中读取 stdout 和 stderr，以便等待会话 ID 时可以超时
+        // （实际内容从 JSONL 文件读取，stdout 也需要持续读取以免管道写满阻塞进程）
         let mut child = session.child;
+        let (line_tx, line_rx) = std::sync::mpsc::channel::<(&'static str, String)>();
+        if let Some(stdout) = child.stdout.take() {
+            spawn_line_reader(stdout, "stdout", line_tx.clone());
+        }
         if let Some(stderr) = child.stderr.take() {
-            let reader = BufReader::new(stderr);
-
-            for line in reader.lines() {
-                if let Ok(line_text) = line {
-                    eprintln!("[iflow stderr] {}", line_text);
+            spawn_line_reader(stderr, "stderr", line_tx.clone());
+        }
+        drop(line_tx);
 
-                    if !session_id_found {
-                        if let Some(id) = extract_session_id(&line_text) {
-                            eprintln!("[start_iflow_chat] 找到 session_id: {}", id);
+        let deadline = std::time::Instant::now() + IFLOW_SESSION_ID_TIMEOUT;
+        loop {
+            // 已找到会话 ID 后只需阻塞读取剩余输出
+            let received = if session_id_found {
+                line_rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                let wait = deadline
+                    .saturating_duration_since(std::time::Instant::now())
+                    .max(IFLOW_SESSION_ID_POLL_INTERVAL);
+                line_rx.recv_timeout(wait)
+            };
 
-                            // 更新 sessions 映射
-                            if let Ok(mut sessions) = sessions_arc.lock() {
-                                sessions.remove(&temp_id);
-                                sessions.insert(id.clone(), process_id);
-                            }
+            match received {
+                Ok((stream, line_text)) => {
+                    tracing::debug!("[iflow {}] {}", stream, line_text);
 
+                    if !session_id_found {
+                        if let Some(id) = extract_session_id(&session_id_re, &line_text) {
                             session_id_found = true;
-
-                            // 发送 session_id 到前端
-                            // 注意：前端 chatStore 期望 event.session_id 在顶层，而不是 extra.session_id
-                            let _ = window_clone.emit("chat-event", serde_json::json!({
-                                "type": "system",
-                                "session_id": id
-                            }).to_string());
-
-                            // 查找 JSONL 文件并启动监控
-                            match IFlowService::find_session_jsonl(&config_clone, &id) {
-                                Ok(jsonl_path) => {
-                                    eprintln!("[start_iflow_chat] 找到 JSONL 文件: {:?}", jsonl_path);
-
-                                let sessions_arc_clone = Arc::clone(&sessions_arc);
-                                let id_clone = id.clone();
-                                let window_clone2 = window_clone.clone();
-                                let config_clone2 = config_clone.clone();
-
-                                // 第一次启动会话，从头开始读取（start_line = 0）
-                                IFlowService::monitor_jsonl_file(
-                                    jsonl_path,
-                                    id_clone.clone(),
-                                    move |event| {
-                                        let event_json = serde_json::to_string(&event)
-                                            .unwrap_or_else(|_| "{}".to_string());
-                                        eprintln!("[iflow] 发送事件: {}", event_json);
-                                        let _ = window_clone2.emit("chat-event", event_json);
-
-                                        if matches!(event, StreamEvent::SessionEnd) {
-                                            if let Ok(mut sessions) = sessions_arc_clone.lock() {
-                                                sessions.remove(&id_clone);
-                                            }
-                                        }
-                                    },
-                                    0, // start_line: 从头开始
-                                );
-                                }
-                                Err(e) => {
-                                    eprintln!("[start_iflow_chat] 查找 JSONL 文件失败: {:?}", e);
-                                }
-                            }
+                            handle_session_id(id);
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // 超时仍未从 stderr 解析出会话 ID，改为从会话目录检测
+                    if std::time::Instant::now() >= deadline {
+                        if let Some(id) = IFlowService::detect_latest_session_id(&config_clone, started_at) {
+                            tracing::info!("[start_iflow_chat] stderr 未提供会话 ID，从会话文件检测到: {}", id);
+                            session_id_found = true;
+                            handle_session_id(id);
                         }
                     }
                 }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        // stderr 已关闭仍未获得会话 ID 时最后检测一次
+        if !session_id_found {
+            match IFlowService::detect_latest_session_id(&config_clone, started_at) {
+                Some(id) => handle_session_id(id),
+                None => tracing::warn!("[start_iflow_chat] 未能获取 IFlow 会话 ID"),
             }
         }
 
         // 等待进程结束
         let _ = child.wait();
+        release_session(&sessions_arc, process_id);
 
-        eprintln!("[start_iflow_chat] 后台线程结束");
+        // 等待文件监控结束并发送剩余事件
+        coalescer.finish();
+        unregister_event_gate(&gates, process_id);
+        tracing::info!("[start_iflow_chat] 后台线程结束");
     });
 
     Ok(return_session_id)
 }
 
+/// Claude Code 限制单次回复最大输出 Token 数的环境变量
+const CLAUDE_MAX_OUTPUT_TOKENS_ENV: &str = "CLAUDE_CODE_MAX_OUTPUT_TOKENS";
+
+/// 应用本次会话的生成参数
+///
+/// - `max_tokens`：Claude Code 通过 `CLAUDE_CODE_MAX_OUTPUT_TOKENS` 环境变量支持，IFlow 不支持
+/// - `temperature`：两个 CLI 均未提供设置方式
+///
+/// 不支持的参数记录警告并忽略，不会导致会话启动失败
+fn apply_generation_overrides(cfg: &mut Config, engine: EngineId, temperature: Option<f32>, max_tokens: Option<u32>) {
+    if let Some(temperature) = temperature {
+        tracing::warn!("[apply_generation_overrides] {:?} 引擎不支持设置 temperature，已忽略: {}", engine, temperature);
+    }
+
+    match (engine, max_tokens) {
+        (_, None) => {}
+        (_, Some(0)) => {
+            tracing::warn!("[apply_generation_overrides] max_tokens 为 0，已忽略");
+        }
+        (EngineId::ClaudeCode, Some(max_tokens)) => {
+            tracing::info!("[apply_generation_overrides] 设置 {}={}", CLAUDE_MAX_OUTPUT_TOKENS_ENV, max_tokens);
+            cfg.env.insert(CLAUDE_MAX_OUTPUT_TOKENS_ENV.to_string(), max_tokens.to_string());
+        }
+        (EngineId::IFlow, Some(max_tokens)) => {
+            tracing::warn!("[apply_generation_overrides] IFlow 引擎不支持设置 max_tokens，已忽略: {}", max_tokens);
+        }
+    }
+}
+
+/// 应用本次调用指定的模型与审批模式
+///
+/// 模型两个引擎均支持（`--model`）；审批模式仅 IFlow 支持，Claude Code 引擎下只记录警告并忽略
+fn apply_engine_overrides(
+    cfg: &mut Config,
+    engine: EngineId,
+    model: Option<String>,
+    approval_mode: Option<String>,
+) {
+    match engine {
+        EngineId::IFlow => {
+            if model.is_some() {
+                cfg.iflow.model = model;
+            }
+            if approval_mode.is_some() {
+                cfg.iflow.approval_mode = approval_mode;
+            }
+        }
+        EngineId::ClaudeCode => {
+            if model.is_some() {
+                cfg.claude_code.model = model;
+            }
+            if let Some(approval_mode) = approval_mode {
+                tracing::warn!("[apply_engine_overrides] Claude Code 引擎不支持指定审批模式，已忽略: {}", approval_mode);
+            }
+        }
+    }
+}
+
 /// 继续聊天会话
 ///
 /// 统一接口，根据 engine_id 参数选择具体的 AI 引擎实现
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn continue_chat(
     session_id: String,
     message: String,
@@ -576,11 +954,15 @@ pub async fn continue_chat(
     work_dir: Option<String>,
     engine_id: Option<String>,
     system_prompt: Option<String>,
+    append_system_prompt: Option<String>,
+    context_id: Option<String>,
+    model: Option<String>,
+    approval_mode: Option<String>,
 ) -> Result<()> {
-    eprintln!("[continue_chat] 继续会话: {}", session_id);
-    eprintln!("[continue_chat] 消息长度: {} 字符", message.len());
+    tracing::info!("[continue_chat] 继续会话: {}", session_id);
+    tracing::info!("[continue_chat] 消息长度: {} 字符", message.len());
     if let Some(ref prompt) = system_prompt {
-        eprintln!("[continue_chat] 系统提示词长度: {} 字符", prompt.len());
+        tracing::info!("[continue_chat] 系统提示词长度: {} 字符", prompt.len());
     }
 
     // 从 AppState 获取实际配置（在独立作用域中，确保 MutexGuard 在 await 前释放）
@@ -592,7 +974,7 @@ pub async fn continue_chat(
         // 如果传入了 work_dir 参数，优先使用它而不是配置中的
         if let Some(ref work_dir_str) = work_dir {
             let work_dir_path = PathBuf::from(work_dir_str);
-            eprintln!("[continue_chat] 使用传入的工作目录: {:?}", work_dir_path);
+            tracing::info!("[continue_chat] 使用传入的工作目录: {:?}", work_dir_path);
             cfg.work_dir = Some(work_dir_path);
         }
 
@@ -601,116 +983,360 @@ pub async fn continue_chat(
         let engine = EngineId::from_str(&engine_id_str)
             .unwrap_or(EngineId::ClaudeCode);
 
-        eprintln!("[continue_chat] 使用引擎: {:?}", engine);
+        tracing::info!("[continue_chat] 使用引擎: {:?}", engine);
+
+        apply_engine_overrides(&mut cfg, engine, model, approval_mode);
 
         (cfg, engine)
     }; // MutexGuard 在此处释放
 
+    let slot = reserve_session_slot(&state.sessions, config.max_concurrent_sessions, Some(&session_id))?;
+
+    let window_handle = window.clone();
     match engine {
         EngineId::ClaudeCode => {
-            continue_claude_chat(&config, &session_id, &message, window, state, system_prompt.as_deref()).await
+            let system_prompt = SystemPrompt::new(system_prompt.as_deref(), append_system_prompt.as_deref());
+            continue_claude_chat(&config, &session_id, &message, window, state, slot, system_prompt).await?
         }
         EngineId::IFlow => {
-            continue_iflow_chat_internal(&config, &session_id, &message, window, state).await
+            continue_iflow_chat_internal(&config, &session_id, &message, window, state, slot).await?
         }
     }
-}
 
-/// 继续 Claude Code 聊天会话
-async fn continue_claude_chat(
-    config: &Config,
-    session_id: &str,
-    message: &str,
-    window: Window,
-    state: State<'_, crate::AppState>,
-    system_prompt: Option<&str>,
-) -> Result<()> {
-    eprintln!("[continue_claude_chat] 继续 Claude 会话: {}", session_id);
+    remember_last_session(&window_handle, LastSession {
+        engine_id: engine.as_str().to_string(),
+        session_id,
+        work_dir: config.work_dir.clone(),
+        context_id,
+    });
 
-    // 如果已存在旧进程，先尝试终止它
-    let old_pid = {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| crate::error::AppError::Unknown(e.to_string()))?;
-        sessions.remove(session_id)
-    };
+    Ok(())
+}
 
-    if let Some(pid) = old_pid {
-        eprintln!("[continue_claude_chat] 发现旧进程 PID: {}, 尝试终止", pid);
-        terminate_process(pid);
+/// 进程退出后从 sessions 中移除对应的映射
+///
+/// 按 PID 匹配而不是会话 ID：会话 ID 可能已从临时 ID 更新为真实 ID，
+/// 且 continue 时同一会话 ID 可能已映射到新进程
+fn release_session(sessions: &Arc<Mutex<HashMap<String, SessionProcess>>>, pid: u32) {
+    if let Ok(mut sessions) = sessions.lock() {
+        sessions.retain(|_, p| *p != SessionProcess::Running(pid));
     }
+}
 
-    // 根据平台构建命令
-    #[cfg(windows)]
-    let mut cmd = {
-        let claude_cmd = config.get_claude_cmd();
-        let (node_exe, cli_js) = resolve_node_and_cli(&claude_cmd)?;
-        build_node_command_resume(&node_exe, &cli_js, session_id, message, system_prompt)
-    };
+/// 会话映射中的进程状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionProcess {
+    /// 已预留名额，进程尚未启动
+    Pending,
+    /// 进程已启动
+    Running(u32),
+}
 
-    #[cfg(not(windows))]
-    let mut cmd = {
-        let claude_cmd = config.get_claude_cmd();
-        let mut c = Command::new(&claude_cmd)
-            .arg("--resume")
-            .arg(session_id);
-        // 添加 system-prompt 参数（如果有）
-        if let Some(prompt) = system_prompt {
-            if !prompt.is_empty() {
-                c.arg("--system-prompt").arg(prompt);
-            }
-        }
-        c.arg("--print")
-            .arg("--verbose")
-            .arg("--output-format")
-            .arg("stream-json")
-            .arg("--permission-mode")
-            .arg("bypassPermissions")
-            .arg(message)
-    };
+/// 预留的活动会话名额
+///
+/// 检查上限时同时在 sessions 中插入 `Pending` 占位项，并发启动的会话不会同时通过检查。
+/// 进程启动后通过 `fill` 替换为真实映射，启动失败时占位项随 guard 一起移除
+struct SessionSlot {
+    sessions: Arc<Mutex<HashMap<String, SessionProcess>>>,
+    placeholder: Option<String>,
+}
 
-    cmd.stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+impl SessionSlot {
+    /// 将占位项替换为会话 ID 到进程 PID 的映射
+    fn fill(mut self, session_id: &str, pid: u32) -> Result<()> {
+        let mut sessions = self.sessions.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        if let Some(placeholder) = self.placeholder.take() {
+            sessions.remove(&placeholder);
+        }
+        sessions.insert(session_id.to_string(), SessionProcess::Running(pid));
+        Ok(())
+    }
+}
 
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        if let Some(placeholder) = self.placeholder.take() {
+            if let Ok(mut sessions) = self.sessions.lock() {
+                sessions.remove(&placeholder);
+            }
+        }
+    }
+}
 
-    if let Some(ref work_dir) = config.work_dir {
-        eprintln!("[continue_claude_chat] work_dir: {:?}", work_dir);
-        cmd.current_dir(work_dir);
+/// 检查活动会话数是否已达上限，未达上限时预留一个名额
+///
+/// `exclude` 为即将被替换的会话（continue 时旧进程会被终止），不计入活动数
+fn reserve_session_slot(
+    sessions: &Arc<Mutex<HashMap<String, SessionProcess>>>,
+    max_concurrent_sessions: usize,
+    exclude: Option<&str>,
+) -> Result<SessionSlot> {
+    let mut guard = sessions.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    let active = guard.keys()
+        .filter(|id| Some(id.as_str()) != exclude)
+        .count();
+
+    if active >= max_concurrent_sessions {
+        tracing::warn!("[reserve_session_slot] 活动会话已达上限: {}/{}", active, max_concurrent_sessions);
+        return Err(AppError::ProcessError(format!(
+            "活动会话过多（too many active sessions）: {}/{}，请等待其他会话结束或中断后重试",
+            active, max_concurrent_sessions
+        )));
     }
 
-    if let Some(ref git_bash_path) = config.git_bin_path {
-        eprintln!("[continue_claude_chat] 设置 CLAUDE_CODE_GIT_BASH_PATH: {}", git_bash_path);
-        cmd.env("CLAUDE_CODE_GIT_BASH_PATH", git_bash_path);
+    let placeholder = format!("pending-{}", Uuid::new_v4());
+    guard.insert(placeholder.clone(), SessionProcess::Pending);
+
+    Ok(SessionSlot {
+        sessions: Arc::clone(sessions),
+        placeholder: Some(placeholder),
+    })
+}
+
+/// 会话容量信息
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCapacity {
+    pub active: usize,
+    pub max: usize,
+}
+
+/// 进程已启动的会话数，预留中的名额不计入
+fn running_session_count(sessions: &HashMap<String, SessionProcess>) -> usize {
+    sessions.values()
+        .filter(|p| matches!(p, SessionProcess::Running(_)))
+        .count()
+}
+
+/// 获取当前活动会话数与上限
+#[tauri::command]
+pub async fn get_session_capacity(
+    state: State<'_, crate::AppState>,
+) -> Result<SessionCapacity> {
+    let max = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        config_store.get().max_concurrent_sessions
+    };
+    let sessions = state.sessions.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    let active = running_session_count(&sessions);
+
+    Ok(SessionCapacity { active, max })
+}
+
+/// 记录最近一次会话到配置，供下次启动时恢复
+fn remember_last_session(window: &Window, last_session: LastSession) {
+    let state = window.state::<crate::AppState>();
+    let result = match state.config_store.lock() {
+        Ok(mut config_store) => config_store.set_last_session(last_session),
+        Err(e) => Err(AppError::Unknown(e.to_string())),
+    };
+
+    if let Err(e) = result {
+        tracing::warn!("[remember_last_session] 保存最近会话失败: {:?}", e);
     }
+}
 
-    eprintln!("[continue_claude_chat] 执行命令: {:?}", cmd);
+/// 继续工作目录下最近一次会话（无需指定会话 ID）
+///
+/// Claude Code 使用 `--continue`，IFlow 使用最新会话文件对应的 `--resume`。
+/// 返回实际恢复的会话 ID
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn continue_latest_chat(
+    message: String,
+    window: Window,
+    state: State<'_, crate::AppState>,
+    work_dir: Option<String>,
+    engine_id: Option<String>,
+    system_prompt: Option<String>,
+    append_system_prompt: Option<String>,
+    context_id: Option<String>,
+) -> Result<String> {
+    tracing::info!("[continue_latest_chat] 继续最近会话，消息长度: {} 字符", message.len());
 
-    let child = cmd.spawn()
-        .map_err(|e| AppError::ProcessError(format!("继续 Claude 会话失败: {}", e)))?;
+    // 从 AppState 获取实际配置（在独立作用域中，确保 MutexGuard 在 await 前释放）
+    let (config, engine) = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        let mut cfg = config_store.get().clone();
+
+        if let Some(ref work_dir_str) = work_dir {
+            cfg.work_dir = Some(PathBuf::from(work_dir_str));
+        }
+
+        let engine_id_str = engine_id.unwrap_or_else(|| cfg.default_engine.clone());
+        let engine = EngineId::from_str(&engine_id_str)
+            .unwrap_or(EngineId::ClaudeCode);
+
+        tracing::info!("[continue_latest_chat] 使用引擎: {:?}, 工作目录: {:?}", engine, cfg.work_dir);
+
+        (cfg, engine)
+    }; // MutexGuard 在此处释放
+
+    let slot = reserve_session_slot(&state.sessions, config.max_concurrent_sessions, None)?;
+
+    match engine {
+        EngineId::ClaudeCode => {
+            let system_prompt = SystemPrompt::new(system_prompt.as_deref(), append_system_prompt.as_deref());
+            start_claude_chat(&config, ClaudeResume::Latest, &message, window, state, slot, system_prompt, context_id).await
+        }
+        EngineId::IFlow => {
+            let session_id = IFlowService::latest_session_id(&config)?;
+            tracing::info!("[continue_latest_chat] IFlow 最近会话: {}", session_id);
+
+            let window_handle = window.clone();
+            continue_iflow_chat_internal(&config, &session_id, &message, window, state, slot).await?;
+
+            remember_last_session(&window_handle, LastSession {
+                engine_id: engine.as_str().to_string(),
+                session_id: session_id.clone(),
+                work_dir: config.work_dir.clone(),
+                context_id,
+            });
+
+            Ok(session_id)
+        }
+    }
+}
+
+/// 重新生成最后一次回复：重新发送会话中最后一条用户消息
+///
+/// Claude Code 与 IFlow 的会话保存在 CLI 的会话文件中，无法删除已有回复，
+/// 因此会在会话末尾追加一轮新的对话，而不是替换上一条回复。返回继续的会话 ID
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn regenerate_last(
+    session_id: String,
+    window: Window,
+    state: State<'_, crate::AppState>,
+    work_dir: Option<String>,
+    engine_id: Option<String>,
+    system_prompt: Option<String>,
+    append_system_prompt: Option<String>,
+    context_id: Option<String>,
+    model: Option<String>,
+    approval_mode: Option<String>,
+) -> Result<String> {
+    tracing::info!("[regenerate_last] 重新生成会话最后一次回复: {}", session_id);
+
+    let (config, engine) = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        let mut cfg = config_store.get().clone();
+        if let Some(ref work_dir_str) = work_dir {
+            cfg.work_dir = Some(PathBuf::from(work_dir_str));
+        }
+
+        let engine = EngineId::from_str(engine_id.as_deref().unwrap_or(&cfg.default_engine))
+            .unwrap_or(EngineId::ClaudeCode);
+        (cfg, engine)
+    };
+
+    let prompt = match engine {
+        EngineId::ClaudeCode => {
+            let project_path = config.work_dir.as_ref().map(|p| p.to_string_lossy().to_string());
+            let history = get_claude_code_session_history(session_id.clone(), project_path).await?;
+            history.iter()
+                .rev()
+                .filter(|m| m.role == "user")
+                .map(|m| claude_message_text(&m.content))
+                .find(|text| !text.trim().is_empty())
+        }
+        EngineId::IFlow => {
+            IFlowService::get_session_history(&config, &session_id)?
+                .into_iter()
+                .rev()
+                .filter(|m| m.r#type == "user")
+                .map(|m| m.content)
+                .find(|text| !text.trim().is_empty())
+        }
+    };
+
+    let prompt = prompt.ok_or_else(|| {
+        AppError::ProcessError(format!("会话中没有可重新发送的用户消息: {}", session_id))
+    })?;
+    tracing::info!("[regenerate_last] 重新发送用户消息，长度: {} 字符", prompt.len());
+
+    continue_chat(
+        session_id.clone(),
+        prompt,
+        window,
+        state,
+        work_dir,
+        Some(engine.as_str().to_string()),
+        system_prompt,
+        append_system_prompt,
+        context_id,
+        model,
+        approval_mode,
+    ).await?;
+
+    Ok(session_id)
+}
+
+/// 提取 Claude Code 消息中的文本内容（忽略工具调用结果等非文本块）
+fn claude_message_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks.iter()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 继续 Claude Code 聊天会话
+async fn continue_claude_chat(
+    config: &Config,
+    session_id: &str,
+    message: &str,
+    window: Window,
+    state: State<'_, crate::AppState>,
+    slot: SessionSlot,
+    system_prompt: SystemPrompt<'_>,
+) -> Result<()> {
+    tracing::info!("[continue_claude_chat] 继续 Claude 会话: {}", session_id);
+
+    // 如果已存在旧进程，先终止它
+    terminate_previous_process(&state.sessions, session_id, "continue_claude_chat").await?;
+
+    let child = spawn_claude(config, ClaudeResume::Session(session_id), message, system_prompt)?;
 
     let new_pid = child.id();
     let window_clone = window.clone();
     let session_id_owned = session_id.to_string();
+    let emit_raw_events = config.emit_raw_events;
 
-    eprintln!("[continue_claude_chat] 新进程 PID: {}", new_pid);
+    tracing::info!("[continue_claude_chat] 新进程 PID: {}", new_pid);
 
-    {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| crate::error::AppError::Unknown(e.to_string()))?;
-        sessions.insert(session_id_owned.clone(), new_pid);
-    }
+    slot.fill(&session_id_owned, new_pid)?;
+
+    let sessions_arc = Arc::clone(&state.sessions);
+    let recorder = session_event_recorder(&state, config, session_id);
+    let gate = register_event_gate(&state, new_pid);
+    let gates = Arc::clone(&state.event_gates);
+    let coalescer = chat_event_coalescer(window_clone, config, recorder, gate, "continue_claude_chat");
+    let pricing = config.clone();
 
     std::thread::spawn(move || {
-        eprintln!("[continue_claude_chat] 后台线程开始");
-        let session = ChatSession::with_id_and_child(session_id_owned, child);
+        tracing::info!("[continue_claude_chat] 后台线程开始");
+        let session = ChatSession::with_id_and_child(session_id_owned, child)
+            .with_raw_events(emit_raw_events)
+            .with_session_registry(Arc::clone(&sessions_arc));
+        let events = coalescer.sender();
         session.read_events(move |event| {
-            let event_json = serde_json::to_string(&event)
-                .unwrap_or_else(|_| "{}".to_string());
-            eprintln!("[continue_claude_chat] 发送事件: {}", event_json);
-            let _ = window_clone.emit("chat-event", event_json);
+            let _ = events.send(attach_estimated_cost(event, &pricing));
         });
-        eprintln!("[continue_claude_chat] 后台线程结束");
+        coalescer.finish();
+        unregister_event_gate(&gates, new_pid);
+        release_session(&sessions_arc, new_pid);
+        tracing::info!("[continue_claude_chat] 后台线程结束");
     });
 
     Ok(())
@@ -723,106 +1349,174 @@ async fn continue_iflow_chat_internal(
     message: &str,
     window: Window,
     state: State<'_, crate::AppState>,
+    slot: SessionSlot,
 ) -> Result<()> {
-    eprintln!("[continue_iflow_chat] 继续 IFlow 会话: {}", session_id);
+    tracing::info!("[continue_iflow_chat] 继续 IFlow 会话: {}", session_id);
 
-    let old_pid = {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| AppError::Unknown(e.to_string()))?;
-        sessions.remove(session_id)
-    };
-
-    if let Some(pid) = old_pid {
-        eprintln!("[continue_iflow_chat] 发现旧进程 PID: {:?}, 尝试终止", pid);
-        terminate_process(pid);
-    }
+    terminate_previous_process(&state.sessions, session_id, "continue_iflow_chat").await?;
 
     let mut child = IFlowService::continue_chat(config, session_id, message)?;
     let new_pid = child.id();
 
-    eprintln!("[continue_iflow_chat] 新进程 PID: {:?}", new_pid);
+    tracing::info!("[continue_iflow_chat] 新进程 PID: {:?}", new_pid);
 
     let session_id_owned = session_id.to_string();
-    {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| AppError::Unknown(e.to_string()))?;
-        sessions.insert(session_id_owned.clone(), new_pid);
-    }
+    slot.fill(&session_id_owned, new_pid)?;
 
     let sessions_arc = Arc::clone(&state.sessions);
-    let window_clone = window.clone();
+    let sessions_release = Arc::clone(&state.sessions);
     let config_clone = config.clone();
+    let recorder = session_event_recorder(&state, config, session_id);
+    let gate = register_event_gate(&state, new_pid);
+    let gates = Arc::clone(&state.event_gates);
+    let coalescer = chat_event_coalescer(window, config, recorder, gate, "iflow");
 
     std::thread::spawn(move || {
-        eprintln!("[continue_iflow_chat] 后台线程开始");
+        tracing::info!("[continue_iflow_chat] 后台线程开始");
 
         if let Ok(jsonl_path) = IFlowService::find_session_jsonl(&config_clone, &session_id_owned) {
             // 获取当前文件行数，从下一行开始读取，避免重复发送已有内容
             let start_line = IFlowService::get_jsonl_line_count(&jsonl_path).unwrap_or(0);
-            eprintln!("[continue_iflow_chat] 当前文件有 {} 行，从第 {} 行开始读取", start_line, start_line);
+            tracing::info!("[continue_iflow_chat] 当前文件有 {} 行，从第 {} 行开始读取", start_line, start_line);
 
             let session_id_clone = session_id_owned.clone();
+            let events = coalescer.sender();
             IFlowService::monitor_jsonl_file(
                 jsonl_path,
                 session_id_clone.clone(),
                 move |event| {
-                    let event_json = serde_json::to_string(&event)
-                        .unwrap_or_else(|_| "{}".to_string());
-                    eprintln!("[iflow] 发送事件: {}", event_json);
-                    let _ = window_clone.emit("chat-event", event_json);
-
                     if matches!(event, StreamEvent::SessionEnd) {
                         if let Ok(mut sessions) = sessions_arc.lock() {
                             sessions.remove(&session_id_clone);
                         }
                     }
+
+                    let _ = events.send(event);
                 },
                 start_line, // 从当前行数开始，跳过已有内容
             );
         }
 
         let _ = child.wait();
+        release_session(&sessions_release, new_pid);
 
-        eprintln!("[continue_iflow_chat] 后台线程结束");
+        // 等待文件监控结束并发送剩余事件
+        coalescer.finish();
+        unregister_event_gate(&gates, new_pid);
+        tracing::info!("[continue_iflow_chat] 后台线程结束");
     });
 
     Ok(())
 }
 
+/// 确认进程退出的最长等待时间
+const TERMINATE_CONFIRM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Unix 下发送 TERM 后等待优雅退出的时间，超时后升级为 KILL
+#[cfg(not(windows))]
+const TERMINATE_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// 轮询进程状态的间隔
+const TERMINATE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// 检查进程是否仍在运行
+fn is_process_alive(pid: u32) -> bool {
+    #[cfg(windows)]
+    {
+        use std::process::Command;
+        let output = Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output();
+
+        match output {
+            Ok(output) => String::from_utf8_lossy(&output.stdout)
+                .contains(&format!("\"{}\"", pid)),
+            Err(_) => false,
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        use std::process::Command;
+        // 使用 ps 而不是 kill -0：未被回收的僵尸进程也视为已退出
+        let output = Command::new("ps")
+            .args(["-o", "stat=", "-p", &pid.to_string()])
+            .output();
+
+        match output {
+            Ok(output) => {
+                let stat = String::from_utf8_lossy(&output.stdout);
+                let stat = stat.trim();
+                !stat.is_empty() && !stat.starts_with('Z')
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// 在超时时间内等待进程退出，返回进程是否已退出
+fn wait_for_exit(pid: u32, timeout: std::time::Duration) -> bool {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        if !is_process_alive(pid) {
+            return true;
+        }
+        if std::time::Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(TERMINATE_POLL_INTERVAL);
+    }
+}
+
 /// 终止指定进程（包括其子进程）
-fn terminate_process(pid: u32) {
+///
+/// 返回进程是否已确认退出
+fn terminate_process(pid: u32) -> bool {
     #[cfg(windows)]
     {
         use std::process::Command;
         // 使用 /T 参数终止进程树
         let result = Command::new("taskkill")
             .args(["/F", "/T", "/PID", &pid.to_string()])
+            .creation_flags(CREATE_NO_WINDOW)
             .output();
 
         match result {
             Ok(output) => {
                 if output.status.success() {
-                    eprintln!("[terminate_process] 成功终止进程树: {}", pid);
+                    tracing::info!("[terminate_process] 已发送终止进程树: {}", pid);
                 } else {
-                    eprintln!("[terminate_process] 终止进程失败: {}", String::from_utf8_lossy(&output.stderr));
+                    tracing::warn!("[terminate_process] 终止进程失败: {}", String::from_utf8_lossy(&output.stderr));
                 }
             }
             Err(e) => {
-                eprintln!("[terminate_process] 执行 taskkill 命令失败: {}", e);
+                tracing::warn!("[terminate_process] 执行 taskkill 命令失败: {}", e);
             }
         }
+
+        let exited = wait_for_exit(pid, TERMINATE_CONFIRM_TIMEOUT);
+        if exited {
+            tracing::info!("[terminate_process] 进程已退出: {}", pid);
+        } else {
+            tracing::warn!("[terminate_process] 进程在超时后仍未退出: {}", pid);
+        }
+        exited
     }
 
     #[cfg(not(windows))]
     {
         use std::process::Command;
-        // Unix-like: 先尝试正常终止，等待后强制终止
+        // Unix-like: 先尝试正常终止，超时未退出再强制终止
         let _ = Command::new("kill")
             .arg("-TERM")
             .arg(pid.to_string())
             .output();
 
-        std::thread::sleep(std::time::Duration::from_millis(500));
+        if wait_for_exit(pid, TERMINATE_GRACE_PERIOD) {
+            tracing::info!("[terminate_process] 进程已正常退出: {}", pid);
+            return true;
+        }
 
         let result = Command::new("kill")
             .args(["-9", &pid.to_string()])
@@ -831,49 +1525,168 @@ fn terminate_process(pid: u32) {
         match result {
             Ok(output) => {
                 if output.status.success() {
-                    eprintln!("[terminate_process] 成功终止进程: {}", pid);
+                    tracing::info!("[terminate_process] 已发送 KILL 信号: {}", pid);
                 } else {
-                    eprintln!("[terminate_process] 终止进程失败: {}", String::from_utf8_lossy(&output.stderr));
+                    tracing::warn!("[terminate_process] 终止进程失败: {}", String::from_utf8_lossy(&output.stderr));
                 }
             }
             Err(e) => {
-                eprintln!("[terminate_process] 执行 kill 命令失败: {}", e);
+                tracing::warn!("[terminate_process] 执行 kill 命令失败: {}", e);
             }
         }
+
+        let exited = wait_for_exit(pid, TERMINATE_CONFIRM_TIMEOUT.saturating_sub(TERMINATE_GRACE_PERIOD));
+        if exited {
+            tracing::info!("[terminate_process] 进程已强制终止: {}", pid);
+        } else {
+            tracing::warn!("[terminate_process] 进程在超时后仍未退出: {}", pid);
+        }
+        exited
     }
 }
 
+/// 在阻塞线程池中终止进程（轮询等待退出会阻塞），返回进程是否已确认退出
+async fn terminate_process_blocking(pid: u32) -> Result<bool> {
+    tauri::async_runtime::spawn_blocking(move || terminate_process(pid))
+        .await
+        .map_err(|e| AppError::Unknown(e.to_string()))
+}
+
+/// 继续会话前终止该会话仍在运行的旧进程
+///
+/// 旧进程未能退出时放回映射并返回错误，避免新旧进程同时写入同一会话
+async fn terminate_previous_process(
+    sessions: &Arc<Mutex<HashMap<String, SessionProcess>>>,
+    session_id: &str,
+    log_tag: &str,
+) -> Result<()> {
+    let old_pid = {
+        let mut sessions = sessions.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        sessions.remove(session_id)
+    };
+
+    let pid = match old_pid {
+        Some(SessionProcess::Running(pid)) => pid,
+        Some(SessionProcess::Pending) | None => return Ok(()),
+    };
+
+    tracing::info!("[{}] 发现旧进程 PID: {}, 尝试终止", log_tag, pid);
+    if terminate_process_blocking(pid).await? {
+        return Ok(());
+    }
+
+    if let Ok(mut sessions) = sessions.lock() {
+        sessions.insert(session_id.to_string(), SessionProcess::Running(pid));
+    }
+    Err(AppError::ProcessError(format!(
+        "会话 {} 的旧进程 {} 未能在超时时间内退出，请稍后重试",
+        session_id, pid
+    )))
+}
+
 /// 中断聊天会话
+///
+/// 终止进程并确认其已退出，随后向前端发送 reason 为 "interrupted" 的 session_end 事件。
+/// 该事件经由会话的事件出口发送并关闭出口，读取线程之后才发出的事件会被丢弃
 #[tauri::command]
 pub async fn interrupt_chat(
     session_id: String,
+    window: Window,
     state: tauri::State<'_, crate::AppState>,
 ) -> Result<()> {
-    eprintln!("[interrupt_chat] 中断会话: {}", session_id);
+    tracing::info!("[interrupt_chat] 中断会话: {}", session_id);
+
+    // 与其他聊天事件一样写入会话缓冲，窗口重新打开后也能取到中断事件
+    let recorder = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        session_event_recorder(&state, config_store.get(), &session_id)
+    };
 
+    interrupt_session(&state.sessions, &state.event_gates, &session_id, |event_json| {
+        recorder.record(event_json);
+        let _ = window.emit("chat-event", event_json);
+    }).await
+}
+
+/// 从会话映射中移除并终止会话进程，确认退出后经由其事件出口调用 `emit` 发送 session_end 事件
+///
+/// 进程未能退出时放回映射，允许前端重试
+async fn interrupt_session(
+    sessions: &Arc<Mutex<HashMap<String, SessionProcess>>>,
+    event_gates: &Arc<Mutex<HashMap<u32, EventGate>>>,
+    session_id: &str,
+    emit: impl FnOnce(&str),
+) -> Result<()> {
     // 从 sessions 中取出并移除 PID
     let pid_opt = {
-        let mut sessions = state.sessions.lock()
-            .map_err(|e| crate::error::AppError::Unknown(e.to_string()))?;
-        sessions.remove(&session_id)
+        let mut sessions = sessions.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        sessions.remove(session_id)
     };
 
-    if let Some(pid) = pid_opt {
-        eprintln!("[interrupt_chat] 找到进程 PID: {}, 正在终止", pid);
-        terminate_process(pid);
-        eprintln!("[interrupt_chat] 中断命令已发送");
-    } else {
-        eprintln!("[interrupt_chat] 未找到会话: {}", session_id);
-        return Err(AppError::ProcessError(format!("未找到会话: {}", session_id)));
+    let pid = match pid_opt {
+        Some(SessionProcess::Running(pid)) => pid,
+        Some(SessionProcess::Pending) | None => {
+            tracing::warn!("[interrupt_chat] 未找到会话: {}", session_id);
+            return Err(AppError::ProcessError(format!("未找到会话: {}", session_id)));
+        }
+    };
+
+    tracing::info!("[interrupt_chat] 找到进程 PID: {}, 正在终止", pid);
+
+    let exited = terminate_process_blocking(pid).await?;
+
+    if !exited {
+        if let Ok(mut sessions) = sessions.lock() {
+            sessions.insert(session_id.to_string(), SessionProcess::Running(pid));
+        }
+        return Err(AppError::ProcessError(format!("进程 {} 未能在超时时间内退出", pid)));
+    }
+
+    tracing::info!("[interrupt_chat] 进程已退出");
+
+    let event_json = serde_json::json!({
+        "type": "session_end",
+        "reason": "interrupted",
+        "session_id": session_id
+    }).to_string();
+
+    // 出口已被移除说明读取线程已结束，事件均已发送，可直接发送
+    let gate = event_gates.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?
+        .remove(&pid);
+    match gate {
+        Some(gate) => gate.close(|| emit(&event_json)),
+        None => emit(&event_json),
     }
 
     Ok(())
 }
 
-/// 从文本中提取 IFlow session ID
-fn extract_session_id(text: &str) -> Option<String> {
-    let re = regex::Regex::new(r"session-[a-f0-9-]+").ok()?;
-    re.find(text).map(|m| m.as_str().to_string())
+/// 默认的 IFlow 会话 ID 格式：`session-xxx` 或标准 UUID
+const DEFAULT_SESSION_ID_PATTERN: &str =
+    r"session-[0-9a-fA-F-]+|\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b";
+
+/// 构建识别 IFlow 会话 ID 的正则，配置的表达式无效时回退到默认格式
+fn session_id_regex(pattern: Option<&str>) -> regex::Regex {
+    if let Some(pattern) = pattern.filter(|p| !p.trim().is_empty()) {
+        match regex::Regex::new(pattern) {
+            Ok(re) => return re,
+            Err(e) => tracing::info!("[session_id_regex] 会话 ID 正则无效，使用默认格式: {}", e),
+        }
+    }
+    regex::Regex::new(DEFAULT_SESSION_ID_PATTERN).expect("默认会话 ID 正则有效")
+}
+
+/// 从文本中提取 IFlow session ID（含捕获组时取第一个捕获组，否则取整个匹配）
+fn extract_session_id(re: &regex::Regex, text: &str) -> Option<String> {
+    re.captures_iter(text)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(0)))
+        .map(|m| m.as_str().trim_end_matches('-'))
+        .find(|id| !id.is_empty() && *id != "session")
+        .map(|id| id.to_string())
 }
 
 // ============================================================================
@@ -881,21 +1694,47 @@ fn extract_session_id(text: &str) -> Option<String> {
 // ============================================================================
 
 use crate::models::iflow_events::{
-    IFlowSessionMeta, IFlowHistoryMessage, IFlowFileContext, IFlowTokenStats,
+    IFlowSessionMeta, IFlowHistoryMessage, IFlowMessageNode, IFlowFileContext, IFlowFileContextDiff,
+    IFlowTokenStats,
 };
 
 /// 列出 IFlow 会话
 #[tauri::command]
 pub async fn list_iflow_sessions(
+    project_path: Option<String>,
+    tags: Option<Vec<String>>,
     state: tauri::State<'_, crate::AppState>,
 ) -> Result<Vec<IFlowSessionMeta>> {
-    eprintln!("[list_iflow_sessions] 获取 IFlow 会话列表");
+    tracing::info!("[list_iflow_sessions] 获取 IFlow 会话列表");
 
-    let config_store = state.config_store.lock()
+    let config = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        let mut cfg = config_store.get().clone();
+        if let Some(path) = project_path {
+            cfg.work_dir = Some(PathBuf::from(path));
+        }
+        cfg
+    };
+    let mut sessions = crate::services::iflow_service::IFlowService::list_sessions(&config)?;
+
+    // 合并用户自定义标题和标签，并按标签过滤
+    let session_meta = state.session_meta.lock()
         .map_err(|e| AppError::Unknown(e.to_string()))?;
+    let tags = tags.unwrap_or_default();
+    sessions.retain(|s| session_meta.has_all_tags(&s.session_id, &tags));
+    for session in &mut sessions {
+        if let Some(title) = session_meta.title(&session.session_id) {
+            session.title = title.to_string();
+        }
+        session.tags = session_meta.tags(&session.session_id).to_vec();
+        session.pinned = session_meta.is_pinned(&session.session_id);
+    }
 
-    let config = config_store.get().clone();
-    crate::services::iflow_service::IFlowService::list_sessions(&config)
+    // 置顶会话排在前面（稳定排序，保持组内原有顺序）
+    sessions.sort_by_key(|s| !s.pinned);
+
+    Ok(sessions)
 }
 
 /// 获取 IFlow 会话历史
@@ -904,7 +1743,7 @@ pub async fn get_iflow_session_history(
     session_id: String,
     state: tauri::State<'_, crate::AppState>,
 ) -> Result<Vec<IFlowHistoryMessage>> {
-    eprintln!("[get_iflow_session_history] 获取会话历史: {}", session_id);
+    tracing::info!("[get_iflow_session_history] 获取会话历史: {}", session_id);
 
     let config_store = state.config_store.lock()
         .map_err(|e| AppError::Unknown(e.to_string()))?;
@@ -913,13 +1752,29 @@ pub async fn get_iflow_session_history(
     crate::services::iflow_service::IFlowService::get_session_history(&config, &session_id)
 }
 
+/// 获取 IFlow 会话消息树（包含对话分支）
+#[tauri::command]
+pub async fn get_iflow_session_tree(
+    session_id: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<IFlowMessageNode>> {
+    tracing::info!("[get_iflow_session_tree] 获取会话树: {}", session_id);
+
+    let config = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        config_store.get().clone()
+    };
+    crate::services::iflow_service::IFlowService::get_session_tree(&config, &session_id)
+}
+
 /// 获取 IFlow 文件上下文
 #[tauri::command]
 pub async fn get_iflow_file_contexts(
     session_id: String,
     state: tauri::State<'_, crate::AppState>,
 ) -> Result<Vec<IFlowFileContext>> {
-    eprintln!("[get_iflow_file_contexts] 获取文件上下文: {}", session_id);
+    tracing::info!("[get_iflow_file_contexts] 获取文件上下文: {}", session_id);
 
     let config_store = state.config_store.lock()
         .map_err(|e| AppError::Unknown(e.to_string()))?;
@@ -928,13 +1783,30 @@ pub async fn get_iflow_file_contexts(
     crate::services::iflow_service::IFlowService::get_file_contexts(&config, &session_id)
 }
 
+/// 比较两个 IFlow 会话访问过的文件
+#[tauri::command]
+pub async fn diff_session_file_contexts(
+    session_a: String,
+    session_b: String,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<IFlowFileContextDiff> {
+    tracing::info!("[diff_session_file_contexts] 比较会话文件: {} / {}", session_a, session_b);
+
+    let config = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        config_store.get().clone()
+    };
+    crate::services::iflow_service::IFlowService::diff_file_contexts(&config, &session_a, &session_b)
+}
+
 /// 获取 IFlow Token 统计
 #[tauri::command]
 pub async fn get_iflow_token_stats(
     session_id: String,
     state: tauri::State<'_, crate::AppState>,
 ) -> Result<IFlowTokenStats> {
-    eprintln!("[get_iflow_token_stats] 获取 Token 统计: {}", session_id);
+    tracing::info!("[get_iflow_token_stats] 获取 Token 统计: {}", session_id);
 
     let config_store = state.config_store.lock()
         .map_err(|e| AppError::Unknown(e.to_string()))?;
@@ -952,6 +1824,12 @@ pub async fn get_iflow_token_stats(
 #[serde(rename_all = "camelCase")]
 pub struct ClaudeCodeSessionMeta {
     pub session_id: String,
+    /// 会话标题（用户自定义标题，未设置时为首条提示词）
+    pub title: String,
+    /// 会话标签
+    pub tags: Vec<String>,
+    /// 是否置顶
+    pub pinned: bool,
     pub first_prompt: String,
     pub message_count: u32,
     pub created: String,
@@ -975,8 +1853,10 @@ pub struct ClaudeCodeMessage {
 #[tauri::command]
 pub async fn list_claude_code_sessions(
     project_path: Option<String>,
+    tags: Option<Vec<String>>,
+    state: State<'_, crate::AppState>,
 ) -> Result<Vec<ClaudeCodeSessionMeta>> {
-    eprintln!("[list_claude_code_sessions] 获取 Claude Code 会话列表");
+    tracing::info!("[list_claude_code_sessions] 获取 Claude Code 会话列表");
 
     // 获取项目目录名（用于构建 .claude 路径）
     let project_dir = if let Some(path) = project_path {
@@ -994,13 +1874,13 @@ pub async fn list_claude_code_sessions(
     let projects_dir = claude_projects_dir();
     let index_path = projects_dir.join(&project_name).join("sessions-index.json");
 
-    eprintln!("[list_claude_code_sessions] 项目路径: {:?}", project_dir);
-    eprintln!("[list_claude_code_sessions] 项目名: {}", project_name);
-    eprintln!("[list_claude_code_sessions] projects 目录: {:?}", projects_dir);
-    eprintln!("[list_claude_code_sessions] 索引文件: {:?}", index_path);
+    tracing::info!("[list_claude_code_sessions] 项目路径: {:?}", project_dir);
+    tracing::info!("[list_claude_code_sessions] 项目名: {}", project_name);
+    tracing::info!("[list_claude_code_sessions] projects 目录: {:?}", projects_dir);
+    tracing::info!("[list_claude_code_sessions] 索引文件: {:?}", index_path);
 
     if !index_path.exists() {
-        eprintln!("[list_claude_code_sessions] 索引文件不存在，返回空列表");
+        tracing::warn!("[list_claude_code_sessions] 索引文件不存在，返回空列表");
         return Ok(vec![]);
     }
 
@@ -1012,6 +1892,9 @@ pub async fn list_claude_code_sessions(
         .map_err(|e| AppError::Unknown(format!("解析索引文件失败: {}", e)))?;
 
     let mut sessions = vec![];
+    let session_meta = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    let tags = tags.unwrap_or_default();
 
     if let Some(entries) = index.get("entries").and_then(|v| v.as_array()) {
         for entry in entries {
@@ -1024,14 +1907,27 @@ pub async fn list_claude_code_sessions(
                     entry.get("modified").and_then(|v| v.as_str()),
                     entry.get("fullPath").and_then(|v| v.as_str()),
                 ) {
+                // 按标签过滤（需包含所有指定标签）
+                if !session_meta.has_all_tags(session_id, &tags) {
+                    continue;
+                }
+
                 // 获取文件大小
                 let file_size = std::fs::metadata(full_path)
                     .map(|m| m.len())
                     .unwrap_or(0);
 
+                let first_prompt = truncate_string(first_prompt, 100);
+                let title = session_meta.title(session_id)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| first_prompt.clone());
+
                 sessions.push(ClaudeCodeSessionMeta {
                     session_id: session_id.to_string(),
-                    first_prompt: truncate_string(first_prompt, 100),
+                    title,
+                    tags: session_meta.tags(session_id).to_vec(),
+                    pinned: session_meta.is_pinned(session_id),
+                    first_prompt,
                     message_count: message_count as u32,
                     created: created.to_string(),
                     modified: modified.to_string(),
@@ -1042,10 +1938,11 @@ pub async fn list_claude_code_sessions(
         }
     }
 
-    // 按修改时间倒序排序
+    // 按修改时间倒序排序，置顶会话排在前面
     sessions.sort_by(|a, b| b.modified.cmp(&a.modified));
+    sessions.sort_by_key(|s| !s.pinned);
 
-    eprintln!("[list_claude_code_sessions] 找到 {} 个会话", sessions.len());
+    tracing::info!("[list_claude_code_sessions] 找到 {} 个会话", sessions.len());
     Ok(sessions)
 }
 
@@ -1055,7 +1952,7 @@ pub async fn get_claude_code_session_history(
     session_id: String,
     project_path: Option<String>,
 ) -> Result<Vec<ClaudeCodeMessage>> {
-    eprintln!("[get_claude_code_session_history] 获取会话历史: {}", session_id);
+    tracing::info!("[get_claude_code_session_history] 获取会话历史: {}", session_id);
 
     let project_dir = if let Some(path) = project_path {
         PathBuf::from(path)
@@ -1068,10 +1965,10 @@ pub async fn get_claude_code_session_history(
     let projects_dir = claude_projects_dir();
     let session_file_path = projects_dir.join(&project_name).join(format!("{}.jsonl", session_id));
 
-    eprintln!("[get_claude_code_session_history] 项目路径: {:?}", project_dir);
-    eprintln!("[get_claude_code_session_history] 项目名: {}", project_name);
-    eprintln!("[get_claude_code_session_history] projects 目录: {:?}", projects_dir);
-    eprintln!("[get_claude_code_session_history] 会话文件: {:?}", session_file_path);
+    tracing::info!("[get_claude_code_session_history] 项目路径: {:?}", project_dir);
+    tracing::info!("[get_claude_code_session_history] 项目名: {}", project_name);
+    tracing::info!("[get_claude_code_session_history] projects 目录: {:?}", projects_dir);
+    tracing::info!("[get_claude_code_session_history] 会话文件: {:?}", session_file_path);
 
     if !session_file_path.exists() {
         return Err(AppError::Unknown(format!("会话文件不存在: {:?}", session_file_path)));
@@ -1107,10 +2004,257 @@ pub async fn get_claude_code_session_history(
         }
     }
 
-    eprintln!("[get_claude_code_session_history] 解析到 {} 条消息", messages.len());
+    tracing::info!("[get_claude_code_session_history] 解析到 {} 条消息", messages.len());
     Ok(messages)
 }
 
+// ============================================================================
+// 跨引擎会话列表
+// ============================================================================
+
+/// 统一的会话元数据（合并各引擎的会话列表）
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnifiedSessionMeta {
+    /// 引擎 ID
+    pub engine: String,
+    pub session_id: String,
+    pub title: String,
+    pub message_count: u32,
+    /// 最后修改时间
+    pub modified: String,
+    /// 输入与输出 Token 总数（无法统计时为 None）
+    pub tokens: Option<u32>,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+}
+
+/// 列出项目下所有引擎的会话
+///
+/// 置顶会话在前，其余按修改时间倒序排列。单个引擎读取失败时跳过该引擎
+#[tauri::command]
+pub async fn list_all_sessions(
+    project_path: Option<String>,
+    tags: Option<Vec<String>>,
+    state: State<'_, crate::AppState>,
+) -> Result<Vec<UnifiedSessionMeta>> {
+    tracing::info!("[list_all_sessions] 获取全部会话列表");
+
+    let config = {
+        let store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        store.get().clone()
+    };
+
+    let mut sessions = Vec::new();
+
+    match list_claude_code_sessions(project_path.clone(), tags.clone(), state).await {
+        Ok(list) => sessions.extend(list.into_iter().map(|s| UnifiedSessionMeta {
+            engine: EngineId::ClaudeCode.as_str().to_string(),
+            session_id: s.session_id,
+            title: s.title,
+            message_count: s.message_count,
+            modified: s.modified,
+            // 与 IFlow 一致，只统计输入与输出 Token（缓存 Token 不计入）
+            tokens: token_summary::claude_file_token_stats(&config, Path::new(&s.file_path))
+                .map_err(|e| tracing::debug!("[list_all_sessions] 统计 Token 失败 {}: {}", s.file_path, e))
+                .ok()
+                .and_then(|stats| u32::try_from(stats.input + stats.output).ok()),
+            tags: s.tags,
+            pinned: s.pinned,
+        })),
+        Err(e) => tracing::warn!("[list_all_sessions] 读取 Claude Code 会话失败: {}", e),
+    }
+
+    match list_iflow_sessions(project_path, tags, state).await {
+        Ok(list) => sessions.extend(list.into_iter().map(|s| UnifiedSessionMeta {
+            engine: EngineId::IFlow.as_str().to_string(),
+            session_id: s.session_id,
+            title: s.title,
+            message_count: s.message_count,
+            modified: s.updated_at,
+            tokens: s.input_tokens.checked_add(s.output_tokens),
+            tags: s.tags,
+            pinned: s.pinned,
+        })),
+        Err(e) => tracing::warn!("[list_all_sessions] 读取 IFlow 会话失败: {}", e),
+    }
+
+    // 两个引擎的时间格式可能不同，解析后比较，无法解析的排在最后
+    sessions.sort_by_key(|s| {
+        let modified = chrono::DateTime::parse_from_rfc3339(&s.modified)
+            .map(|dt| dt.timestamp_millis())
+            .ok();
+        (!s.pinned, std::cmp::Reverse(modified))
+    });
+
+    tracing::info!("[list_all_sessions] 找到 {} 个会话", sessions.len());
+    Ok(sessions)
+}
+
+/// 汇总项目下所有会话的 Token 用量与估算费用
+///
+/// 未指定引擎时使用默认引擎。结果按会话目录状态缓存，会话文件未变化时直接返回缓存
+#[tauri::command]
+pub async fn get_project_token_summary(
+    project_path: Option<String>,
+    engine: Option<String>,
+    state: State<'_, crate::AppState>,
+) -> Result<ProjectTokenSummary> {
+    let config = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        let mut cfg = config_store.get().clone();
+        if let Some(ref path) = project_path {
+            cfg.work_dir = Some(PathBuf::from(path));
+        }
+        cfg
+    };
+
+    let engine = EngineId::from_str(engine.as_deref().unwrap_or(&config.default_engine))
+        .unwrap_or(EngineId::ClaudeCode);
+    tracing::info!("[get_project_token_summary] 项目: {:?}, 引擎: {:?}", config.work_dir, engine);
+
+    let session_dir = match engine {
+        EngineId::ClaudeCode => {
+            let project_dir = match config.work_dir {
+                Some(ref dir) => dir.clone(),
+                None => std::env::current_dir()
+                    .map_err(|e| AppError::Unknown(format!("获取当前目录失败: {}", e)))?,
+            };
+            claude_projects_dir().join(project_name_from_path(&project_dir))
+        }
+        EngineId::IFlow => IFlowService::project_session_dir(&config)?,
+    };
+
+    let files = token_summary::list_session_files(&session_dir);
+    let fingerprint = SessionDirFingerprint::of(&session_dir, &files);
+
+    {
+        let cache = state.token_summary_cache.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        if let Some(summary) = cache.get(engine.as_str(), &session_dir, &fingerprint) {
+            tracing::info!("[get_project_token_summary] 使用缓存结果");
+            return Ok(summary);
+        }
+    }
+
+    let summary = match engine {
+        EngineId::ClaudeCode => token_summary::summarize_files(engine.as_str(), &files, |path| {
+            token_summary::claude_file_token_stats(&config, path)
+        }),
+        EngineId::IFlow => token_summary::summarize_files(engine.as_str(), &files, |path| {
+            IFlowService::token_stats_from_file(&config, path).map(|stats| FileTokenStats {
+                input: stats.total_input_tokens as u64,
+                output: stats.total_output_tokens as u64,
+                cache_creation: stats.total_cache_creation_tokens as u64,
+                cache_read: stats.total_cache_read_tokens as u64,
+                cost: stats.estimated_cost_usd,
+            })
+        }),
+    };
+
+    tracing::info!(
+        "[get_project_token_summary] {} 个会话，共 {} tokens",
+        summary.session_count, summary.total_tokens
+    );
+
+    let mut cache = state.token_summary_cache.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    cache.insert(engine.as_str(), &session_dir, fingerprint, summary.clone());
+
+    Ok(summary)
+}
+
+/// 获取最近一次会话（用于启动时"从上次中断处继续"）
+///
+/// 会话文件已不存在时返回 None
+#[tauri::command]
+pub async fn get_last_session(
+    state: State<'_, crate::AppState>,
+) -> Result<Option<LastSession>> {
+    let config = {
+        let config_store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        config_store.get().clone()
+    };
+
+    let last_session = match config.last_session.clone() {
+        Some(last_session) => last_session,
+        None => return Ok(None),
+    };
+
+    let exists = match EngineId::from_str(&last_session.engine_id) {
+        Some(EngineId::ClaudeCode) => {
+            find_claude_session_file(&last_session.session_id, last_session.work_dir.as_deref()).is_some()
+        }
+        Some(EngineId::IFlow) => {
+            let mut cfg = config;
+            cfg.work_dir = last_session.work_dir.clone();
+            IFlowService::find_session_jsonl(&cfg, &last_session.session_id).is_ok()
+        }
+        None => false,
+    };
+
+    if !exists {
+        tracing::warn!("[get_last_session] 会话文件已不存在: {}", last_session.session_id);
+        return Ok(None);
+    }
+
+    Ok(Some(last_session))
+}
+
+/// 在系统文件管理器中定位会话 JSONL 文件
+#[tauri::command]
+pub async fn reveal_session_file(
+    session_id: String,
+    engine: String,
+    project_path: Option<String>,
+    state: State<'_, crate::AppState>,
+) -> Result<()> {
+    let work_dir = project_path.map(PathBuf::from);
+
+    let path = match EngineId::from_str(&engine) {
+        Some(EngineId::ClaudeCode) => find_claude_session_file(&session_id, work_dir.as_deref())
+            .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?,
+        Some(EngineId::IFlow) => {
+            let mut config = {
+                let config_store = state.config_store.lock()
+                    .map_err(|e| AppError::Unknown(e.to_string()))?;
+                config_store.get().clone()
+            };
+            if work_dir.is_some() {
+                config.work_dir = work_dir;
+            }
+            IFlowService::find_session_jsonl(&config, &session_id)?
+        }
+        None => return Err(AppError::ConfigError(format!("未知引擎: {}", engine))),
+    };
+
+    tracing::info!("[reveal_session_file] 定位会话文件: {:?}", path);
+
+    tauri_plugin_opener::reveal_item_in_dir(&path)
+        .map_err(|e| AppError::Unknown(format!("打开文件位置失败: {}", e)))
+}
+
+/// 查找 Claude Code 会话文件
+///
+/// 指定工作目录时只查找对应项目目录，否则遍历所有项目目录
+fn find_claude_session_file(session_id: &str, work_dir: Option<&Path>) -> Option<PathBuf> {
+    let projects_dir = claude_projects_dir();
+    let file_name = format!("{}.jsonl", session_id);
+
+    if let Some(dir) = work_dir {
+        let path = projects_dir.join(project_name_from_path(dir)).join(&file_name);
+        return path.exists().then_some(path);
+    }
+
+    std::fs::read_dir(&projects_dir).ok()?
+        .flatten()
+        .map(|entry| entry.path().join(&file_name))
+        .find(|path| path.exists())
+}
+
 /// 将路径转换为 Claude Code 项目名格式
 /// 例如: "D:\Polaris" -> "D--Polaris"
 fn project_name_from_path(path: &Path) -> String {
@@ -1152,3 +2296,373 @@ fn truncate_string(s: &str, max_len: usize) -> String {
         format!("{}...", s.chars().take(max_len.saturating_sub(3)).collect::<String>())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_event_gets_estimated_cost_from_model_usage() {
+        let mut config = Config::default();
+        config.model_pricing.insert("claude-sonnet".to_string(), crate::models::config::ModelPrice {
+            input: 3.0,
+            output: 15.0,
+            cache_write: None,
+            cache_read: None,
+        });
+        let result_event = |model_usage: serde_json::Value| {
+            let line = serde_json::json!({ "type": "result", "subtype": "success", "modelUsage": model_usage });
+            let event = StreamEvent::parse_line(&line.to_string()).unwrap();
+            match attach_estimated_cost(event, &config) {
+                StreamEvent::Result { extra, .. } => extra["estimated_cost_usd"].clone(),
+                other => panic!("事件类型不符: {:?}", other),
+            }
+        };
+
+        let cost = result_event(serde_json::json!({
+            "claude-sonnet-4-5": {
+                "inputTokens": 1000,
+                "outputTokens": 100,
+                "cacheCreationInputTokens": 1000,
+                "cacheReadInputTokens": 10000
+            }
+        }));
+        let expected = (1000.0 * 3.0 + 100.0 * 15.0 + 1000.0 * 3.75 + 10000.0 * 0.3) / 1_000_000.0;
+        assert!((cost.as_f64().unwrap() - expected).abs() < 1e-12);
+
+        // 任一模型未配置价格或没有用量时费用未知
+        let cost = result_event(serde_json::json!({
+            "claude-sonnet-4-5": { "inputTokens": 1000, "outputTokens": 100 },
+            "claude-haiku-4-5": { "inputTokens": 1000, "outputTokens": 100 }
+        }));
+        assert!(cost.is_null());
+        assert!(result_event(serde_json::json!({})).is_null());
+
+        // 非结果事件保持不变
+        let event = attach_estimated_cost(StreamEvent::SessionEnd, &config);
+        assert!(matches!(event, StreamEvent::SessionEnd));
+    }
+
+    #[test]
+    fn spawn_error_not_found_suggests_install() {
+        let e = std::io::Error::new(std::io::ErrorKind::NotFound, "program not found");
+        let message = claude_spawn_error("claude", e, "启动 Claude").to_string();
+
+        assert!(message.contains("启动 Claude失败"));
+        assert!(message.contains("找不到 Claude CLI（claude）"));
+        assert!(message.contains("npm install -g @anthropic-ai/claude-code"));
+    }
+
+    #[test]
+    fn spawn_error_other_kinds_keep_io_message() {
+        let e = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "access denied");
+        let message = claude_spawn_error("claude", e, "启动 Claude").to_string();
+
+        assert!(message.contains("启动 Claude失败: access denied"));
+        assert!(!message.contains("npm install"));
+    }
+
+    fn claude_args(resume: ClaudeResume<'_>, system_prompt: SystemPrompt<'_>) -> Vec<String> {
+        claude_args_with_model(resume, system_prompt, None)
+    }
+
+    fn claude_args_with_model(resume: ClaudeResume<'_>, system_prompt: SystemPrompt<'_>, model: Option<&str>) -> Vec<String> {
+        let mut cmd = Command::new("claude");
+        apply_claude_args(&mut cmd, resume, "hello", system_prompt, model);
+        cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    /// 返回参数 `flag` 之后紧跟的值
+    fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1))
+            .map(String::as_str)
+    }
+
+    #[test]
+    fn append_mode_uses_append_system_prompt() {
+        let args = claude_args(ClaudeResume::New, SystemPrompt::new(None, Some("追加规则")));
+
+        assert_eq!(arg_value(&args, "--append-system-prompt"), Some("追加规则"));
+        assert!(!args.iter().any(|a| a == "--system-prompt"));
+        assert_eq!(args.last().map(String::as_str), Some("hello"));
+    }
+
+    #[test]
+    fn replace_mode_uses_system_prompt() {
+        let args = claude_args(ClaudeResume::Session("abc"), SystemPrompt::new(Some("替换规则"), None));
+
+        assert_eq!(arg_value(&args, "--system-prompt"), Some("替换规则"));
+        assert!(!args.iter().any(|a| a == "--append-system-prompt"));
+        assert_eq!(arg_value(&args, "--resume"), Some("abc"));
+    }
+
+    #[test]
+    fn empty_system_prompt_adds_no_flag() {
+        let args = claude_args(ClaudeResume::New, SystemPrompt::new(Some(""), Some("")));
+
+        assert!(!args.iter().any(|a| a.contains("system-prompt")));
+    }
+
+    #[test]
+    fn model_override_is_passed_to_claude_cli() {
+        let mut cfg = Config::default();
+        apply_engine_overrides(&mut cfg, EngineId::ClaudeCode, Some("sonnet".to_string()), Some("plan".to_string()));
+        assert_eq!(cfg.claude_code.model.as_deref(), Some("sonnet"));
+        assert_eq!(cfg.iflow.approval_mode, None);
+
+        let args = claude_args_with_model(ClaudeResume::New, SystemPrompt::new(None, None), cfg.claude_code.model.as_deref());
+        assert_eq!(arg_value(&args, "--model"), Some("sonnet"));
+        assert_eq!(args.last().map(String::as_str), Some("hello"));
+
+        let args = claude_args_with_model(ClaudeResume::New, SystemPrompt::new(None, None), Some(""));
+        assert!(!args.iter().any(|a| a == "--model"));
+    }
+
+    #[test]
+    fn extracts_session_prefixed_id() {
+        let re = session_id_regex(None);
+
+        assert_eq!(
+            extract_session_id(&re, "Session saved: session-3f2a9c1e-77b0-4d1b").as_deref(),
+            Some("session-3f2a9c1e-77b0-4d1b")
+        );
+    }
+
+    #[test]
+    fn extracts_uuid_session_id() {
+        let re = session_id_regex(None);
+
+        assert_eq!(
+            extract_session_id(&re, "resume with --resume 0c8d6a3e-5b1f-4e2a-9c7d-1a2b3c4d5e6f").as_deref(),
+            Some("0c8d6a3e-5b1f-4e2a-9c7d-1a2b3c4d5e6f")
+        );
+    }
+
+    #[test]
+    fn line_without_session_id_yields_none() {
+        let re = session_id_regex(None);
+
+        assert_eq!(extract_session_id(&re, "Thinking... (esc to cancel)"), None);
+        assert_eq!(extract_session_id(&re, "new session- started"), None);
+    }
+
+    #[test]
+    fn custom_pattern_uses_first_capture_group() {
+        let re = session_id_regex(Some(r"conversation id: (\w+)"));
+        assert_eq!(extract_session_id(&re, "conversation id: abc123").as_deref(), Some("abc123"));
+
+        // 无效的自定义表达式回退到默认格式
+        let re = session_id_regex(Some("(unclosed"));
+        assert_eq!(extract_session_id(&re, "session-abc").as_deref(), Some("session-abc"));
+    }
+
+    #[test]
+    fn max_tokens_sets_claude_env_var() {
+        let mut cfg = Config::default();
+        apply_generation_overrides(&mut cfg, EngineId::ClaudeCode, None, Some(4096));
+
+        assert_eq!(cfg.env.get(CLAUDE_MAX_OUTPUT_TOKENS_ENV).map(String::as_str), Some("4096"));
+        assert!(cfg.session_env().any(|(k, v)| k == CLAUDE_MAX_OUTPUT_TOKENS_ENV && v == "4096"));
+    }
+
+    #[test]
+    fn max_tokens_ignored_when_unsupported_or_zero() {
+        let mut cfg = Config::default();
+        apply_generation_overrides(&mut cfg, EngineId::IFlow, None, Some(4096));
+        apply_generation_overrides(&mut cfg, EngineId::ClaudeCode, None, Some(0));
+        apply_generation_overrides(&mut cfg, EngineId::ClaudeCode, None, None);
+
+        assert!(!cfg.env.contains_key(CLAUDE_MAX_OUTPUT_TOKENS_ENV));
+    }
+
+    #[test]
+    fn temperature_is_ignored_for_both_engines() {
+        let mut cfg = Config::default();
+        apply_generation_overrides(&mut cfg, EngineId::ClaudeCode, Some(0.2), None);
+        apply_generation_overrides(&mut cfg, EngineId::IFlow, Some(0.2), None);
+
+        assert!(cfg.env.is_empty());
+        assert_eq!(cfg.iflow.model, Config::default().iflow.model);
+    }
+
+    #[test]
+    fn session_slots_enforce_the_limit() {
+        let sessions = Arc::new(Mutex::new(HashMap::new()));
+        let first = reserve_session_slot(&sessions, 2, None).unwrap();
+        let second = reserve_session_slot(&sessions, 2, None).unwrap();
+        assert!(reserve_session_slot(&sessions, 2, None).is_err());
+        // 预留中的名额参与上限检查，但不计入上报的活动会话数
+        assert!(sessions.lock().unwrap().values().all(|p| *p == SessionProcess::Pending));
+        assert_eq!(running_session_count(&sessions.lock().unwrap()), 0);
+
+        // 启动失败时释放预留的名额
+        drop(second);
+        let second = reserve_session_slot(&sessions, 2, None).unwrap();
+
+        first.fill("s1", 100).unwrap();
+        second.fill("s2", 200).unwrap();
+        assert_eq!(
+            sessions.lock().unwrap().clone(),
+            HashMap::from([
+                ("s1".to_string(), SessionProcess::Running(100)),
+                ("s2".to_string(), SessionProcess::Running(200)),
+            ])
+        );
+        assert!(reserve_session_slot(&sessions, 2, None).is_err());
+        assert_eq!(running_session_count(&sessions.lock().unwrap()), 2);
+
+        // continue 时被替换的会话不计入
+        assert!(reserve_session_slot(&sessions, 2, Some("s1")).is_ok());
+    }
+
+    #[test]
+    fn concurrent_reservations_never_exceed_the_limit() {
+        let sessions = Arc::new(Mutex::new(HashMap::new()));
+        let barrier = Arc::new(std::sync::Barrier::new(8));
+
+        let handles: Vec<_> = (0..8).map(|_| {
+            let sessions = Arc::clone(&sessions);
+            let barrier = Arc::clone(&barrier);
+            std::thread::spawn(move || {
+                barrier.wait();
+                reserve_session_slot(&sessions, 3, None).ok()
+            })
+        }).collect();
+        let slots: Vec<SessionSlot> = handles.into_iter()
+            .filter_map(|h| h.join().unwrap())
+            .collect();
+
+        assert_eq!(slots.len(), 3);
+        assert_eq!(sessions.lock().unwrap().len(), 3);
+    }
+
+    /// 在超时时间内轮询子进程是否已退出（try_wait 同时回收僵尸进程）
+    #[cfg(unix)]
+    fn child_exited(child: &mut Child) -> bool {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            if child.try_wait().unwrap().is_some() {
+                return true;
+            }
+            if std::time::Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn terminate_process_stops_running_child() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        assert!(child.try_wait().unwrap().is_none());
+
+        let exited = tauri::async_runtime::block_on(terminate_process_blocking(child.id())).unwrap();
+        assert!(exited);
+        assert!(child_exited(&mut child));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interrupt_removes_session_and_emits_interrupted_end() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        let sessions = Arc::new(Mutex::new(HashMap::from([
+            ("s1".to_string(), SessionProcess::Running(pid)),
+        ])));
+        let gate = EventGate::new();
+        let gates = Arc::new(Mutex::new(HashMap::from([(pid, gate.clone())])));
+
+        let mut emitted = Vec::new();
+        tauri::async_runtime::block_on(interrupt_session(&sessions, &gates, "s1", |json| {
+            emitted.push(serde_json::from_str::<serde_json::Value>(json).unwrap());
+        })).unwrap();
+
+        assert!(child_exited(&mut child));
+        assert!(sessions.lock().unwrap().is_empty());
+        assert!(gates.lock().unwrap().is_empty());
+        assert_eq!(emitted, [serde_json::json!({
+            "type": "session_end",
+            "reason": "interrupted",
+            "session_id": "s1",
+        })]);
+        // 出口已关闭，读取线程之后的事件被丢弃
+        assert!(!gate.emit(|| panic!("出口关闭后不应再发送事件")));
+
+        // 会话已移除，再次中断返回错误
+        let again = tauri::async_runtime::block_on(interrupt_session(&sessions, &gates, "s1", |_| {}));
+        assert!(again.is_err());
+    }
+
+    #[cfg(unix)]
+    fn run_failing_session(registered: bool) -> Vec<StreamEvent> {
+        let child = Command::new("sh")
+            .args(["-c", "echo 'fatal: model not available' >&2; exit 3"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let sessions = Arc::new(Mutex::new(HashMap::new()));
+        if registered {
+            sessions.lock().unwrap().insert("s1".to_string(), SessionProcess::Running(child.id()));
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        ChatSession::with_id_and_child("s1".to_string(), child)
+            .with_session_registry(sessions)
+            .read_events(move |event| sink.lock().unwrap().push(event));
+
+        let events = events.lock().unwrap().clone();
+        events
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_process_reports_stderr_tail() {
+        let events = run_failing_session(true);
+
+        let error = events.iter().find_map(|e| match e {
+            StreamEvent::Error { error, .. } => Some(error.clone()),
+            _ => None,
+        });
+        assert_eq!(error.as_deref(), Some("fatal: model not available"));
+        assert!(matches!(events.last(), Some(StreamEvent::SessionEnd)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interrupted_process_emits_neither_error_nor_session_end() {
+        let events = run_failing_session(false);
+
+        assert!(!events.iter().any(|e| matches!(e, StreamEvent::Error { .. })));
+        assert!(!events.iter().any(|e| matches!(e, StreamEvent::SessionEnd)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn custom_env_reaches_spawned_claude_process() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-claude");
+        std::fs::write(&script, "#!/bin/sh\nprintf '%s|%s' \"$POLARIS_TEST_VAR\" \"$LD_PRELOAD\"\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = Config::default();
+        config.claude_code.cli_path = script.to_string_lossy().to_string();
+        config.env.insert("POLARIS_TEST_VAR".to_string(), "from-config".to_string());
+        config.env.insert("LD_PRELOAD".to_string(), "/tmp/evil.so".to_string());
+
+        let child = spawn_claude(&config, ClaudeResume::New, "hi", SystemPrompt::new(None, None)).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (custom, preload) = stdout.split_once('|').unwrap();
+        assert_eq!(custom, "from-config");
+        assert_ne!(preload, "/tmp/evil.so");
+    }
+}
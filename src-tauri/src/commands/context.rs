@@ -3,9 +3,12 @@
  * 供 IDE 插件调用的上下文管理接口
  */
 
+use crate::models::config::ContextMemoryConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::State;
 
 // ========================================
@@ -189,6 +192,10 @@ pub struct ContextQueryRequest {
     pub min_priority: Option<u8>,
     pub current_file: Option<String>,
     pub mentioned_files: Option<Vec<String>>,
+    /// 文本查询（匹配路径、符号名、条目 ID），支持模糊匹配
+    pub query: Option<String>,
+    /// 最多返回的条目数
+    pub limit: Option<usize>,
 }
 
 /// 上下文查询结果
@@ -197,6 +204,9 @@ pub struct ContextQueryResult {
     pub entries: Vec<ContextEntry>,
     pub total_tokens: u32,
     pub summary: ContextSummary,
+    /// 文本查询的相关度得分（条目 ID -> 得分，越高越相关）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub scores: HashMap<String, u32>,
 }
 
 /// 上下文摘要
@@ -239,20 +249,257 @@ pub struct IdeDiagnostics {
 // 内存存储
 // ========================================
 
+/// 持久化写入的最小间隔（防抖）
+const PERSIST_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// 上下文存储统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextStats {
+    pub entry_count: usize,
+    pub estimated_bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
 /// 内存中的上下文存储
+///
+/// 设置了持久化路径时，修改会以防抖方式写入磁盘，启动时从磁盘恢复。
+/// 条目数或总字节数超过上限时，按最近最少访问（LRU）淘汰
 pub struct ContextMemoryStore {
     entries: HashMap<String, ContextEntry>,
+    /// 每个条目的估算字节数
+    entry_sizes: HashMap<String, usize>,
+    /// 每个条目的最后访问序号（越大越新）
+    last_accessed: HashMap<String, u64>,
+    /// 单调递增的访问计数器
+    access_clock: u64,
+    /// 当前估算总字节数
+    total_bytes: usize,
+    /// 最大条目数
+    max_entries: usize,
+    /// 最大总字节数
+    max_bytes: usize,
+    /// 持久化文件路径（None 表示仅内存）
+    persist_path: Option<PathBuf>,
+    /// 是否有尚未写入磁盘的修改
+    dirty: bool,
+    /// 上次写入磁盘的时间
+    last_persisted: Option<Instant>,
 }
 
 impl ContextMemoryStore {
     pub fn new() -> Self {
+        let limits = ContextMemoryConfig::default();
         Self {
             entries: HashMap::new(),
+            entry_sizes: HashMap::new(),
+            last_accessed: HashMap::new(),
+            access_clock: 0,
+            total_bytes: 0,
+            max_entries: limits.max_entries,
+            max_bytes: limits.max_bytes,
+            persist_path: None,
+            dirty: false,
+            last_persisted: None,
+        }
+    }
+
+    /// 创建带磁盘持久化的存储，并加载已有数据
+    pub fn with_persistence(path: PathBuf) -> Self {
+        let mut store = Self::new();
+        let mut loaded: Vec<ContextEntry> = Self::load_from_file(&path).into_values().collect();
+        // 按创建时间加载，使较旧的条目先被淘汰
+        loaded.sort_by_key(|e| e.created_at);
+        for entry in loaded {
+            store.insert_entry(entry);
         }
+        tracing::info!("[ContextMemoryStore] 从 {:?} 加载 {} 条上下文", path, store.entries.len());
+        store.persist_path = Some(path);
+        store
     }
 
-    pub fn upsert(&mut self, entry: ContextEntry) {
-        self.entries.insert(entry.id.clone(), entry);
+    /// 设置容量上限，并立即淘汰超出的条目
+    pub fn set_limits(&mut self, max_entries: usize, max_bytes: usize) {
+        self.max_entries = max_entries.max(1);
+        self.max_bytes = max_bytes.max(1);
+        if self.evict(&[]) > 0 {
+            self.mark_dirty();
+        }
+    }
+
+    /// 获取存储统计
+    pub fn stats(&self) -> ContextStats {
+        ContextStats {
+            entry_count: self.entries.len(),
+            estimated_bytes: self.total_bytes,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    /// 估算条目占用的字节数（以序列化后的 JSON 长度近似）
+    fn estimate_size(entry: &ContextEntry) -> usize {
+        serde_json::to_vec(entry).map(|v| v.len()).unwrap_or(0)
+    }
+
+    /// 记录一次访问
+    fn touch(&mut self, id: &str) {
+        self.access_clock += 1;
+        if let Some(t) = self.last_accessed.get_mut(id) {
+            *t = self.access_clock;
+        }
+    }
+
+    /// 插入条目并更新大小与访问记录（不做淘汰）
+    fn insert_entry(&mut self, entry: ContextEntry) {
+        let id = entry.id.clone();
+        let size = Self::estimate_size(&entry);
+        self.remove_entry(&id);
+        self.total_bytes += size;
+        self.entry_sizes.insert(id.clone(), size);
+        self.access_clock += 1;
+        self.last_accessed.insert(id.clone(), self.access_clock);
+        self.entries.insert(id, entry);
+    }
+
+    /// 移除条目并更新大小与访问记录
+    fn remove_entry(&mut self, id: &str) -> Option<ContextEntry> {
+        if let Some(size) = self.entry_sizes.remove(id) {
+            self.total_bytes = self.total_bytes.saturating_sub(size);
+        }
+        self.last_accessed.remove(id);
+        self.entries.remove(id)
+    }
+
+    /// 按 LRU 淘汰条目直到满足容量限制，`protected` 中的条目不会被淘汰
+    ///
+    /// 返回淘汰的条目数
+    fn evict(&mut self, protected: &[String]) -> usize {
+        let mut evicted = 0;
+        while self.entries.len() > self.max_entries || self.total_bytes > self.max_bytes {
+            let victim = self.last_accessed
+                .iter()
+                .filter(|(id, _)| !protected.contains(id))
+                .min_by_key(|(_, t)| **t)
+                .map(|(id, _)| id.clone());
+
+            match victim {
+                Some(id) => {
+                    self.remove_entry(&id);
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+        if evicted > 0 {
+            tracing::info!("[ContextMemoryStore] 淘汰 {} 条上下文", evicted);
+        }
+        evicted
+    }
+
+    /// 从文件加载上下文条目（跳过已过期条目）
+    fn load_from_file(path: &Path) -> HashMap<String, ContextEntry> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(_) => return HashMap::new(),
+        };
+
+        let entries: Vec<ContextEntry> = match serde_json::from_str(&content) {
+            Ok(e) => e,
+            Err(e) => {
+                tracing::warn!("[ContextMemoryStore] 解析上下文文件失败: {}", e);
+                return HashMap::new();
+            }
+        };
+
+        let now = now_secs();
+        entries
+            .into_iter()
+            .filter(|e| e.expires_at.is_none_or(|exp| exp > now))
+            .map(|e| (e.id.clone(), e))
+            .collect()
+    }
+
+    /// 标记已修改，距上次写入超过防抖间隔时立即写入
+    fn mark_dirty(&mut self) {
+        if self.persist_path.is_none() {
+            return;
+        }
+        self.dirty = true;
+
+        let due = self.last_persisted
+            .is_none_or(|t| t.elapsed() >= PERSIST_DEBOUNCE);
+        if due {
+            self.flush();
+        }
+    }
+
+    /// 将未写入的修改写入磁盘
+    pub fn flush(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let path = match self.persist_path {
+            Some(ref p) => p.clone(),
+            None => return,
+        };
+
+        let entries: Vec<&ContextEntry> = self.entries.values().collect();
+        let result = serde_json::to_vec(&entries)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                crate::services::fs_utils::write_atomic(&path, &bytes)
+                    .map_err(|e| e.to_string())
+            });
+
+        match result {
+            Ok(()) => {
+                self.dirty = false;
+                self.last_persisted = Some(Instant::now());
+            }
+            Err(e) => tracing::warn!("[ContextMemoryStore] 写入上下文文件失败: {}", e),
+        }
+    }
+
+    /// 启动后台线程，定期写入防抖期间积累的修改
+    pub fn spawn_flusher(store: Arc<Mutex<Self>>) {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(PERSIST_DEBOUNCE);
+            match store.lock() {
+                Ok(mut guard) => guard.flush(),
+                Err(_) => break,
+            }
+        });
+    }
+
+    pub fn upsert(&mut self, entry: ContextEntry) -> Result<(), String> {
+        self.upsert_many(vec![entry])
+    }
+
+    /// 批量添加或更新（只触发一次持久化）
+    ///
+    /// 整批条目超出容量上限时拒绝写入，不修改现有数据；
+    /// 否则全部写入后再淘汰其他最久未访问的条目
+    pub fn upsert_many(&mut self, entries: Vec<ContextEntry>) -> Result<(), String> {
+        let mut batch: HashMap<String, usize> = HashMap::new();
+        for entry in &entries {
+            batch.insert(entry.id.clone(), Self::estimate_size(entry));
+        }
+        let batch_bytes: usize = batch.values().sum();
+        if batch.len() > self.max_entries || batch_bytes > self.max_bytes {
+            return Err(format!(
+                "上下文超出容量上限: {} 条 / {} 字节（上限 {} 条 / {} 字节）",
+                batch.len(), batch_bytes, self.max_entries, self.max_bytes
+            ));
+        }
+
+        for entry in entries {
+            self.insert_entry(entry);
+        }
+        let protected: Vec<String> = batch.into_keys().collect();
+        self.evict(&protected);
+        self.mark_dirty();
+        Ok(())
     }
 
     pub fn get(&self, id: &str) -> Option<&ContextEntry> {
@@ -260,18 +507,30 @@ impl ContextMemoryStore {
     }
 
     pub fn remove(&mut self, id: &str) -> Option<ContextEntry> {
-        self.entries.remove(id)
+        let removed = self.remove_entry(id);
+        if removed.is_some() {
+            self.mark_dirty();
+        }
+        removed
     }
 
-    pub fn get_all(&self) -> Vec<ContextEntry> {
+    pub fn get_all(&mut self) -> Vec<ContextEntry> {
+        let ids: Vec<String> = self.entries.keys().cloned().collect();
+        for id in &ids {
+            self.touch(id);
+        }
         self.entries.values().cloned().collect()
     }
 
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.entry_sizes.clear();
+        self.last_accessed.clear();
+        self.total_bytes = 0;
+        self.mark_dirty();
     }
 
-    pub fn query(&self, request: &ContextQueryRequest) -> ContextQueryResult {
+    pub fn query(&mut self, request: &ContextQueryRequest) -> ContextQueryResult {
         let mut entries: Vec<ContextEntry> = self.entries.values()
             .filter(|entry| {
                 // 过滤条件
@@ -301,6 +560,19 @@ impl ContextMemoryStore {
         // 按优先级排序
         entries.sort_by(|a, b| b.priority.cmp(&a.priority));
 
+        // 文本查询：按相关度过滤并排序（得分相同时保持优先级顺序）
+        let mut scores = HashMap::new();
+        if let Some(query) = request.query.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            let min_results = request.limit.unwrap_or(FUZZY_FALLBACK_MIN_RESULTS);
+            scores = Self::score_entries(&entries, query, min_results);
+            entries.retain(|e| scores.contains_key(&e.id));
+            entries.sort_by(|a, b| scores[&b.id].cmp(&scores[&a.id]));
+        }
+
+        if let Some(limit) = request.limit {
+            entries.truncate(limit);
+        }
+
         // 计算 Token 预算
         let max_tokens = request.max_tokens.unwrap_or(8000);
         let mut total_tokens = 0;
@@ -311,6 +583,12 @@ impl ContextMemoryStore {
                 total_tokens <= max_tokens
             })
             .collect();
+        scores.retain(|id, _| selected.iter().any(|e| &e.id == id));
+
+        // 记录访问，供 LRU 淘汰使用
+        for entry in &selected {
+            self.touch(&entry.id);
+        }
 
         // 构建摘要
         let summary = Self::build_summary(&selected);
@@ -319,9 +597,70 @@ impl ContextMemoryStore {
             entries: selected,
             total_tokens,
             summary,
+            scores,
         }
     }
 
+    /// 计算条目与查询文本的相关度
+    ///
+    /// 先做大小写不敏感的子串匹配（快速路径）；命中数少于 `min_results` 时，
+    /// 再对其余条目做子序列模糊匹配作为补充
+    fn score_entries(entries: &[ContextEntry], query: &str, min_results: usize) -> HashMap<String, u32> {
+        let query_lower = query.to_lowercase();
+        let mut scores = HashMap::new();
+
+        for entry in entries {
+            let best = Self::search_keys(entry)
+                .iter()
+                .filter_map(|key| exact_match_score(&key.to_lowercase(), &query_lower))
+                .max();
+            if let Some(score) = best {
+                scores.insert(entry.id.clone(), score);
+            }
+        }
+
+        if scores.len() < min_results {
+            for entry in entries {
+                if scores.contains_key(&entry.id) {
+                    continue;
+                }
+                let best = Self::search_keys(entry)
+                    .iter()
+                    .filter_map(|key| fuzzy_match_score(&key.to_lowercase(), &query_lower))
+                    .max();
+                if let Some(score) = best {
+                    scores.insert(entry.id.clone(), score);
+                }
+            }
+        }
+
+        scores
+    }
+
+    /// 条目中可被文本查询匹配的字段
+    fn search_keys(entry: &ContextEntry) -> Vec<&str> {
+        let mut keys = vec![entry.id.as_str()];
+        match &entry.content {
+            ContextContent::File(f) => keys.push(&f.path),
+            ContextContent::FileStructure(f) => keys.push(&f.path),
+            ContextContent::Symbol(s) => {
+                keys.push(&s.name);
+                keys.push(&s.definition.path);
+            }
+            ContextContent::Selection(s) => keys.push(&s.path),
+            ContextContent::Diagnostics(d) => {
+                if let Some(ref path) = d.path {
+                    keys.push(path);
+                }
+            }
+            ContextContent::ProjectMeta(p) => {
+                keys.push(&p.name);
+                keys.push(&p.root_dir);
+            }
+        }
+        keys
+    }
+
     fn build_summary(entries: &[ContextEntry]) -> ContextSummary {
         use std::collections::{HashSet, HashMap};
 
@@ -362,6 +701,75 @@ impl Default for ContextMemoryStore {
     }
 }
 
+/// 子串匹配结果不足该数量时启用模糊匹配
+const FUZZY_FALLBACK_MIN_RESULTS: usize = 5;
+
+/// 子串匹配的基础得分（保证高于任何模糊匹配得分）
+const EXACT_MATCH_BASE_SCORE: u32 = 10_000;
+
+/// 子串匹配得分：文件名完全一致 > 文件名包含 > 路径包含
+fn exact_match_score(candidate: &str, query: &str) -> Option<u32> {
+    if !candidate.contains(query) {
+        return None;
+    }
+
+    let file_name = candidate.rsplit(['/', '\\']).next().unwrap_or(candidate);
+    let bonus = if file_name == query {
+        2_000
+    } else if file_name.contains(query) {
+        1_000
+    } else {
+        0
+    };
+
+    // 候选越短越相关
+    let length_penalty = candidate.len().min(999) as u32;
+    Some(EXACT_MATCH_BASE_SCORE + bonus - length_penalty)
+}
+
+/// 子序列模糊匹配得分：查询字符需按顺序全部出现，连续命中和单词边界命中加分
+fn fuzzy_match_score(candidate: &str, query: &str) -> Option<u32> {
+    let mut score = 0u32;
+    let mut query_chars = query.chars().peekable();
+    let mut prev_matched = false;
+    let mut prev_char: Option<char> = None;
+
+    for c in candidate.chars() {
+        let Some(&q) = query_chars.peek() else {
+            break;
+        };
+
+        if c == q {
+            score += 1;
+            if prev_matched {
+                score += 5;
+            }
+            if prev_char.is_none_or(|p| matches!(p, '/' | '\\' | '_' | '-' | '.' | ' ' | ':')) {
+                score += 3;
+            }
+            prev_matched = true;
+            query_chars.next();
+        } else {
+            prev_matched = false;
+        }
+        prev_char = Some(c);
+    }
+
+    if query_chars.peek().is_some() {
+        None
+    } else {
+        Some(score)
+    }
+}
+
+/// 当前 Unix 时间戳（秒）
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 // ========================================
 // Tauri 命令
 // ========================================
@@ -373,8 +781,7 @@ pub async fn context_upsert(
     store: State<'_, Arc<Mutex<ContextMemoryStore>>>,
 ) -> Result<(), String> {
     let mut guard = store.lock().map_err(|e| e.to_string())?;
-    guard.upsert(entry);
-    Ok(())
+    guard.upsert(entry)
 }
 
 /// 批量添加或更新上下文条目
@@ -384,10 +791,7 @@ pub async fn context_upsert_many(
     store: State<'_, Arc<Mutex<ContextMemoryStore>>>,
 ) -> Result<(), String> {
     let mut guard = store.lock().map_err(|e| e.to_string())?;
-    for entry in entries {
-        guard.upsert(entry);
-    }
-    Ok(())
+    guard.upsert_many(entries)
 }
 
 /// 查询上下文
@@ -396,7 +800,7 @@ pub async fn context_query(
     request: ContextQueryRequest,
     store: State<'_, Arc<Mutex<ContextMemoryStore>>>,
 ) -> Result<ContextQueryResult, String> {
-    let guard = store.lock().map_err(|e| e.to_string())?;
+    let mut guard = store.lock().map_err(|e| e.to_string())?;
     Ok(guard.query(&request))
 }
 
@@ -405,7 +809,7 @@ pub async fn context_query(
 pub async fn context_get_all(
     store: State<'_, Arc<Mutex<ContextMemoryStore>>>,
 ) -> Result<Vec<ContextEntry>, String> {
-    let guard = store.lock().map_err(|e| e.to_string())?;
+    let mut guard = store.lock().map_err(|e| e.to_string())?;
     Ok(guard.get_all())
 }
 
@@ -430,6 +834,15 @@ pub async fn context_clear(
     Ok(())
 }
 
+/// 获取上下文存储统计（条目数与估算字节数）
+#[tauri::command]
+pub async fn context_stats(
+    store: State<'_, Arc<Mutex<ContextMemoryStore>>>,
+) -> Result<ContextStats, String> {
+    let guard = store.lock().map_err(|e| e.to_string())?;
+    Ok(guard.stats())
+}
+
 /// IDE 插件上报当前文件上下文
 #[tauri::command]
 pub async fn ide_report_current_file(
@@ -458,8 +871,7 @@ pub async fn ide_report_current_file(
         estimated_tokens: 500, // 简化估算
     };
 
-    guard.upsert(entry);
-    Ok(())
+    guard.upsert(entry)
 }
 
 /// IDE 插件上报文件结构
@@ -489,8 +901,7 @@ pub async fn ide_report_file_structure(
         estimated_tokens: 100,
     };
 
-    guard.upsert(entry);
-    Ok(())
+    guard.upsert(entry)
 }
 
 /// IDE 插件上报诊断信息
@@ -520,6 +931,166 @@ pub async fn ide_report_diagnostics(
         estimated_tokens: 50,
     };
 
-    guard.upsert(entry);
-    Ok(())
+    guard.upsert(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_entry(id: &str, path: &str) -> ContextEntry {
+        ContextEntry {
+            id: id.to_string(),
+            source: ContextSource::Ide,
+            type_: ContextType::File,
+            priority: 3,
+            content: ContextContent::File(FileContent {
+                path: path.to_string(),
+                content: format!("// {}", path),
+                language: "rust".to_string(),
+            }),
+            workspace_id: Some("ws".to_string()),
+            created_at: now_secs(),
+            expires_at: None,
+            estimated_tokens: 10,
+        }
+    }
+
+    fn text_query(query: &str, limit: Option<usize>) -> ContextQueryRequest {
+        ContextQueryRequest {
+            workspace_id: None,
+            types: None,
+            sources: None,
+            max_tokens: None,
+            min_priority: None,
+            current_file: None,
+            mentioned_files: None,
+            query: Some(query.to_string()),
+            limit,
+        }
+    }
+
+    #[test]
+    fn eviction_drops_least_recently_accessed_entry() {
+        let mut store = ContextMemoryStore::new();
+        store.set_limits(3, usize::MAX);
+        for id in ["a", "b", "c"] {
+            store.upsert(file_entry(id, &format!("src/{}.rs", id))).unwrap();
+        }
+
+        // 查询命中 a，使其成为最近访问的条目
+        let result = store.query(&text_query("a.rs", None));
+        assert_eq!(result.entries.len(), 1);
+
+        store.upsert(file_entry("d", "src/d.rs")).unwrap();
+        assert!(store.get("b").is_none(), "最久未访问的 b 应被淘汰");
+        assert!(store.get("a").is_some());
+
+        store.upsert(file_entry("e", "src/e.rs")).unwrap();
+        assert!(store.get("c").is_none());
+        assert_eq!(store.stats().entry_count, 3);
+    }
+
+    #[test]
+    fn upsert_many_rejects_batch_over_limits() {
+        let mut store = ContextMemoryStore::new();
+        store.set_limits(2, usize::MAX);
+        store.upsert(file_entry("a", "src/a.rs")).unwrap();
+
+        let batch = vec![
+            file_entry("b", "src/b.rs"),
+            file_entry("c", "src/c.rs"),
+            file_entry("d", "src/d.rs"),
+        ];
+        assert!(store.upsert_many(batch).is_err());
+        assert_eq!(store.stats().entry_count, 1, "拒绝的批次不应修改现有数据");
+        assert!(store.get("a").is_some());
+
+        let size = ContextMemoryStore::estimate_size(&file_entry("b", "src/b.rs"));
+        store.set_limits(10, size * 2);
+        let batch = vec![
+            file_entry("b", "src/b.rs"),
+            file_entry("c", "src/c.rs"),
+            file_entry("d", "src/d.rs"),
+        ];
+        assert!(store.upsert_many(batch).is_err());
+        assert!(store.get("a").is_some());
+    }
+
+    #[test]
+    fn exact_match_outscores_fuzzy_match() {
+        let exact = exact_match_score("src/commands/context.rs", "context").unwrap();
+        let fuzzy = fuzzy_match_score("src/commands/context.rs", "cmdctx").unwrap();
+        assert!(exact > fuzzy);
+
+        // 文件名完全一致 > 文件名包含 > 仅路径包含
+        let same_name = exact_match_score("src/main", "main").unwrap();
+        let name_contains = exact_match_score("src/main.rs", "main").unwrap();
+        let path_contains = exact_match_score("src/main/mod.rs", "main").unwrap();
+        assert!(same_name > name_contains && name_contains > path_contains);
+
+        assert_eq!(exact_match_score("src/lib.rs", "main"), None);
+        assert_eq!(fuzzy_match_score("src/lib.rs", "main"), None);
+    }
+
+    #[test]
+    fn fuzzy_matches_fill_in_when_exact_matches_are_few() {
+        let mut store = ContextMemoryStore::new();
+        store.upsert_many(vec![
+            file_entry("e1", "src/main.rs"),
+            file_entry("e2", "src/domain/mod.rs"),
+            file_entry("e3", "src/mapping.rs"),
+            file_entry("e4", "src/lib.rs"),
+        ]).unwrap();
+
+        // 子串命中 2 条，少于默认的 FUZZY_FALLBACK_MIN_RESULTS，补充模糊匹配
+        let result = store.query(&text_query("main", None));
+        let ids: Vec<&str> = result.entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["e1", "e2", "e3"]);
+        assert!(result.scores["e2"] > result.scores["e3"], "子串匹配应排在模糊匹配之前");
+
+        // 子串命中数已满足 limit 时不做模糊匹配
+        let result = store.query(&text_query("main", Some(2)));
+        let ids: Vec<&str> = result.entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["e1", "e2"]);
+    }
+
+    #[test]
+    fn query_results_are_truncated_to_limit() {
+        let mut store = ContextMemoryStore::new();
+        for i in 0..6 {
+            store.upsert(file_entry(&format!("e{}", i), &format!("src/handler_{}.rs", i))).unwrap();
+        }
+
+        let result = store.query(&text_query("handler", Some(3)));
+        assert_eq!(result.entries.len(), 3);
+        assert_eq!(result.scores.len(), 3);
+        assert_eq!(result.total_tokens, 30);
+    }
+
+    #[test]
+    fn persisted_entries_survive_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("context_memory.json");
+
+        let mut store = ContextMemoryStore::with_persistence(path.clone());
+        store.upsert(file_entry("a", "src/a.rs")).unwrap();
+        store.upsert_many(vec![
+            file_entry("b", "src/b.rs"),
+            file_entry("c", "src/c.rs"),
+        ]).unwrap();
+        store.flush();
+        drop(store);
+
+        let reloaded = ContextMemoryStore::with_persistence(path);
+        assert_eq!(reloaded.stats().entry_count, 3);
+        for (id, expected_path) in [("a", "src/a.rs"), ("b", "src/b.rs"), ("c", "src/c.rs")] {
+            let entry = reloaded.get(id).expect("条目应从磁盘恢复");
+            match &entry.content {
+                ContextContent::File(file) => assert_eq!(file.path, expected_path),
+                other => panic!("内容类型不符: {:?}", other),
+            }
+            assert_eq!(entry.workspace_id.as_deref(), Some("ws"));
+        }
+    }
 }
@@ -1,5 +1,8 @@
 use crate::error::{AppError, Result};
-use std::path::Path;
+use crate::services::fs_utils::{is_binary_bytes, write_atomic};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::time::SystemTime;
 
@@ -19,9 +22,35 @@ pub struct FileMatch {
 pub struct CommandFile {
     pub name: String,
     pub description: Option<String>,
+    /// 参数提示（frontmatter 中的 argument-hint）
+    pub argument_hint: Option<String>,
     pub params: Option<Vec<CommandParam>>,
     pub content: String,
     pub file_path: String,
+    /// 命令来源
+    pub source: CommandSource,
+}
+
+/// 命令来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandSource {
+    /// 项目级（<work_dir>/.claude/commands）
+    Project,
+    /// 用户级（~/.claude/commands）
+    User,
+}
+
+/// 获取指定来源的命令目录
+fn commands_dir(source: CommandSource, work_dir: Option<&str>) -> Result<PathBuf> {
+    match source {
+        CommandSource::Project => Ok(Path::new(work_dir.unwrap_or("."))
+            .join(".claude")
+            .join("commands")),
+        CommandSource::User => dirs::home_dir()
+            .map(|home| home.join(".claude").join("commands"))
+            .ok_or_else(|| AppError::ConfigError("无法获取用户主目录".to_string())),
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -63,45 +92,52 @@ pub async fn read_directory(path: String) -> Result<Vec<FileInfo>> {
     for entry in entries {
         let entry = entry?;
         let metadata = entry.metadata()?;
-        
-        let file_path = entry.path();
-        let name = file_path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string();
-        
-        let is_dir = metadata.is_dir();
-        let size = if !is_dir { Some(metadata.len()) } else { None };
-        
-        // 获取修改时间
-        let modified = metadata.modified()
-            .ok()
-            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs().to_string());
-        
-        // 获取文件扩展名
-        let extension = if !is_dir {
-            file_path.extension()
-                .and_then(|ext| ext.to_str())
-                .map(|s| s.to_lowercase())
-        } else {
-            None
-        };
-        
-        let file_info = FileInfo {
-            name,
-            path: file_path.to_string_lossy().to_string(),
-            is_dir,
-            size,
-            modified,
-            extension,
-            children: None, // 子目录内容预留，需要懒加载
-        };
-        
-        files.push(file_info);
+        files.push(file_info_from_metadata(&entry.path(), &metadata));
     }
     
-    // 排序：目录在前，然后按名称排序
+    sort_dirs_first(&mut files);
+    
+    Ok(files)
+}
+
+/// 根据路径和元数据构建文件信息（不含子项）
+fn file_info_from_metadata(file_path: &Path, metadata: &fs::Metadata) -> FileInfo {
+    let name = file_path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("Unknown")
+        .to_string();
+
+    let is_dir = metadata.is_dir();
+    let size = if !is_dir { Some(metadata.len()) } else { None };
+
+    // 获取修改时间
+    let modified = metadata.modified()
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs().to_string());
+
+    // 获取文件扩展名
+    let extension = if !is_dir {
+        file_path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|s| s.to_lowercase())
+    } else {
+        None
+    };
+
+    FileInfo {
+        name,
+        path: file_path.to_string_lossy().to_string(),
+        is_dir,
+        size,
+        modified,
+        extension,
+        children: None, // 子目录内容预留，需要懒加载
+    }
+}
+
+/// 排序：目录在前，然后按名称排序
+fn sort_dirs_first(files: &mut [FileInfo]) {
     files.sort_by(|a, b| {
         match (a.is_dir, b.is_dir) {
             (true, false) => std::cmp::Ordering::Less,
@@ -109,8 +145,127 @@ pub async fn read_directory(path: String) -> Result<Vec<FileInfo>> {
             _ => a.name.cmp(&b.name),
         }
     });
-    
-    Ok(files)
+}
+
+/// 目录树的最大深度
+const MAX_TREE_DEPTH: usize = 16;
+
+/// 目录树的最大节点数，超过后停止遍历
+const MAX_TREE_NODES: usize = 20_000;
+
+/// 目录树结果
+#[derive(serde::Serialize)]
+pub struct DirectoryTree {
+    /// 根目录节点（字段与 FileInfo 相同）
+    #[serde(flatten)]
+    pub root: FileInfo,
+    /// 节点数超过上限而提前停止遍历时为 true，此时树不完整
+    pub truncated: bool,
+}
+
+/// 递归获取目录树（目录在前排序）
+///
+/// `max_depth` 为 1 时等同于 `read_directory`；不跟随符号链接，避免循环。
+/// `respect_gitignore` 为 true 时跳过 .gitignore 忽略的文件
+#[tauri::command]
+pub async fn get_directory_tree(
+    path: String,
+    max_depth: Option<usize>,
+    respect_gitignore: Option<bool>,
+) -> Result<DirectoryTree> {
+    let root = Path::new(&path);
+
+    if !root.exists() {
+        return Err(AppError::InvalidPath("路径不存在".to_string()));
+    }
+
+    if !root.is_dir() {
+        return Err(AppError::InvalidPath("不是目录".to_string()));
+    }
+
+    let max_depth = max_depth.unwrap_or(3).clamp(1, MAX_TREE_DEPTH);
+    build_directory_tree(root, max_depth, respect_gitignore.unwrap_or(true), MAX_TREE_NODES)
+}
+
+/// 遍历目录并组装目录树，节点数超过 `max_nodes` 时停止并标记为不完整
+fn build_directory_tree(
+    root: &Path,
+    max_depth: usize,
+    respect_gitignore: bool,
+    max_nodes: usize,
+) -> Result<DirectoryTree> {
+    let walker = ignore::WalkBuilder::new(root)
+        .max_depth(Some(max_depth))
+        .follow_links(false)
+        .hidden(false)
+        .ignore(false)
+        .parents(respect_gitignore)
+        .git_ignore(respect_gitignore)
+        .git_global(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .require_git(false)
+        // 跳过 .git 与编辑备份目录，二者都不属于用户的项目文件
+        .filter_entry(|entry| entry.file_name() != ".git" && entry.file_name() != BACKUP_DIR_NAME)
+        .build();
+
+    // 按父目录收集子项，最后自底向上组装
+    let mut children_map: HashMap<PathBuf, Vec<FileInfo>> = HashMap::new();
+    let mut node_count = 0;
+    let mut truncated = false;
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::debug!("[get_directory_tree] 跳过无法读取的条目: {}", e);
+                continue;
+            }
+        };
+
+        if entry.depth() == 0 {
+            continue;
+        }
+
+        node_count += 1;
+        if node_count > max_nodes {
+            tracing::info!("[get_directory_tree] 节点数超过上限 {}，停止遍历", max_nodes);
+            truncated = true;
+            break;
+        }
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if let Some(parent) = entry.path().parent() {
+            children_map
+                .entry(parent.to_path_buf())
+                .or_default()
+                .push(file_info_from_metadata(entry.path(), &metadata));
+        }
+    }
+
+    let root_metadata = fs::metadata(root)?;
+    let mut tree = file_info_from_metadata(root, &root_metadata);
+    attach_children(&mut tree, &mut children_map);
+
+    Ok(DirectoryTree { root: tree, truncated })
+}
+
+/// 将收集到的子项挂到对应目录节点上
+fn attach_children(node: &mut FileInfo, children_map: &mut HashMap<PathBuf, Vec<FileInfo>>) {
+    if !node.is_dir {
+        return;
+    }
+
+    if let Some(mut children) = children_map.remove(Path::new(&node.path)) {
+        for child in children.iter_mut() {
+            attach_children(child, children_map);
+        }
+        sort_dirs_first(&mut children);
+        node.children = Some(children);
+    }
 }
 
 /// 获取文件内容（限制大小）
@@ -138,6 +293,116 @@ pub async fn get_file_content(path: String) -> Result<String> {
     Ok(content)
 }
 
+/// 文件内容读取的默认字节上限
+const DEFAULT_MAX_CONTENT_BYTES: u64 = 1024 * 1024;
+
+/// 文件超过大小上限时，用于检测编码的开头字节数
+const ENCODING_SNIFF_BYTES: u64 = 8192;
+
+/// 带编码信息的文件内容
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileContent {
+    /// 文本内容（二进制或超过大小上限时为 None）
+    pub content: Option<String>,
+    /// 检测到的编码：utf-8、utf-8-bom、utf-16le、utf-16be；二进制文件为 binary
+    /// （超过大小上限时根据文件开头检测）
+    pub encoding: String,
+    pub is_binary: bool,
+    /// 是否存在无法解码的字节（已替换为 U+FFFD）
+    pub lossy: bool,
+    /// 是否因超过大小上限而省略内容
+    pub content_omitted: bool,
+    pub size: u64,
+}
+
+/// 读取文件内容并检测编码
+///
+/// 与 `get_file_content` 不同，二进制文件和非 UTF-8 文件不会报错，
+/// 而是通过 `is_binary` / `lossy` 字段说明情况
+#[tauri::command]
+pub async fn read_file_content(path: String, max_bytes: Option<u64>) -> Result<FileContent> {
+    let path_obj = Path::new(&path);
+
+    if !path_obj.exists() {
+        return Err(AppError::InvalidPath("文件不存在".to_string()));
+    }
+
+    if path_obj.is_dir() {
+        return Err(AppError::InvalidPath("是目录，不是文件".to_string()));
+    }
+
+    let size = fs::metadata(path_obj)?.len();
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_MAX_CONTENT_BYTES);
+
+    if size > max_bytes {
+        // 只读取文件开头用于检测编码，内容本身省略
+        let mut head = Vec::with_capacity(ENCODING_SNIFF_BYTES as usize);
+        fs::File::open(path_obj)?
+            .take(ENCODING_SNIFF_BYTES)
+            .read_to_end(&mut head)?;
+        let detected = decode_file_bytes(&head, size);
+        return Ok(FileContent {
+            content: None,
+            encoding: detected.encoding,
+            is_binary: detected.is_binary,
+            lossy: false,
+            content_omitted: true,
+            size,
+        });
+    }
+
+    let bytes = fs::read(path_obj)?;
+    Ok(decode_file_bytes(&bytes, size))
+}
+
+/// 按 BOM / UTF-8 / 有损 UTF-8 的顺序解码文件内容
+fn decode_file_bytes(bytes: &[u8], size: u64) -> FileContent {
+    let text = |content: String, encoding: &str, lossy: bool| FileContent {
+        content: Some(content),
+        encoding: encoding.to_string(),
+        is_binary: false,
+        lossy,
+        content_omitted: false,
+        size,
+    };
+
+    // UTF-16 含有大量 NUL 字节，需在二进制检测之前通过 BOM 识别
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        let content = String::from_utf16_lossy(&units);
+        let lossy = content.contains('\u{FFFD}') || rest.len() % 2 != 0;
+        return text(content, "utf-16le", lossy);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        let content = String::from_utf16_lossy(&units);
+        let lossy = content.contains('\u{FFFD}') || rest.len() % 2 != 0;
+        return text(content, "utf-16be", lossy);
+    }
+
+    if is_binary_bytes(bytes) {
+        return FileContent {
+            content: None,
+            encoding: "binary".to_string(),
+            is_binary: true,
+            lossy: false,
+            content_omitted: false,
+            size,
+        };
+    }
+
+    let (bytes, encoding) = match bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        Some(rest) => (rest, "utf-8-bom"),
+        None => (bytes, "utf-8"),
+    };
+
+    match std::str::from_utf8(bytes) {
+        Ok(content) => text(content.to_string(), encoding, false),
+        Err(_) => text(String::from_utf8_lossy(bytes).into_owned(), encoding, true),
+    }
+}
+
 /// 创建文件
 #[tauri::command]
 pub async fn create_file(path: String, content: Option<String>) -> Result<()> {
@@ -160,6 +425,115 @@ pub async fn create_file(path: String, content: Option<String>) -> Result<()> {
     Ok(())
 }
 
+/// 编辑预览中变更类型
+#[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditChangeType {
+    Added,
+    Modified,
+    Unchanged,
+}
+
+/// 编辑预览结果（不写入磁盘）
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditPreview {
+    pub file_path: String,
+    pub change_type: EditChangeType,
+    /// 原内容（新文件为 None）
+    pub old_content: Option<String>,
+    pub new_content: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// 预览对文件的修改：计算当前内容与新内容的行级差异，不修改磁盘文件
+#[tauri::command]
+pub async fn preview_edit(path: String, new_content: String) -> Result<EditPreview> {
+    let path_obj = Path::new(&path);
+
+    if path_obj.is_dir() {
+        return Err(AppError::InvalidPath("是目录，不是文件".to_string()));
+    }
+
+    let old_content = if path_obj.exists() {
+        Some(fs::read_to_string(path_obj)?)
+    } else {
+        None
+    };
+
+    let diff = similar::TextDiff::from_lines(old_content.as_deref().unwrap_or(""), &new_content);
+    let (mut additions, mut deletions) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            similar::ChangeTag::Insert => additions += 1,
+            similar::ChangeTag::Delete => deletions += 1,
+            similar::ChangeTag::Equal => {}
+        }
+    }
+
+    let change_type = match old_content {
+        None => EditChangeType::Added,
+        Some(_) if additions == 0 && deletions == 0 => EditChangeType::Unchanged,
+        Some(_) => EditChangeType::Modified,
+    };
+
+    Ok(EditPreview {
+        file_path: path,
+        change_type,
+        old_content,
+        new_content,
+        additions,
+        deletions,
+    })
+}
+
+/// 备份文件所在的目录名（位于被备份文件的同级目录）
+///
+/// 该目录会出现在用户项目中，目录树遍历时会跳过它（文件搜索本就跳过隐藏目录），
+/// 是否提交到版本库由用户的 .gitignore 决定
+const BACKUP_DIR_NAME: &str = ".polaris-backups";
+
+/// 写入文件前先备份原文件，再原子写入新内容
+///
+/// 备份保存在同级的 `.polaris-backups/<文件名>.<时间戳>.bak`（时间戳为本地时间
+/// `YYYYMMDD-HHMMSS.mmm`），返回备份路径供前端撤销；备份不会自动清理。
+/// 文件原本不存在时不做备份，返回 None
+#[tauri::command]
+pub async fn write_file_backup(path: String, content: String) -> Result<Option<String>> {
+    let path_obj = Path::new(&path);
+
+    if path_obj.is_dir() {
+        return Err(AppError::InvalidPath("是目录，不是文件".to_string()));
+    }
+
+    let backup_path = if path_obj.exists() {
+        let parent = path_obj.parent().unwrap_or_else(|| Path::new("."));
+        let file_name = path_obj.file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| AppError::InvalidPath("无效的文件名".to_string()))?;
+
+        let backup_dir = parent.join(BACKUP_DIR_NAME);
+        fs::create_dir_all(&backup_dir)?;
+
+        let backup_path = backup_dir.join(format!(
+            "{}.{}.bak",
+            file_name,
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        ));
+        fs::copy(path_obj, &backup_path)?;
+        tracing::info!("[write_file_backup] 已备份: {:?}", backup_path);
+
+        Some(backup_path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    write_atomic(path_obj, content.as_bytes())?;
+
+    Ok(backup_path)
+}
+
 /// 创建目录
 #[tauri::command]
 pub async fn create_directory(path: String) -> Result<()> {
@@ -213,27 +587,114 @@ pub async fn path_exists(path: String) -> Result<bool> {
     Ok(Path::new(&path).exists())
 }
 
-/// 读取工作区中的自定义命令
-/// 从 .claude/commands/ 目录读取 .md 文件
+/// 读取自定义命令
+/// 从项目级 .claude/commands/ 和用户级 ~/.claude/commands/ 目录读取 .md 文件，
+/// 同名命令以项目级为准
 #[tauri::command]
 pub async fn read_commands(work_dir: Option<String>) -> Result<Vec<CommandFile>> {
-    let mut commands = Vec::new();
+    let project_dir = commands_dir(CommandSource::Project, work_dir.as_deref())?;
+    let user_dir = match commands_dir(CommandSource::User, None) {
+        Ok(user_dir) => Some(user_dir),
+        Err(e) => {
+            tracing::debug!("[read_commands] 跳过用户级命令: {}", e);
+            None
+        }
+    };
 
-    let work_path = work_dir.unwrap_or_else(|| String::from("."));
-    let base_path = Path::new(&work_path);
+    Ok(read_command_dirs(&project_dir, user_dir.as_deref()))
+}
 
-    // 构建 .claude/commands/ 路径
-    let commands_dir = base_path.join(".claude").join("commands");
+/// 合并项目级与用户级命令目录，同名命令以项目级为准
+fn read_command_dirs(project_dir: &Path, user_dir: Option<&Path>) -> Vec<CommandFile> {
+    let mut commands = read_command_dir(project_dir, CommandSource::Project);
 
-    if !commands_dir.exists() {
-        return Ok(commands);
+    if let Some(user_dir) = user_dir {
+        for cmd in read_command_dir(user_dir, CommandSource::User) {
+            if !commands.iter().any(|c| c.name == cmd.name) {
+                commands.push(cmd);
+            }
+        }
     }
 
-    // 读取目录中的 .md 文件
-    let entries = fs::read_dir(&commands_dir)?;
+    // 按名称排序
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
 
-    for entry in entries {
-        let entry = entry?;
+    commands
+}
+
+/// 创建自定义命令文件，返回文件路径
+///
+/// 同名命令已存在时需要 `overwrite` 为 true 才会覆盖
+#[tauri::command]
+pub async fn create_command(
+    scope: CommandSource,
+    name: String,
+    content: String,
+    description: Option<String>,
+    work_dir: Option<String>,
+    overwrite: Option<bool>,
+) -> Result<String> {
+    let path = command_file_path(scope, &name, work_dir.as_deref())?;
+
+    if path.exists() && !overwrite.unwrap_or(false) {
+        return Err(AppError::InvalidPath(format!("命令已存在: {}", name)));
+    }
+
+    let mut file_content = String::new();
+    if let Some(description) = description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        // 转义为 JSON 字符串，兼容 YAML 双引号字符串语法
+        file_content.push_str(&format!("---\ndescription: {}\n---\n\n", serde_json::Value::from(description)));
+    }
+    file_content.push_str(&content);
+    if !file_content.ends_with('\n') {
+        file_content.push('\n');
+    }
+
+    tracing::info!("[create_command] 写入命令文件: {:?}", path);
+    write_atomic(&path, file_content.as_bytes())?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// 删除自定义命令文件
+#[tauri::command]
+pub async fn delete_command(
+    scope: CommandSource,
+    name: String,
+    work_dir: Option<String>,
+) -> Result<()> {
+    let path = command_file_path(scope, &name, work_dir.as_deref())?;
+
+    if !path.is_file() {
+        return Err(AppError::InvalidPath(format!("命令不存在: {}", name)));
+    }
+
+    tracing::info!("[delete_command] 删除命令文件: {:?}", path);
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// 校验命令名并返回命令文件路径
+fn command_file_path(scope: CommandSource, name: &str, work_dir: Option<&str>) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.chars().any(|c| c.is_whitespace() || matches!(c, '/' | '\\' | ':'));
+    if !valid {
+        return Err(AppError::InvalidPath(format!("无效的命令名: {}", name)));
+    }
+
+    Ok(commands_dir(scope, work_dir)?.join(format!("{}.md", name)))
+}
+
+/// 读取目录中的命令文件，无法读取的文件会被跳过
+fn read_command_dir(dir: &Path, source: CommandSource) -> Vec<CommandFile> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut commands = Vec::new();
+    for entry in entries.flatten() {
         let path = entry.path();
 
         // 只处理 .md 文件
@@ -241,19 +702,19 @@ pub async fn read_commands(work_dir: Option<String>) -> Result<Vec<CommandFile>>
             continue;
         }
 
-        // 读取文件内容
-        let content = fs::read_to_string(&path)?;
-
-        // 解析文件
-        if let Ok(cmd) = parse_command_file(&content, &path) {
-            commands.push(cmd);
+        // 读取并解析文件
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                if let Ok(mut cmd) = parse_command_file(&content, &path) {
+                    cmd.source = source;
+                    commands.push(cmd);
+                }
+            }
+            Err(e) => tracing::warn!("[read_commands] 读取命令文件失败 {:?}: {}", path, e),
         }
     }
 
-    // 按名称排序
-    commands.sort_by(|a, b| a.name.cmp(&b.name));
-
-    Ok(commands)
+    commands
 }
 
 /// 解析命令文件（YAML frontmatter + 内容）
@@ -284,6 +745,7 @@ fn parse_command_file(content: &str, path: &Path) -> Result<CommandFile> {
         .to_string();
 
     let mut description = None;
+    let mut argument_hint = None;
     let mut params = None;
 
     // 解析 frontmatter
@@ -294,7 +756,9 @@ fn parse_command_file(content: &str, path: &Path) -> Result<CommandFile> {
         for line in frontmatter.lines() {
             let line = line.trim();
             if let Some(rest) = line.strip_prefix("description:") {
-                description = Some(rest.trim().trim_matches('"').trim_matches('\'').to_string());
+                description = Some(unquote_frontmatter_value(rest));
+            } else if let Some(rest) = line.strip_prefix("argument-hint:") {
+                argument_hint = Some(unquote_frontmatter_value(rest));
             } else if let Some(rest) = line.strip_prefix("params:") {
                 // 简单参数解析
                 params = Some(parse_simple_params(rest.trim()));
@@ -318,12 +782,19 @@ fn parse_command_file(content: &str, path: &Path) -> Result<CommandFile> {
     Ok(CommandFile {
         name,
         description,
+        argument_hint,
         params,
         content: command_content,
         file_path: path.to_string_lossy().to_string(),
+        source: CommandSource::Project,
     })
 }
 
+/// 去除 frontmatter 值两端的空白和引号
+fn unquote_frontmatter_value(value: &str) -> String {
+    value.trim().trim_matches('"').trim_matches('\'').to_string()
+}
+
 /// 简单参数解析
 fn parse_simple_params(params_str: &str) -> Vec<CommandParam> {
     let mut result = Vec::new();
@@ -462,4 +933,284 @@ fn search_recursive(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn child_names(node: &FileInfo) -> Vec<&str> {
+        node.children.iter().flatten().map(|c| c.name.as_str()).collect()
+    }
+
+    fn child<'a>(node: &'a FileInfo, name: &str) -> &'a FileInfo {
+        node.children.iter().flatten().find(|c| c.name == name).unwrap()
+    }
+
+    #[test]
+    fn directory_tree_stops_at_max_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        fs::write(dir.path().join("a/b/c/deep.txt"), "x").unwrap();
+        fs::write(dir.path().join("top.txt"), "x").unwrap();
+
+        let tree = build_directory_tree(dir.path(), 2, true, MAX_TREE_NODES).unwrap();
+
+        assert!(!tree.truncated);
+        assert_eq!(child_names(&tree.root), vec!["a", "top.txt"]);
+        let b = child(child(&tree.root, "a"), "b");
+        assert!(b.children.is_none());
+    }
+
+    #[test]
+    fn directory_tree_respects_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "ignored.txt\nbuild/\n").unwrap();
+        fs::write(dir.path().join("ignored.txt"), "x").unwrap();
+        fs::write(dir.path().join("kept.txt"), "x").unwrap();
+        fs::create_dir(dir.path().join("build")).unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+
+        let tree = build_directory_tree(dir.path(), 3, true, MAX_TREE_NODES).unwrap();
+        assert_eq!(child_names(&tree.root), vec![".gitignore", "kept.txt"]);
+
+        let tree = build_directory_tree(dir.path(), 3, false, MAX_TREE_NODES).unwrap();
+        assert_eq!(child_names(&tree.root), vec!["build", ".gitignore", "ignored.txt", "kept.txt"]);
+    }
+
+    #[test]
+    fn directory_tree_reports_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), "x").unwrap();
+        }
+
+        let tree = build_directory_tree(dir.path(), 1, true, 2).unwrap();
+
+        assert!(tree.truncated);
+        assert_eq!(tree.root.children.as_ref().map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn read_commands_parses_argument_hint_and_prefers_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let project_dir = dir.path().join("project");
+        let user_dir = dir.path().join("user");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::create_dir_all(&user_dir).unwrap();
+        fs::write(
+            project_dir.join("review.md"),
+            "---\ndescription: \"审查代码\"\nargument-hint: [file]\n---\n\n审查 $ARGUMENTS\n",
+        ).unwrap();
+        fs::write(user_dir.join("review.md"), "---\nargument-hint: <pr>\n---\n\n用户级审查\n").unwrap();
+        fs::write(user_dir.join("deploy.md"), "---\nargument-hint: '<env>'\n---\n\n部署\n").unwrap();
+        fs::write(user_dir.join("notes.txt"), "不是命令").unwrap();
+
+        let commands = read_command_dirs(&project_dir, Some(&user_dir));
+
+        let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["deploy", "review"]);
+
+        let deploy = &commands[0];
+        assert_eq!(deploy.source, CommandSource::User);
+        assert_eq!(deploy.argument_hint.as_deref(), Some("<env>"));
+        assert_eq!(deploy.description, None);
+
+        // 同名命令以项目级为准
+        let review = &commands[1];
+        assert_eq!(review.source, CommandSource::Project);
+        assert_eq!(review.argument_hint.as_deref(), Some("[file]"));
+        assert_eq!(review.description.as_deref(), Some("审查代码"));
+    }
+
+    #[test]
+    fn command_names_with_path_components_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let work_dir = dir.path().to_str().unwrap();
+
+        for name in ["", "../escape", "..", ".hidden", "a/b", "a\\b", "c:evil", "two words"] {
+            assert!(
+                command_file_path(CommandSource::Project, name, Some(work_dir)).is_err(),
+                "应拒绝命令名 {:?}",
+                name
+            );
+            let result = tauri::async_runtime::block_on(delete_command(
+                CommandSource::Project,
+                name.to_string(),
+                Some(work_dir.to_string()),
+            ));
+            assert!(result.is_err());
+        }
+
+        let path = command_file_path(CommandSource::Project, "review-pr", Some(work_dir)).unwrap();
+        assert_eq!(path, dir.path().join(".claude").join("commands").join("review-pr.md"));
+    }
+
+    #[test]
+    fn create_command_requires_overwrite_for_existing_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let work_dir = Some(dir.path().to_string_lossy().to_string());
+        let create = |content: &str, overwrite: Option<bool>| {
+            tauri::async_runtime::block_on(create_command(
+                CommandSource::Project,
+                "review".to_string(),
+                content.to_string(),
+                Some("审查".to_string()),
+                work_dir.clone(),
+                overwrite,
+            ))
+        };
+
+        let path = create("第一版", None).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "---\ndescription: \"审查\"\n---\n\n第一版\n");
+
+        assert!(create("第二版", None).is_err());
+        assert!(create("第二版", Some(false)).is_err());
+        assert!(fs::read_to_string(&path).unwrap().contains("第一版"));
+
+        create("第二版", Some(true)).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("第二版"));
+
+        let delete = || tauri::async_runtime::block_on(delete_command(
+            CommandSource::Project,
+            "review".to_string(),
+            work_dir.clone(),
+        ));
+        delete().unwrap();
+        assert!(!Path::new(&path).exists());
+        assert!(delete().is_err(), "删除不存在的命令应报错");
+    }
+
+    #[test]
+    fn write_file_backup_keeps_previous_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        let write = |content: &str| {
+            tauri::async_runtime::block_on(write_file_backup(
+                path.to_string_lossy().to_string(),
+                content.to_string(),
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(write("v1"), None, "新文件不需要备份");
+
+        let backup = PathBuf::from(write("v2").unwrap());
+        assert_eq!(backup.parent(), Some(dir.path().join(BACKUP_DIR_NAME).as_path()));
+        let backup_name = backup.file_name().unwrap().to_str().unwrap();
+        let timestamp = backup_name
+            .strip_prefix("main.rs.")
+            .and_then(|rest| rest.strip_suffix(".bak"))
+            .unwrap();
+        assert!(
+            chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d-%H%M%S%.3f").is_ok(),
+            "时间戳格式不符: {}",
+            timestamp
+        );
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "v1");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v2");
+
+        // 备份目录不出现在目录树中
+        let tree = build_directory_tree(dir.path(), 3, false, MAX_TREE_NODES).unwrap();
+        assert_eq!(child_names(&tree.root), vec!["main.rs"]);
+    }
+
+    #[test]
+    fn preview_edit_classifies_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        let preview = |content: &str| {
+            tauri::async_runtime::block_on(preview_edit(
+                path.to_string_lossy().to_string(),
+                content.to_string(),
+            ))
+            .unwrap()
+        };
+
+        let added = preview("a\nb\n");
+        assert!(matches!(added.change_type, EditChangeType::Added));
+        assert_eq!((added.additions, added.deletions), (2, 0));
+        assert!(added.old_content.is_none());
+        assert!(!path.exists(), "预览不应写入磁盘");
+
+        fs::write(&path, "a\nb\n").unwrap();
+        let unchanged = preview("a\nb\n");
+        assert!(matches!(unchanged.change_type, EditChangeType::Unchanged));
+        assert_eq!((unchanged.additions, unchanged.deletions), (0, 0));
+
+        let modified = preview("a\nc\nd\n");
+        assert!(matches!(modified.change_type, EditChangeType::Modified));
+        assert_eq!((modified.additions, modified.deletions), (2, 1));
+        assert_eq!(modified.old_content.as_deref(), Some("a\nb\n"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+
+        let is_dir = tauri::async_runtime::block_on(preview_edit(
+            dir.path().to_string_lossy().to_string(),
+            String::new(),
+        ));
+        assert!(is_dir.is_err());
+    }
+
+    #[test]
+    fn decode_plain_and_bom_utf8() {
+        let plain = decode_file_bytes("你好".as_bytes(), 6);
+        assert_eq!(plain.content.as_deref(), Some("你好"));
+        assert_eq!(plain.encoding, "utf-8");
+        assert!(!plain.lossy);
+
+        let bom = decode_file_bytes(b"\xEF\xBB\xBFhello", 8);
+        assert_eq!(bom.content.as_deref(), Some("hello"));
+        assert_eq!(bom.encoding, "utf-8-bom");
+    }
+
+    #[test]
+    fn decode_utf16_with_bom() {
+        let le = decode_file_bytes(&[0xFF, 0xFE, b'h', 0, b'i', 0], 6);
+        assert_eq!(le.content.as_deref(), Some("hi"));
+        assert_eq!(le.encoding, "utf-16le");
+        assert!(!le.is_binary);
+        assert!(!le.lossy);
+
+        let be = decode_file_bytes(&[0xFE, 0xFF, 0, b'h', 0, b'i'], 6);
+        assert_eq!(be.content.as_deref(), Some("hi"));
+        assert_eq!(be.encoding, "utf-16be");
+
+        // 奇数长度的 UTF-16 内容视为有损
+        let odd = decode_file_bytes(&[0xFF, 0xFE, b'h', 0, b'i'], 5);
+        assert_eq!(odd.content.as_deref(), Some("h"));
+        assert!(odd.lossy);
+    }
+
+    #[test]
+    fn decode_invalid_utf8_is_lossy() {
+        let decoded = decode_file_bytes(b"caf\xE9", 4);
+
+        assert_eq!(decoded.content.as_deref(), Some("caf\u{FFFD}"));
+        assert_eq!(decoded.encoding, "utf-8");
+        assert!(decoded.lossy);
+    }
+
+    #[test]
+    fn decode_binary_omits_content() {
+        let decoded = decode_file_bytes(&[0x89, b'P', b'N', b'G', 0, 0, 1], 7);
+
+        assert!(decoded.is_binary);
+        assert!(decoded.content.is_none());
+        assert_eq!(decoded.encoding, "binary");
+    }
+
+    #[test]
+    fn oversized_file_reports_detected_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.txt");
+        fs::write(&path, b"\xEF\xBB\xBFsome long text").unwrap();
+
+        let result = tauri::async_runtime::block_on(
+            read_file_content(path.to_string_lossy().to_string(), Some(4)),
+        ).unwrap();
+
+        assert!(result.content_omitted);
+        assert!(result.content.is_none());
+        assert_eq!(result.encoding, "utf-8-bom");
+    }
+}
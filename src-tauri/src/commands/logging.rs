@@ -1,10 +1,9 @@
 use std::path::PathBuf;
-use std::io;
+use std::io::{self, Write};
 use tauri::State;
 
 use crate::error::{AppError, Result};
 use crate::services::logger;
-use crate::services::config_store::ConfigStore;
 use crate::AppState;
 
 /// 获取日志目录
@@ -13,6 +12,12 @@ pub fn get_log_dir() -> PathBuf {
     logger::Logger::log_dir()
 }
 
+/// 获取日志目录路径（字符串形式，供前端"打开日志"使用）
+#[tauri::command]
+pub fn get_log_path() -> String {
+    get_log_dir().to_string_lossy().to_string()
+}
+
 /// 读取日志内容
 #[tauri::command]
 pub fn read_logs(max_lines: usize) -> Result<String> {
@@ -20,6 +25,13 @@ pub fn read_logs(max_lines: usize) -> Result<String> {
         .map_err(|e: io::Error| AppError::Unknown(e.to_string()))
 }
 
+/// 运行时修改日志级别（无需重启）
+#[tauri::command]
+pub fn set_log_level(level: String) -> Result<()> {
+    logger::Logger::set_level(&level)
+        .map_err(AppError::ConfigError)
+}
+
 /// 清空日志文件
 #[tauri::command]
 pub fn clear_logs() -> Result<()> {
@@ -51,9 +63,12 @@ pub fn open_log_dir() -> Result<()> {
     Ok(())
 }
 
-/// 设置日志开关
+/// 设置日志开关（立即生效并保存到配置）
 #[tauri::command]
 pub fn set_logging_enabled(enabled: bool, state: State<AppState>) -> Result<()> {
+    logger::Logger::set_enabled(enabled)
+        .map_err(AppError::ConfigError)?;
+
     let store = state.config_store.lock()
         .map_err(|e| AppError::Unknown(e.to_string()))?;
     let mut config = store.get().clone();
@@ -73,3 +88,101 @@ pub fn is_logging_enabled(state: State<AppState>) -> bool {
         .map(|store| store.enable_logging())
         .unwrap_or(false)
 }
+
+/// 导出时需要脱敏的配置字段（同时匹配 snake_case 与 camelCase）
+const SENSITIVE_CONFIG_KEYS: &[&str] = &[
+    "api_key", "apiKey",
+    "secret_key", "secretKey",
+    "app_secret", "appSecret",
+    // 自定义环境变量可能包含令牌或代理凭据
+    "env",
+];
+
+/// 递归脱敏 JSON 中的敏感字段
+fn redact_sensitive(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                if SENSITIVE_CONFIG_KEYS.contains(&key.as_str()) {
+                    if !val.is_null() {
+                        *val = serde_json::Value::String("***".to_string());
+                    }
+                } else {
+                    redact_sensitive(val);
+                }
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for item in arr.iter_mut() {
+                redact_sensitive(item);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 导出日志（日志文件 + 脱敏后的配置）为 zip 压缩包
+///
+/// `dest_path` 为目录时，在其中生成带时间戳的文件名；否则直接作为压缩包路径
+#[tauri::command]
+pub fn export_logs(dest_path: String, state: State<AppState>) -> Result<String> {
+    let config = {
+        let store = state.config_store.lock()
+            .map_err(|e| AppError::Unknown(e.to_string()))?;
+        store.get().clone()
+    };
+
+    let log_files = logger::Logger::log_files();
+    if log_files.is_empty() {
+        return Err(AppError::ConfigError(if config.enable_logging {
+            "暂无日志文件可导出".to_string()
+        } else {
+            "文件日志未启用，请先开启日志后复现问题再导出".to_string()
+        }));
+    }
+
+    let mut config_value = serde_json::to_value(&config)?;
+    redact_sensitive(&mut config_value);
+    let config_json = serde_json::to_string_pretty(&config_value)?;
+
+    let dest = PathBuf::from(&dest_path);
+    let archive_path = if dest.is_dir() {
+        dest.join(format!(
+            "polaris-logs-{}.zip",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    } else {
+        dest
+    };
+
+    if let Some(parent) = archive_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    let file = std::fs::File::create(&archive_path)?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let zip_err = |e: zip::result::ZipError| AppError::Unknown(format!("写入压缩包失败: {}", e));
+
+    for log_file in log_files {
+        let name = match log_file.file_name().and_then(|n| n.to_str()) {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        let content = std::fs::read(&log_file)?;
+        zip.start_file(format!("logs/{}", name), options).map_err(zip_err)?;
+        zip.write_all(&content)?;
+    }
+
+    zip.start_file("config.json", options).map_err(zip_err)?;
+    zip.write_all(config_json.as_bytes())?;
+
+    zip.finish().map_err(zip_err)?;
+
+    tracing::info!("[export_logs] 日志已导出: {:?}", archive_path);
+    Ok(archive_path.to_string_lossy().to_string())
+}
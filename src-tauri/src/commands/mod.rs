@@ -3,27 +3,43 @@ pub mod workspace;
 pub mod file_explorer;
 pub mod window;
 pub mod context;
+pub mod logging;
+pub mod session;
 
 // 重新导出命令函数，确保它们在模块级别可见
-pub use chat::{start_chat, continue_chat, interrupt_chat};
 pub use chat::{
-    list_iflow_sessions, get_iflow_session_history,
-    get_iflow_file_contexts, get_iflow_token_stats,
+    start_chat, continue_chat, continue_latest_chat, regenerate_last, interrupt_chat, get_last_session,
+    drain_session_events,
+    get_session_capacity, reveal_session_file,
+};
+pub use chat::{
+    list_iflow_sessions, get_iflow_session_history, get_iflow_session_tree,
+    get_iflow_file_contexts, diff_session_file_contexts, get_iflow_token_stats, list_all_sessions,
+    get_project_token_summary,
 };
 pub use workspace::validate_workspace_path;
 pub use workspace::get_directory_info;
+pub use workspace::{get_workspace_info, get_claude_md, set_claude_md};
 pub use file_explorer::{
-    read_directory, get_file_content, create_file, create_directory,
-    delete_file, rename_file, path_exists, read_commands, search_files
+    read_directory, get_directory_tree, get_file_content, read_file_content, create_file,
+    write_file_backup, preview_edit, create_directory,
+    delete_file, rename_file, path_exists, read_commands, create_command, delete_command,
+    search_files,
 };
 pub use window::{
     show_floating_window, show_main_window, toggle_floating_window,
     is_floating_window_visible, set_floating_window_position, get_floating_window_position
 };
 
+// 会话元数据命令
+pub use session::{
+    set_session_title, get_session_title, set_session_pinned,
+    add_session_tag, remove_session_tag, list_session_tags,
+};
+
 // 上下文管理命令
 pub use context::{
     context_upsert, context_upsert_many, context_query, context_get_all,
-    context_remove, context_clear,
+    context_remove, context_clear, context_stats,
     ide_report_current_file, ide_report_file_structure, ide_report_diagnostics,
 };
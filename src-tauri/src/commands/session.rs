@@ -0,0 +1,84 @@
+//! 会话元数据相关命令
+
+use crate::error::{AppError, Result};
+use crate::AppState;
+use tauri::State;
+
+/// 设置会话自定义标题（传入空标题时恢复为自动生成的标题）
+#[tauri::command]
+pub fn set_session_title(
+    session_id: String,
+    title: Option<String>,
+    state: State<AppState>,
+) -> Result<()> {
+    tracing::info!("[set_session_title] 会话: {}", session_id);
+
+    let mut store = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    store.set_title(&session_id, title)
+}
+
+/// 设置会话置顶状态
+#[tauri::command]
+pub fn set_session_pinned(
+    session_id: String,
+    pinned: bool,
+    state: State<AppState>,
+) -> Result<()> {
+    tracing::info!("[set_session_pinned] 会话: {}, 置顶: {}", session_id, pinned);
+
+    let mut store = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    store.set_pinned(&session_id, pinned)
+}
+
+/// 为会话添加标签（不区分大小写，自动去重）
+#[tauri::command]
+pub fn add_session_tag(
+    session_id: String,
+    tag: String,
+    state: State<AppState>,
+) -> Result<Vec<String>> {
+    let mut store = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    store.add_tag(&session_id, &tag)?;
+    Ok(store.tags(&session_id).to_vec())
+}
+
+/// 移除会话标签
+#[tauri::command]
+pub fn remove_session_tag(
+    session_id: String,
+    tag: String,
+    state: State<AppState>,
+) -> Result<Vec<String>> {
+    let mut store = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    store.remove_tag(&session_id, &tag)?;
+    Ok(store.tags(&session_id).to_vec())
+}
+
+/// 列出标签：指定会话时返回该会话的标签，否则返回所有会话使用过的标签
+#[tauri::command]
+pub fn list_session_tags(
+    session_id: Option<String>,
+    state: State<AppState>,
+) -> Result<Vec<String>> {
+    let store = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    Ok(match session_id {
+        Some(id) => store.tags(&id).to_vec(),
+        None => store.all_tags(),
+    })
+}
+
+/// 获取会话自定义标题
+#[tauri::command]
+pub fn get_session_title(
+    session_id: String,
+    state: State<AppState>,
+) -> Result<Option<String>> {
+    let store = state.session_meta.lock()
+        .map_err(|e| AppError::Unknown(e.to_string()))?;
+    Ok(store.title(&session_id).map(str::to_string))
+}
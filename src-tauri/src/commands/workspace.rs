@@ -1,5 +1,7 @@
 use crate::error::{AppError, Result};
-use std::path::Path;
+use crate::services::fs_utils::write_atomic;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
 /// 验证工作区路径
 #[tauri::command]
@@ -54,4 +56,273 @@ pub struct DirectoryInfo {
     pub name: String,
     pub path: String,
     pub has_git: bool,
-}
\ No newline at end of file
+}
+
+/// 文件数统计上限（超过后停止遍历，结果为估算值）
+const MAX_FILE_COUNT_SCAN: usize = 10_000;
+
+/// 工作区详细信息
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceInfo {
+    /// 路径是否存在
+    pub exists: bool,
+    /// 是否为目录
+    pub is_dir: bool,
+    /// 是否为 Git 仓库
+    pub is_git_repo: bool,
+    /// 是否可写
+    pub is_writable: bool,
+    /// 是否存在 CLAUDE.md
+    pub has_claude_md: bool,
+    /// 是否存在 .polaris.json
+    pub has_polaris_config: bool,
+    /// 文件数（遵循 .gitignore，超过上限时为上限值）
+    pub file_count_estimate: usize,
+    /// 文件数是否达到统计上限
+    pub file_count_truncated: bool,
+}
+
+/// 获取工作区详细信息（用于启动会话前提示只读、非 Git 仓库等情况）
+///
+/// 大目录的文件遍历较慢，放到阻塞线程池中执行
+#[tauri::command]
+pub async fn get_workspace_info(path: String) -> Result<WorkspaceInfo> {
+    tauri::async_runtime::spawn_blocking(move || workspace_info(Path::new(&path)))
+        .await
+        .map_err(|e| AppError::Unknown(e.to_string()))
+}
+
+/// 收集工作区信息
+fn workspace_info(path_obj: &Path) -> WorkspaceInfo {
+    let exists = path_obj.exists();
+    let is_dir = path_obj.is_dir();
+    if !is_dir {
+        return WorkspaceInfo {
+            exists,
+            is_dir,
+            is_git_repo: false,
+            is_writable: false,
+            has_claude_md: false,
+            has_polaris_config: false,
+            file_count_estimate: 0,
+            file_count_truncated: false,
+        };
+    }
+
+    let file_count_estimate = ignore::WalkBuilder::new(path_obj)
+        .follow_links(false)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .take(MAX_FILE_COUNT_SCAN)
+        .count();
+
+    WorkspaceInfo {
+        exists,
+        is_dir,
+        is_git_repo: path_obj.join(".git").exists(),
+        is_writable: is_dir_writable(path_obj),
+        has_claude_md: path_obj.join("CLAUDE.md").is_file()
+            || path_obj.join(".claude").join("CLAUDE.md").is_file(),
+        has_polaris_config: path_obj.join(".polaris.json").is_file(),
+        file_count_estimate,
+        file_count_truncated: file_count_estimate >= MAX_FILE_COUNT_SCAN,
+    }
+}
+
+/// 检测目录是否可写
+///
+/// 先检查只读属性；未标记只读时再创建探测文件确认（ACL 等限制无法仅凭属性判断）。
+/// 探测文件在句柄关闭后删除，否则 Windows 下删除会失败而残留文件
+fn is_dir_writable(dir: &Path) -> bool {
+    match std::fs::metadata(dir) {
+        Ok(meta) if !meta.permissions().readonly() => {}
+        _ => return false,
+    }
+
+    let probe = dir.join(format!(".polaris-write-test-{}", Uuid::new_v4()));
+    let created = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .map(drop)
+        .is_ok();
+
+    if created {
+        if let Err(e) = std::fs::remove_file(&probe) {
+            tracing::warn!("[is_dir_writable] 删除探测文件失败 {:?}: {}", probe, e);
+        }
+    }
+    created
+}
+
+/// CLAUDE.md 所在位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClaudeMdLocation {
+    /// 工作区根目录的 CLAUDE.md
+    Root,
+    /// 工作区的 .claude/CLAUDE.md
+    Nested,
+}
+
+impl ClaudeMdLocation {
+    /// 按 Claude Code 的加载顺序排列
+    const ALL: [Self; 2] = [Self::Root, Self::Nested];
+
+    fn path_in(self, work_dir: &Path) -> PathBuf {
+        match self {
+            Self::Root => work_dir.join("CLAUDE.md"),
+            Self::Nested => work_dir.join(".claude").join("CLAUDE.md"),
+        }
+    }
+}
+
+/// 工作区中的一个 CLAUDE.md 文件
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeMdFile {
+    pub location: ClaudeMdLocation,
+    pub path: String,
+    pub content: String,
+}
+
+/// 读取工作区的 CLAUDE.md（项目级指令）
+///
+/// Claude Code 会同时加载根目录的 CLAUDE.md 和 .claude/CLAUDE.md，两者都存在时按此顺序全部返回，
+/// 各自带有来源位置；均不存在时返回空列表
+#[tauri::command]
+pub fn get_claude_md(work_dir: String) -> Result<Vec<ClaudeMdFile>> {
+    read_claude_md_files(Path::new(&work_dir))
+}
+
+fn read_claude_md_files(work_dir: &Path) -> Result<Vec<ClaudeMdFile>> {
+    let mut files = Vec::new();
+    for location in ClaudeMdLocation::ALL {
+        let path = location.path_in(work_dir);
+        if !path.is_file() {
+            continue;
+        }
+
+        tracing::info!("[get_claude_md] 读取: {:?}", path);
+        files.push(ClaudeMdFile {
+            location,
+            content: std::fs::read_to_string(&path)?,
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+    Ok(files)
+}
+
+/// 写入工作区的 CLAUDE.md
+///
+/// 只写入 `location` 指定的一个文件（默认根目录的 CLAUDE.md），不修改另一处的文件；
+/// `content` 按原样写入，应为该文件自身的内容
+#[tauri::command]
+pub fn set_claude_md(work_dir: String, content: String, location: Option<ClaudeMdLocation>) -> Result<()> {
+    write_claude_md(Path::new(&work_dir), &content, location.unwrap_or(ClaudeMdLocation::Root))
+}
+
+fn write_claude_md(work_dir: &Path, content: &str, location: ClaudeMdLocation) -> Result<()> {
+    if !work_dir.is_dir() {
+        return Err(AppError::InvalidPath("路径不存在或不是目录".to_string()));
+    }
+
+    let path = location.path_in(work_dir);
+    tracing::info!("[set_claude_md] 写入: {:?}", path);
+    write_atomic(&path, content.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writable_dir_probe_leaves_no_files() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(is_dir_writable(dir.path()));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn readonly_dir_is_not_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        assert!(!is_dir_writable(dir.path()));
+
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn locations(files: &[ClaudeMdFile]) -> Vec<(ClaudeMdLocation, &str)> {
+        files.iter().map(|f| (f.location, f.content.as_str())).collect()
+    }
+
+    #[test]
+    fn claude_md_absent() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_claude_md_files(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn claude_md_root_only() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("CLAUDE.md"), "root").unwrap();
+
+        let files = read_claude_md_files(dir.path()).unwrap();
+        assert_eq!(locations(&files), vec![(ClaudeMdLocation::Root, "root")]);
+        assert_eq!(files[0].path, dir.path().join("CLAUDE.md").to_string_lossy());
+    }
+
+    #[test]
+    fn claude_md_nested_only() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".claude")).unwrap();
+        std::fs::write(dir.path().join(".claude").join("CLAUDE.md"), "nested").unwrap();
+
+        let files = read_claude_md_files(dir.path()).unwrap();
+        assert_eq!(locations(&files), vec![(ClaudeMdLocation::Nested, "nested")]);
+    }
+
+    #[test]
+    fn claude_md_both_are_returned_and_written_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        write_claude_md(dir.path(), "root", ClaudeMdLocation::Root).unwrap();
+        write_claude_md(dir.path(), "nested", ClaudeMdLocation::Nested).unwrap();
+
+        let files = read_claude_md_files(dir.path()).unwrap();
+        assert_eq!(
+            locations(&files),
+            vec![(ClaudeMdLocation::Root, "root"), (ClaudeMdLocation::Nested, "nested")]
+        );
+
+        // 写入一处不影响另一处
+        write_claude_md(dir.path(), "root v2", ClaudeMdLocation::Root).unwrap();
+        let files = read_claude_md_files(dir.path()).unwrap();
+        assert_eq!(
+            locations(&files),
+            vec![(ClaudeMdLocation::Root, "root v2"), (ClaudeMdLocation::Nested, "nested")]
+        );
+
+        assert!(write_claude_md(&dir.path().join("missing"), "x", ClaudeMdLocation::Root).is_err());
+    }
+
+    #[test]
+    fn workspace_info_for_missing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = workspace_info(&dir.path().join("missing"));
+
+        assert!(!info.exists);
+        assert!(!info.is_writable);
+        assert_eq!(info.file_count_estimate, 0);
+    }
+}
@@ -4,30 +4,52 @@ mod services;
 mod commands;
 
 use error::Result;
-use models::config::{Config, HealthStatus};
+use models::config::{Config, HealthStatus, ModelPrice, PromptPreset};
 use services::config_store::ConfigStore;
 use services::logger::Logger;
-use commands::chat::{start_chat, continue_chat, interrupt_chat};
+use services::session_meta::SessionMetaStore;
+use services::token_summary::TokenSummaryCache;
+use services::event_buffer::SessionEventBuffer;
+use services::event_coalescer::EventGate;
+use commands::chat::SessionProcess;
 use commands::chat::{
-    list_iflow_sessions, get_iflow_session_history,
-    get_iflow_file_contexts, get_iflow_token_stats,
+    start_chat, continue_chat, continue_latest_chat, regenerate_last, interrupt_chat, get_last_session,
+    drain_session_events,
+    get_session_capacity, reveal_session_file,
+};
+use commands::chat::{
+    list_iflow_sessions, get_iflow_session_history, get_iflow_session_tree,
+    get_iflow_file_contexts, diff_session_file_contexts, get_iflow_token_stats, list_all_sessions,
+    get_project_token_summary,
     list_claude_code_sessions, get_claude_code_session_history,
 };
-use commands::{validate_workspace_path, get_directory_info};
+use commands::{
+    validate_workspace_path, get_directory_info, get_workspace_info, get_claude_md, set_claude_md,
+};
 use commands::window::{
     show_floating_window, show_main_window, toggle_floating_window,
     is_floating_window_visible, set_floating_window_position, get_floating_window_position
 };
 use commands::file_explorer::{
-    read_directory, get_file_content, create_file, create_directory,
-    delete_file, rename_file, path_exists, read_commands, search_files
+    read_directory, get_directory_tree, get_file_content, read_file_content, create_file,
+    write_file_backup, preview_edit, create_directory,
+    delete_file, rename_file, path_exists, read_commands, create_command, delete_command,
+    search_files,
 };
 use commands::context::{
     context_upsert, context_upsert_many, context_query, context_get_all,
-    context_remove, context_clear,
+    context_remove, context_clear, context_stats,
     ide_report_current_file, ide_report_file_structure, ide_report_diagnostics,
     ContextMemoryStore,
 };
+use commands::session::{
+    set_session_title, get_session_title, set_session_pinned,
+    add_session_tag, remove_session_tag, list_session_tags,
+};
+use commands::logging::{
+    get_log_dir, get_log_path, read_logs, set_log_level, clear_logs, open_log_dir, export_logs,
+    set_logging_enabled, is_logging_enabled,
+};
 
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
@@ -35,11 +57,19 @@ use std::collections::HashMap;
 /// 全局配置状态
 pub struct AppState {
     pub config_store: Mutex<ConfigStore>,
-    /// 保存会话 ID 到进程 PID 的映射
+    /// 保存会话 ID 到进程 PID 的映射（含已预留、进程尚未启动的名额）
     /// 使用 PID 而不是 Child，因为 Child 会在读取输出时被消费
-    pub sessions: Arc<Mutex<HashMap<String, u32>>>,
+    pub sessions: Arc<Mutex<HashMap<String, SessionProcess>>>,
     /// 上下文存储
     pub context_store: Arc<Mutex<ContextMemoryStore>>,
+    /// 会话元数据（自定义标题等）
+    pub session_meta: Mutex<SessionMetaStore>,
+    /// 项目 Token 汇总缓存
+    pub token_summary_cache: Mutex<TokenSummaryCache>,
+    /// 会话事件缓冲（窗口关闭期间的事件）
+    pub event_buffer: Arc<Mutex<SessionEventBuffer>>,
+    /// 会话进程 PID 到事件出口的映射，中断会话时用于关闭出口
+    pub event_gates: Arc<Mutex<HashMap<u32, EventGate>>>,
 }
 
 // ============================================================================
@@ -59,7 +89,16 @@ fn get_config(state: tauri::State<AppState>) -> Result<Config> {
 fn update_config(config: Config, state: tauri::State<AppState>) -> Result<()> {
     let mut store = state.config_store.lock()
         .map_err(|e| error::AppError::Unknown(e.to_string()))?;
-    store.update(config)
+    store.update(config)?;
+    clear_token_summary_cache(&state);
+    Ok(())
+}
+
+/// 模型价格可能已变化，清空 Token 汇总缓存以重新计算费用
+fn clear_token_summary_cache(state: &AppState) {
+    if let Ok(mut cache) = state.token_summary_cache.lock() {
+        cache.clear();
+    }
 }
 
 /// 设置工作目录
@@ -79,6 +118,57 @@ fn set_claude_cmd(cmd: String, state: tauri::State<AppState>) -> Result<()> {
     store.set_claude_cmd(cmd)
 }
 
+/// 获取模型价格表
+#[tauri::command]
+fn get_model_pricing(state: tauri::State<AppState>) -> Result<HashMap<String, ModelPrice>> {
+    let store = state.config_store.lock()
+        .map_err(|e| error::AppError::Unknown(e.to_string()))?;
+    Ok(store.get().model_pricing.clone())
+}
+
+/// 设置模型价格表
+#[tauri::command]
+fn set_model_pricing(pricing: HashMap<String, ModelPrice>, state: tauri::State<AppState>) -> Result<()> {
+    if let Some((model, _)) = pricing.iter().find(|(_, p)| {
+        [Some(p.input), Some(p.output), p.cache_write, p.cache_read]
+            .into_iter()
+            .flatten()
+            .any(|price| !price.is_finite() || price < 0.0)
+    }) {
+        return Err(error::AppError::ConfigError(format!("模型价格无效: {}", model)));
+    }
+
+    let mut store = state.config_store.lock()
+        .map_err(|e| error::AppError::Unknown(e.to_string()))?;
+    store.set_model_pricing(pricing)?;
+    clear_token_summary_cache(&state);
+    Ok(())
+}
+
+/// 列出系统提示词预设
+#[tauri::command]
+fn list_prompt_presets(state: tauri::State<AppState>) -> Result<Vec<PromptPreset>> {
+    let store = state.config_store.lock()
+        .map_err(|e| error::AppError::Unknown(e.to_string()))?;
+    Ok(store.get().prompt_presets.clone())
+}
+
+/// 保存系统提示词预设（`overwrite` 为 true 时覆盖同名预设）
+#[tauri::command]
+fn save_prompt_preset(preset: PromptPreset, overwrite: Option<bool>, state: tauri::State<AppState>) -> Result<()> {
+    let mut store = state.config_store.lock()
+        .map_err(|e| error::AppError::Unknown(e.to_string()))?;
+    store.save_prompt_preset(preset, overwrite.unwrap_or(false))
+}
+
+/// 删除系统提示词预设
+#[tauri::command]
+fn delete_prompt_preset(name: String, state: tauri::State<AppState>) -> Result<()> {
+    let mut store = state.config_store.lock()
+        .map_err(|e| error::AppError::Unknown(e.to_string()))?;
+    store.delete_prompt_preset(&name)
+}
+
 /// 查找所有可用的 Claude CLI 路径
 #[tauri::command]
 fn find_claude_paths() -> Vec<String> {
@@ -95,21 +185,29 @@ pub struct PathValidationResult {
     pub error: Option<String>,
     /// Claude 版本
     pub version: Option<String>,
+    /// 不影响使用但需要提示的问题（如 Windows 下无法直接通过 Node.js 启动）
+    pub warning: Option<String>,
 }
 
 /// 验证 Claude CLI 路径
 #[tauri::command]
-fn validate_claude_path(path: String) -> PathValidationResult {
-    match ConfigStore::validate_claude_path(path) {
+fn validate_claude_path(path: String, state: tauri::State<AppState>) -> PathValidationResult {
+    let node_path = state.config_store.lock()
+        .ok()
+        .and_then(|store| store.get().claude_code.node_path.clone());
+
+    match ConfigStore::validate_claude_path(path.clone()) {
         Ok((valid, error, version)) => PathValidationResult {
             valid,
             error,
             version,
+            warning: if valid { ConfigStore::claude_launch_warning(&path, node_path.as_deref()) } else { None },
         },
         Err(_) => PathValidationResult {
             valid: false,
             error: Some("验证过程中发生错误".to_string()),
             version: None,
+            warning: None,
         },
     }
 }
@@ -128,11 +226,13 @@ fn validate_iflow_path(path: String) -> PathValidationResult {
             valid,
             error,
             version,
+            warning: None,
         },
         Err(_) => PathValidationResult {
             valid: false,
             error: Some("验证过程中发生错误".to_string()),
             version: None,
+            warning: None,
         },
     }
 }
@@ -166,16 +266,47 @@ pub fn run() {
     let config_store = ConfigStore::new()
         .expect("无法初始化配置存储");
 
-    // 默认不启用日志系统
-    // let _logger_guard = Logger::init(false);
+    // 根据配置初始化日志系统（guard 需保持存活，确保日志写入文件）
+    let _logger_guard = Logger::init(
+        config_store.enable_logging(),
+        config_store.get().log_retention_days,
+    );
+
+    // 初始化上下文存储（持久化到配置目录，重启后恢复）
+    let mut context_store = match ConfigStore::config_dir() {
+        Ok(dir) => ContextMemoryStore::with_persistence(dir.join("context_memory.json")),
+        Err(e) => {
+            tracing::warn!("无法获取配置目录，上下文仅保存在内存中: {}", e);
+            ContextMemoryStore::new()
+        }
+    };
+    let limits = &config_store.get().context_memory;
+    context_store.set_limits(limits.max_entries, limits.max_bytes);
+    let context_store = Arc::new(Mutex::new(context_store));
+    ContextMemoryStore::spawn_flusher(Arc::clone(&context_store));
+
+    // 初始化会话元数据存储
+    let session_meta = match ConfigStore::config_dir() {
+        Ok(dir) => SessionMetaStore::with_persistence(dir.join("session_meta.json")),
+        Err(e) => {
+            tracing::warn!("无法获取配置目录，会话元数据仅保存在内存中: {}", e);
+            SessionMetaStore::new()
+        }
+    };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        // 上下文命令直接注入 Arc<Mutex<ContextMemoryStore>>，与 AppState 共享同一实例
+        .manage(Arc::clone(&context_store))
         .manage(AppState {
             config_store: Mutex::new(config_store),
             sessions: Arc::new(Mutex::new(HashMap::new())),
-            context_store: Arc::new(Mutex::new(ContextMemoryStore::new())),
+            context_store,
+            session_meta: Mutex::new(session_meta),
+            token_summary_cache: Mutex::new(TokenSummaryCache::new()),
+            event_buffer: Arc::new(Mutex::new(SessionEventBuffer::new())),
+            event_gates: Arc::new(Mutex::new(HashMap::new())),
         })
         .invoke_handler(tauri::generate_handler![
             // 配置相关
@@ -183,6 +314,11 @@ pub fn run() {
             update_config,
             set_work_dir,
             set_claude_cmd,
+            get_model_pricing,
+            set_model_pricing,
+            list_prompt_presets,
+            save_prompt_preset,
+            delete_prompt_preset,
             find_claude_paths,
             validate_claude_path,
             find_iflow_paths,
@@ -193,27 +329,53 @@ pub fn run() {
             // 聊天相关（统一接口）
             start_chat,
             continue_chat,
+            continue_latest_chat,
+            regenerate_last,
+            drain_session_events,
             interrupt_chat,
+            get_last_session,
+            get_session_capacity,
+            reveal_session_file,
             // IFlow 会话历史相关
             list_iflow_sessions,
             get_iflow_session_history,
+            get_iflow_session_tree,
             get_iflow_file_contexts,
+            diff_session_file_contexts,
             get_iflow_token_stats,
             // Claude Code 原生会话历史相关
             list_claude_code_sessions,
             get_claude_code_session_history,
+            list_all_sessions,
+            get_project_token_summary,
+            // 会话元数据相关
+            set_session_title,
+            get_session_title,
+            set_session_pinned,
+            add_session_tag,
+            remove_session_tag,
+            list_session_tags,
             // 工作区相关
             validate_workspace_path,
             get_directory_info,
+            get_workspace_info,
+            get_claude_md,
+            set_claude_md,
             // 文件浏览器相关
             read_directory,
+            get_directory_tree,
             get_file_content,
+            read_file_content,
             create_file,
+            write_file_backup,
+            preview_edit,
             create_directory,
             delete_file,
             rename_file,
             path_exists,
             read_commands,
+            create_command,
+            delete_command,
             search_files,
             // 窗口管理相关
             show_floating_window,
@@ -229,10 +391,20 @@ pub fn run() {
             context_get_all,
             context_remove,
             context_clear,
+            context_stats,
             ide_report_current_file,
             ide_report_file_structure,
             ide_report_diagnostics,
-
+            // 日志相关
+            get_log_dir,
+            get_log_path,
+            read_logs,
+            set_log_level,
+            clear_logs,
+            open_log_dir,
+            export_logs,
+            set_logging_enabled,
+            is_logging_enabled,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
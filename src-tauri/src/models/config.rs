@@ -1,18 +1,38 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+/// 不允许通过 `env` 配置覆盖的环境变量（影响可执行文件查找或动态库加载）
+const BLOCKED_ENV_KEYS: &[&str] = &[
+    "PATH",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "DYLD_INSERT_LIBRARIES",
+    "DYLD_LIBRARY_PATH",
+];
+
 /// Claude Code 引擎配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClaudeCodeConfig {
     /// Claude CLI 命令路径
     pub cli_path: String,
+
+    /// Node.js 可执行文件路径（Windows，可选），未设置时自动查找
+    #[serde(default)]
+    pub node_path: Option<String>,
+
+    /// 模型名称（--model），未设置时使用 CLI 默认模型
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
 impl Default for ClaudeCodeConfig {
     fn default() -> Self {
         Self {
             cli_path: "claude".to_string(),
+            node_path: None,
+            model: None,
         }
     }
 }
@@ -23,12 +43,27 @@ impl Default for ClaudeCodeConfig {
 pub struct IFlowConfig {
     /// IFlow CLI 命令路径（可选，默认为 "iflow"）
     pub cli_path: Option<String>,
+
+    /// 模型名称（--model），未设置时使用 CLI 默认模型
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// 审批模式（--approval-mode），未设置时使用 --yolo 自动确认所有操作
+    #[serde(default)]
+    pub approval_mode: Option<String>,
+
+    /// 从 CLI 输出中识别会话 ID 的正则表达式（可选），含捕获组时取第一个捕获组
+    #[serde(default)]
+    pub session_id_pattern: Option<String>,
 }
 
 impl Default for IFlowConfig {
     fn default() -> Self {
         Self {
             cli_path: None,
+            model: None,
+            approval_mode: None,
+            session_id_pattern: None,
         }
     }
 }
@@ -147,6 +182,110 @@ impl Default for FloatingWindowConfig {
     }
 }
 
+/// 上下文存储容量配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextMemoryConfig {
+    /// 最大条目数
+    #[serde(default = "default_context_max_entries")]
+    pub max_entries: usize,
+
+    /// 最大总字节数（估算值）
+    #[serde(default = "default_context_max_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_context_max_entries() -> usize {
+    2000
+}
+
+fn default_context_max_bytes() -> usize {
+    50 * 1024 * 1024
+}
+
+impl Default for ContextMemoryConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: default_context_max_entries(),
+            max_bytes: default_context_max_bytes(),
+        }
+    }
+}
+
+/// 最近一次会话（用于启动时恢复）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastSession {
+    /// 引擎 ID
+    pub engine_id: String,
+
+    /// 会话 ID
+    pub session_id: String,
+
+    /// 工作目录
+    pub work_dir: Option<PathBuf>,
+
+    /// 上下文 ID（由前端传入）
+    pub context_id: Option<String>,
+}
+
+/// 未配置缓存写入价格时相对输入价格的倍数
+const CACHE_WRITE_PRICE_MULTIPLIER: f64 = 1.25;
+
+/// 未配置缓存读取价格时相对输入价格的倍数
+const CACHE_READ_PRICE_MULTIPLIER: f64 = 0.1;
+
+/// 模型价格（美元 / 百万 Token）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    /// 输入价格
+    pub input: f64,
+
+    /// 输出价格
+    pub output: f64,
+
+    /// 缓存写入价格，未配置时为输入价格的 1.25 倍
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+
+    /// 缓存读取价格，未配置时为输入价格的 0.1 倍
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+}
+
+impl ModelPrice {
+    /// 缓存写入价格
+    pub fn cache_write_price(&self) -> f64 {
+        self.cache_write.unwrap_or(self.input * CACHE_WRITE_PRICE_MULTIPLIER)
+    }
+
+    /// 缓存读取价格
+    pub fn cache_read_price(&self) -> f64 {
+        self.cache_read.unwrap_or(self.input * CACHE_READ_PRICE_MULTIPLIER)
+    }
+}
+
+/// 单条消息的 Token 用量（用于估算费用）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+    pub cache_creation: u64,
+    pub cache_read: u64,
+}
+
+/// 系统提示词预设
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptPreset {
+    /// 预设名称（唯一）
+    pub name: String,
+
+    /// 提示词内容
+    pub content: String,
+}
+
 /// 应用配置（新版本）
 ///
 /// 使用嵌套结构，支持多个 AI 引擎
@@ -178,6 +317,50 @@ pub struct Config {
     #[serde(default)]
     pub floating_window: FloatingWindowConfig,
 
+    /// 是否启用文件日志
+    #[serde(default = "default_enable_logging")]
+    pub enable_logging: bool,
+
+    /// 日志文件保留天数
+    #[serde(default = "default_log_retention_days")]
+    pub log_retention_days: usize,
+
+    /// 上下文存储容量配置
+    #[serde(default)]
+    pub context_memory: ContextMemoryConfig,
+
+    /// 最近一次会话
+    #[serde(default)]
+    pub last_session: Option<LastSession>,
+
+    /// 是否将无法解析的 CLI 输出行作为 raw 事件发送到前端（调试用）
+    #[serde(default)]
+    pub emit_raw_events: bool,
+
+    /// 最大并发会话数
+    #[serde(default = "default_max_concurrent_sessions")]
+    pub max_concurrent_sessions: usize,
+
+    /// 传递给 CLI 子进程的额外环境变量（覆盖继承的同名变量，PATH 等变量会被忽略）
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// 模型价格表（键为模型名或模型名前缀），用于估算会话费用
+    #[serde(default)]
+    pub model_pricing: HashMap<String, ModelPrice>,
+
+    /// 系统提示词预设
+    #[serde(default)]
+    pub prompt_presets: Vec<PromptPreset>,
+
+    /// 文本增量事件的合并窗口（毫秒），0 表示逐条发送
+    #[serde(default = "default_event_coalesce_ms")]
+    pub event_coalesce_ms: u64,
+
+    /// 每个会话在后台缓冲的最大事件数（供重新打开的窗口补取），0 表示不缓冲
+    #[serde(default = "default_event_buffer_size")]
+    pub event_buffer_size: usize,
+
     // === 旧字段，保持向后兼容 ===
     /// @deprecated 请使用 claude_code.cli_path
     #[serde(default)]
@@ -188,6 +371,26 @@ fn default_default_engine() -> String {
     "claude-code".to_string()
 }
 
+fn default_enable_logging() -> bool {
+    false
+}
+
+fn default_log_retention_days() -> usize {
+    7
+}
+
+fn default_max_concurrent_sessions() -> usize {
+    4
+}
+
+fn default_event_coalesce_ms() -> u64 {
+    30
+}
+
+fn default_event_buffer_size() -> usize {
+    500
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -198,12 +401,60 @@ impl Default for Config {
             session_dir: None,
             git_bin_path: None,
             floating_window: FloatingWindowConfig::default(),
+            enable_logging: default_enable_logging(),
+            log_retention_days: default_log_retention_days(),
+            context_memory: ContextMemoryConfig::default(),
+            last_session: None,
+            emit_raw_events: false,
+            max_concurrent_sessions: default_max_concurrent_sessions(),
+            env: HashMap::new(),
+            model_pricing: HashMap::new(),
+            prompt_presets: Vec::new(),
+            event_coalesce_ms: default_event_coalesce_ms(),
+            event_buffer_size: default_event_buffer_size(),
             claude_cmd: None,
         }
     }
 }
 
 impl Config {
+    /// 获取传递给 CLI 子进程的额外环境变量（已过滤 PATH 等受保护变量）
+    pub fn session_env(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.env.iter().filter(|(key, _)| {
+            let blocked = BLOCKED_ENV_KEYS.iter().any(|b| b.eq_ignore_ascii_case(key));
+            if blocked {
+                tracing::info!("[Config::session_env] 忽略受保护的环境变量: {}", key);
+            }
+            !blocked
+        })
+    }
+
+    /// 查找模型价格：优先精确匹配，其次匹配最长的模型名前缀
+    pub fn model_price(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.model_pricing.get(model) {
+            return Some(*price);
+        }
+        self.model_pricing.iter()
+            .filter(|(key, _)| !key.is_empty() && model.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// 估算费用（美元），未配置价格的模型返回 None
+    pub fn estimate_cost(&self, model: &str, usage: TokenUsage) -> Option<f64> {
+        let price = self.model_price(model)?;
+        let cost = usage.input as f64 * price.input
+            + usage.output as f64 * price.output
+            + usage.cache_creation as f64 * price.cache_write_price()
+            + usage.cache_read as f64 * price.cache_read_price();
+        Some(cost / 1_000_000.0)
+    }
+
+    /// 按名称查找系统提示词预设
+    pub fn prompt_preset(&self, name: &str) -> Option<&PromptPreset> {
+        self.prompt_presets.iter().find(|p| p.name == name)
+    }
+
     /// 获取 Claude CLI 命令路径（优先使用新字段）
     pub fn get_claude_cmd(&self) -> String {
         // 首先检查旧字段（用于迁移）
@@ -265,3 +516,46 @@ pub struct HealthStatus {
     /// 配置是否有效
     pub config_valid: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_env_filters_protected_variables() {
+        let mut config = Config::default();
+        for (key, value) in [
+            ("ANTHROPIC_BASE_URL", "https://proxy.example.com"),
+            ("HTTPS_PROXY", "http://127.0.0.1:7890"),
+            ("PATH", "/tmp/evil"),
+            ("Path", "C:\\evil"),
+            ("LD_PRELOAD", "/tmp/evil.so"),
+            ("LD_LIBRARY_PATH", "/tmp"),
+            ("DYLD_INSERT_LIBRARIES", "/tmp/evil.dylib"),
+            ("DYLD_LIBRARY_PATH", "/tmp"),
+        ] {
+            config.env.insert(key.to_string(), value.to_string());
+        }
+
+        let mut keys: Vec<&str> = config.session_env().map(|(k, _)| k.as_str()).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec!["ANTHROPIC_BASE_URL", "HTTPS_PROXY"]);
+    }
+
+    #[test]
+    fn estimate_cost_prices_cache_tokens() {
+        let mut config = Config::default();
+        let price: ModelPrice = serde_json::from_str(r#"{"input":3.0,"output":15.0}"#).unwrap();
+        config.model_pricing.insert("claude-sonnet".to_string(), price);
+        config.model_pricing.insert("glm".to_string(), ModelPrice { cache_read: Some(0.5), ..price });
+
+        let usage = TokenUsage { input: 1_000_000, output: 0, cache_creation: 1_000_000, cache_read: 1_000_000 };
+        // 未配置缓存价格时按输入价格的倍数计算
+        let cost = config.estimate_cost("claude-sonnet-4", usage).unwrap();
+        assert!((cost - (3.0 + 3.75 + 0.3)).abs() < 1e-9);
+        let cost = config.estimate_cost("glm-4.6", usage).unwrap();
+        assert!((cost - (3.0 + 3.75 + 0.5)).abs() < 1e-9);
+
+        assert_eq!(config.estimate_cost("unknown", usage), None);
+    }
+}
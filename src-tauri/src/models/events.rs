@@ -1,6 +1,24 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// 错误码：Claude CLI 未登录
+pub const ERROR_CODE_NOT_AUTHENTICATED: &str = "NOT_AUTHENTICATED";
+
+/// Claude CLI 未登录时输出的特征文本（小写匹配）
+const AUTH_ERROR_PATTERNS: &[&str] = &[
+    "invalid api key",
+    "please run /login",
+    "not logged in",
+    "authentication_error",
+    "oauth token has expired",
+];
+
+/// 判断文本是否为 Claude CLI 未登录错误
+pub fn is_auth_error_text(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    AUTH_ERROR_PATTERNS.iter().any(|p| lower.contains(p))
+}
+
 /// 权限拒绝详情
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionDenial {
@@ -58,6 +76,26 @@ pub enum StreamEvent {
         output: Option<String>,
     },
 
+    /// 工具执行进度（长时间运行的工具，如 Bash）
+    ///
+    /// 对应 Claude CLI 的 `tool_progress` 消息，各字段视 CLI 版本可能缺失
+    #[serde(rename = "tool_progress")]
+    ToolProgress {
+        #[serde(rename = "toolUseId", alias = "tool_use_id")]
+        tool_use_id: String,
+        #[serde(rename = "toolName", alias = "tool_name", default)]
+        tool_name: Option<String>,
+        /// 已运行时间（秒）
+        #[serde(rename = "elapsedSeconds", alias = "elapsed_time_seconds", default)]
+        elapsed_seconds: Option<f64>,
+        /// 已产生的输出字节数
+        #[serde(rename = "bytesSoFar", alias = "bytes_so_far", default)]
+        bytes_so_far: Option<u64>,
+        /// 部分输出内容
+        #[serde(rename = "partialOutput", alias = "partial_output", default)]
+        partial_output: Option<String>,
+    },
+
     /// 权限请求（工具调用被拒绝）
     #[serde(rename = "permission_request")]
     PermissionRequest {
@@ -66,6 +104,8 @@ pub enum StreamEvent {
     },
 
     /// 结果
+    ///
+    /// 发送到前端前会附加 `estimated_cost_usd`（按价格表估算，未知时为 null）
     #[serde(rename = "result")]
     Result {
         subtype: String,
@@ -75,11 +115,20 @@ pub enum StreamEvent {
 
     /// 错误
     #[serde(rename = "error")]
-    Error { error: String },
+    Error {
+        error: String,
+        /// 结构化错误码（如 NOT_AUTHENTICATED），便于前端给出针对性提示
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<String>,
+    },
 
     /// 会话结束
     #[serde(rename = "session_end")]
     SessionEnd,
+
+    /// 无法解析的原始输出行（用于排查 CLI 版本不兼容）
+    #[serde(rename = "raw")]
+    Raw { line: String },
 }
 
 impl StreamEvent {
@@ -93,4 +142,69 @@ impl StreamEvent {
         // 直接使用 serde 解析
         serde_json::from_str(line).ok()
     }
+
+    /// 创建普通错误事件
+    pub fn error(error: impl Into<String>) -> Self {
+        Self::Error { error: error.into(), code: None }
+    }
+
+    /// 创建未登录错误事件
+    pub fn not_authenticated(detail: &str) -> Self {
+        Self::Error {
+            error: format!("Claude CLI 未登录，请在终端运行 `claude login` 后重试（{}）", detail.trim()),
+            code: Some(ERROR_CODE_NOT_AUTHENTICATED.to_string()),
+        }
+    }
+
+    /// 若事件表示 Claude CLI 未登录，返回原始错误文本
+    pub fn auth_error_detail(&self) -> Option<String> {
+        match self {
+            Self::Result { extra, .. } => {
+                let is_error = extra.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
+                let result = extra.get("result").and_then(|v| v.as_str())?;
+                (is_error && is_auth_error_text(result)).then(|| result.to_string())
+            }
+            Self::Error { error, code: None } if is_auth_error_text(error) => Some(error.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_auth_error_text_case_insensitively() {
+        assert!(is_auth_error_text("Invalid API key · Please run /login"));
+        assert!(is_auth_error_text("Error: NOT LOGGED IN"));
+        assert!(is_auth_error_text(r#"{"type":"authentication_error"}"#));
+        assert!(!is_auth_error_text("rate limit exceeded"));
+        assert!(!is_auth_error_text(""));
+    }
+
+    #[test]
+    fn auth_error_detail_from_result_event() {
+        let event = StreamEvent::parse_line(
+            r#"{"type":"result","subtype":"success","is_error":true,"result":"Invalid API key · Please run /login"}"#,
+        ).unwrap();
+        assert_eq!(event.auth_error_detail().as_deref(), Some("Invalid API key · Please run /login"));
+
+        // 非错误结果即使包含相同文本也不视为未登录
+        let event = StreamEvent::parse_line(
+            r#"{"type":"result","subtype":"success","is_error":false,"result":"how do I fix invalid api key errors?"}"#,
+        ).unwrap();
+        assert_eq!(event.auth_error_detail(), None);
+    }
+
+    #[test]
+    fn auth_error_detail_from_error_event() {
+        assert_eq!(
+            StreamEvent::error("OAuth token has expired").auth_error_detail().as_deref(),
+            Some("OAuth token has expired")
+        );
+        assert_eq!(StreamEvent::error("connection reset").auth_error_detail(), None);
+        // 已经转换过的未登录事件不再重复识别
+        assert_eq!(StreamEvent::not_authenticated("not logged in").auth_error_detail(), None);
+    }
 }
@@ -72,6 +72,12 @@ pub struct IFlowUsage {
     /// 输出 Token 数
     #[serde(rename = "output_tokens")]
     pub output_tokens: u32,
+    /// 缓存写入 Token 数
+    #[serde(default)]
+    pub cache_creation_input_tokens: u32,
+    /// 缓存读取 Token 数
+    #[serde(default)]
+    pub cache_read_input_tokens: u32,
 }
 
 /// IFlow 工具调用结果
@@ -82,10 +88,24 @@ pub struct IFlowToolUseResult {
     pub tool_name: String,
     /// 状态
     pub status: String,
-    /// 时间戳
+    /// 时间戳（Unix 毫秒或秒）
     pub timestamp: u64,
 }
 
+impl IFlowToolUseResult {
+    /// 将时间戳转换为 ISO 8601 字符串（与其他历史消息的时间格式一致）
+    pub fn timestamp_iso(&self) -> Option<String> {
+        // 小于 1e12 的值视为秒级时间戳
+        let millis = if self.timestamp < 1_000_000_000_000 {
+            self.timestamp.checked_mul(1000)?
+        } else {
+            self.timestamp
+        };
+        chrono::DateTime::from_timestamp_millis(i64::try_from(millis).ok()?)
+            .map(|dt| dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+    }
+}
+
 impl IFlowJsonlEvent {
     /// 解析 JSONL 行
     pub fn parse_line(line: &str) -> Option<Self> {
@@ -118,14 +138,14 @@ impl IFlowJsonlEvent {
                     if let Some(assistant_event) = self.to_assistant_event(message) {
                         events.push(assistant_event);
                     }
-                    // 检查是否会话结束
-                    if message.stop_reason.is_some() {
+                    // 检查是否会话结束（tool_use 等中间轮次不结束会话）
+                    if self.is_session_end() {
                         events.push(crate::models::events::StreamEvent::SessionEnd);
                     }
                 }
             }
             _ => {
-                eprintln!("[IFlow] 未知事件类型: {}", self.event_type);
+                tracing::info!("[IFlow] 未知事件类型: {}", self.event_type);
             }
         }
 
@@ -227,6 +247,32 @@ impl IFlowJsonlEvent {
         }
     }
 
+    /// 提取历史记录中的工具结果（仅 user 消息）
+    pub fn extract_history_tool_results(&self) -> Vec<IFlowToolResult> {
+        let content_array = match self.message.as_ref().map(|m| &m.content) {
+            Some(serde_json::Value::Array(arr)) => arr,
+            _ => return Vec::new(),
+        };
+
+        let meta = self.tool_use_result.as_ref();
+
+        content_array.iter()
+            .filter_map(|item| item.as_object())
+            .filter(|obj| obj.get("type").and_then(|v| v.as_str()) == Some("tool_result"))
+            .map(|obj| IFlowToolResult {
+                tool_use_id: obj.get("tool_use_id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                tool_name: meta.map(|m| m.tool_name.clone()),
+                status: meta.map(|m| m.status.clone()),
+                output: self.extract_tool_output(obj),
+                timestamp: meta.map(|m| m.timestamp),
+                timestamp_iso: meta.and_then(|m| m.timestamp_iso()),
+            })
+            .collect()
+    }
+
     /// 从 tool_result 对象中提取实际输出
     fn extract_tool_output(&self, obj: &serde_json::Map<String, serde_json::Value>) -> String {
         // 优先使用 resultDisplay
@@ -306,8 +352,12 @@ impl IFlowJsonlEvent {
 pub struct IFlowSessionMeta {
     /// 会话 ID
     pub session_id: String,
-    /// 会话标题（从第一条用户消息提取）
+    /// 会话标题（用户自定义标题，未设置时从第一条用户消息提取）
     pub title: String,
+    /// 会话标签
+    pub tags: Vec<String>,
+    /// 是否置顶
+    pub pinned: bool,
     /// 消息数量
     pub message_count: u32,
     /// 文件大小（字节）
@@ -320,6 +370,10 @@ pub struct IFlowSessionMeta {
     pub input_tokens: u32,
     /// 输出 Token 总数
     pub output_tokens: u32,
+    /// 缓存写入 Token 总数
+    pub cache_creation_tokens: u32,
+    /// 缓存读取 Token 总数
+    pub cache_read_tokens: u32,
 }
 
 /// IFlow 简化消息（用于历史展示）
@@ -345,6 +399,26 @@ pub struct IFlowHistoryMessage {
     pub output_tokens: Option<u32>,
     /// 工具调用列表（仅 assistant）
     pub tool_calls: Vec<IFlowToolCall>,
+    /// 工具调用结果列表（仅 user），可通过 tool_use_id 与 tool_calls 配对
+    pub tool_results: Vec<IFlowToolResult>,
+}
+
+/// IFlow 会话树节点
+///
+/// 以扁平列表表示消息树（按时间排序），通过 `parent_uuid`/`children` 关联，
+/// 避免长对话产生过深的嵌套结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IFlowMessageNode {
+    /// 消息内容
+    pub message: IFlowHistoryMessage,
+    /// 树中的父消息 UUID（跳过未展示的事件、断开循环引用后），为空表示根节点
+    pub parent_uuid: Option<String>,
+    /// 子消息 UUID 列表（按时间排序），多于一个表示在此处产生了分支
+    pub children: Vec<String>,
+    /// 节点深度（根节点为 0）
+    pub depth: usize,
+    /// 是否位于当前活动路径上（根节点到最新消息的路径）
+    pub active: bool,
 }
 
 /// IFlow 工具调用
@@ -356,6 +430,27 @@ pub struct IFlowToolCall {
     pub name: String,
     /// 工具输入参数
     pub input: serde_json::Value,
+    /// 工具输出（从匹配的 tool_result 中获取）
+    pub output: Option<String>,
+    /// 执行状态（从匹配的 tool_result 中获取）
+    pub status: Option<String>,
+}
+
+/// IFlow 工具调用结果（用于历史记录）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IFlowToolResult {
+    /// 对应的工具调用 ID
+    pub tool_use_id: String,
+    /// 工具名称
+    pub tool_name: Option<String>,
+    /// 执行状态
+    pub status: Option<String>,
+    /// 工具输出
+    pub output: String,
+    /// 原始时间戳
+    pub timestamp: Option<u64>,
+    /// ISO 8601 格式时间
+    pub timestamp_iso: Option<String>,
 }
 
 /// IFlow 文件上下文
@@ -373,6 +468,17 @@ pub struct IFlowFileContext {
     pub last_accessed: String,
 }
 
+/// 两个会话访问文件的差异（路径按字母排序）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IFlowFileContextDiff {
+    /// 仅会话 A 访问过的文件
+    pub only_a: Vec<String>,
+    /// 仅会话 B 访问过的文件
+    pub only_b: Vec<String>,
+    /// 两个会话都访问过的文件
+    pub both: Vec<String>,
+}
+
 /// IFlow Token 统计
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IFlowTokenStats {
@@ -380,8 +486,16 @@ pub struct IFlowTokenStats {
     pub total_input_tokens: u32,
     /// 输出 Token 总数
     pub total_output_tokens: u32,
+    /// 缓存写入 Token 总数
+    pub total_cache_creation_tokens: u32,
+    /// 缓存读取 Token 总数
+    pub total_cache_read_tokens: u32,
     /// 总 Token 数
     pub total_tokens: u32,
+    /// 会话使用的模型（最后一条 assistant 消息）
+    pub model: Option<String>,
+    /// 估算费用（美元），没有用量或存在未配置价格的模型时为 None
+    pub estimated_cost_usd: Option<f64>,
     /// 消息数量
     pub message_count: u32,
     /// 用户消息数量
@@ -411,3 +525,69 @@ pub struct IFlowProjectsConfig {
     #[serde(flatten)]
     pub projects: HashMap<String, IFlowProjectConfig>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::events::StreamEvent;
+
+    fn assistant_line(stop_reason: &str) -> String {
+        serde_json::json!({
+            "uuid": "u-1",
+            "parentUuid": null,
+            "sessionId": "session-abc",
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "type": "assistant",
+            "isSidechain": false,
+            "userType": "external",
+            "message": {
+                "id": "m-1",
+                "role": "assistant",
+                "content": [
+                    { "type": "text", "text": "读取文件" },
+                    { "type": "tool_use", "id": "call-1", "name": "read_file", "input": { "path": "a.rs" } }
+                ],
+                "model": "glm-4.6",
+                "stop_reason": stop_reason
+            }
+        }).to_string()
+    }
+
+    #[test]
+    fn tool_use_stop_reason_does_not_end_session() {
+        let events = IFlowJsonlEvent::parse_line(&assistant_line("tool_use"))
+            .unwrap()
+            .to_stream_events();
+
+        assert!(matches!(events.as_slice(), [StreamEvent::Assistant { .. }]));
+    }
+
+    #[test]
+    fn end_turn_stop_reason_ends_session() {
+        let events = IFlowJsonlEvent::parse_line(&assistant_line("end_turn"))
+            .unwrap()
+            .to_stream_events();
+
+        assert!(matches!(events.as_slice(), [StreamEvent::Assistant { .. }, StreamEvent::SessionEnd]));
+    }
+
+    fn tool_result(timestamp: u64) -> IFlowToolUseResult {
+        IFlowToolUseResult {
+            tool_name: "read_file".to_string(),
+            status: "success".to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn timestamp_iso_accepts_millis_and_seconds() {
+        assert_eq!(tool_result(1_704_067_200_123).timestamp_iso().as_deref(), Some("2024-01-01T00:00:00.123Z"));
+        assert_eq!(tool_result(1_704_067_200).timestamp_iso().as_deref(), Some("2024-01-01T00:00:00.000Z"));
+    }
+
+    #[test]
+    fn timestamp_iso_rejects_out_of_range_values() {
+        assert_eq!(tool_result(i64::MAX as u64).timestamp_iso(), None);
+        assert_eq!(tool_result(u64::MAX).timestamp_iso(), None);
+    }
+}
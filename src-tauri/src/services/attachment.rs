@@ -0,0 +1,120 @@
+//! 聊天附件处理
+
+use crate::error::{AppError, Result};
+use crate::services::fs_utils::is_binary_bytes;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// 单个附件的最大字节数
+const MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// 支持的图片扩展名
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// 校验附件路径：必须存在、是普通文件、不超过大小上限，且为图片或文本文件
+///
+/// 返回绝对路径（不使用 `canonicalize`，避免 Windows 下生成 `\\?\` 形式的路径）
+pub fn validate_attachments(paths: &[String]) -> Result<Vec<PathBuf>> {
+    paths.iter().map(|p| validate_attachment(p)).collect()
+}
+
+fn validate_attachment(path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(AppError::InvalidPath(format!("附件不存在或不是文件: {}", path.display())));
+    }
+
+    let size = std::fs::metadata(&path)?.len();
+    if size > MAX_ATTACHMENT_BYTES {
+        return Err(AppError::InvalidPath(format!(
+            "附件过大: {}（{} 字节，上限 {} 字节）",
+            path.display(), size, MAX_ATTACHMENT_BYTES
+        )));
+    }
+
+    let is_image = path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false);
+
+    if !is_image {
+        // 非图片文件只允许文本内容
+        let mut head = Vec::with_capacity(8192);
+        std::fs::File::open(&path)?
+            .take(8192)
+            .read_to_end(&mut head)?;
+        if is_binary_bytes(&head) {
+            return Err(AppError::InvalidPath(format!(
+                "不支持的附件类型（仅支持图片或文本文件）: {}",
+                path.display()
+            )));
+        }
+    }
+
+    Ok(std::path::absolute(&path)?)
+}
+
+/// 以 `@path` 引用的形式将附件追加到消息末尾（Claude CLI 会读取被引用的文件）
+pub fn append_attachment_refs(message: &str, attachments: &[PathBuf]) -> String {
+    if attachments.is_empty() {
+        return message.to_string();
+    }
+
+    let refs: Vec<String> = attachments.iter()
+        .map(|p| attachment_ref(p))
+        .collect();
+    format!("{}\n\n{}", message, refs.join("\n"))
+}
+
+/// 生成单个附件的 `@path` 引用，路径包含空白字符时加引号（引号本身转义）
+fn attachment_ref(path: &Path) -> String {
+    let path = path.display().to_string();
+    if path.chars().any(char::is_whitespace) {
+        format!("@\"{}\"", path.replace('"', "\\\""))
+    } else {
+        format!("@{}", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_missing_attachment() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.txt");
+
+        let result = validate_attachments(&[missing.display().to_string()]);
+        assert!(matches!(result, Err(AppError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn accepts_text_attachment_as_absolute_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.md");
+        std::fs::write(&file, "# notes").unwrap();
+
+        let paths = validate_attachments(&[file.display().to_string()]).unwrap();
+        assert_eq!(paths, vec![file]);
+        assert!(paths[0].is_absolute());
+    }
+
+    #[test]
+    fn rejects_binary_attachment() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("data.bin");
+        std::fs::write(&file, [0u8, 159, 146, 150, 0, 0]).unwrap();
+
+        assert!(validate_attachments(&[file.display().to_string()]).is_err());
+    }
+
+    #[test]
+    fn quotes_refs_with_spaces() {
+        let refs = append_attachment_refs("看看这些文件", &[
+            PathBuf::from("/tmp/plain.txt"),
+            PathBuf::from("/tmp/my docs/a.txt"),
+        ]);
+        assert_eq!(refs, "看看这些文件\n\n@/tmp/plain.txt\n@\"/tmp/my docs/a.txt\"");
+    }
+}
@@ -0,0 +1,406 @@
+//! Windows 下 Claude CLI 的启动路径解析
+//!
+//! npm 安装的 claude.cmd 经 cmd.exe 转发时会破坏包含特殊字符的参数，因此优先解析出
+//! node.exe 与 cli.js 直接调用。解析失败时返回的错误会列出缺失的文件及所有已查找的位置。
+//! 查找逻辑以目录、环境变量读取函数等为参数，与平台无关，非 Windows 平台仅在测试中使用
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::error::{AppError, Result};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+/// Windows 进程创建标志：不创建新窗口
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// 读取环境变量的函数
+type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+/// 已解析的 node.exe 路径缓存（每条消息都会启动进程，避免每次重新查找并执行 `node --version`）
+static NODE_PATH_CACHE: OnceLock<NodePathCache> = OnceLock::new();
+
+/// node.exe 路径缓存，以 npm 目录和配置的 node 路径为键
+///
+/// 缓存的文件被删除（如卸载或切换 Node 版本）后重新查找
+#[derive(Default)]
+struct NodePathCache {
+    entries: Mutex<HashMap<(PathBuf, Option<String>), String>>,
+}
+
+impl NodePathCache {
+    /// 获取缓存的 node.exe 路径，缓存缺失或文件已不存在时调用 `resolve` 并缓存结果
+    fn get_or_resolve(
+        &self,
+        npm_dir: &Path,
+        node_path: Option<&str>,
+        resolve: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        let key = (npm_dir.to_path_buf(), node_path.map(str::to_string));
+        let cached = self.entries.lock().ok()
+            .and_then(|entries| entries.get(&key).cloned())
+            .filter(|path| Path::new(path).is_file());
+        if let Some(path) = cached {
+            return Ok(path);
+        }
+
+        let path = resolve()?;
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(key, path.clone());
+        }
+        Ok(path)
+    }
+}
+
+/// 路径是否为批处理脚本（.cmd / .bat）
+pub fn is_batch_script(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("cmd") || e.eq_ignore_ascii_case("bat"))
+}
+
+/// 从 claude.cmd 路径解析出 Node.js 和 cli.js 的路径
+///
+/// claude.cmd 通常位于: C:\Users\...\AppData\Roaming\npm\claude.cmd
+/// node.exe 通常在同一目录或系统 PATH 中，`node_path` 为配置中显式指定的 node.exe
+/// cli.js 位于: node_modules\@anthropic-ai\claude-code\cli.js
+pub fn resolve_node_and_cli(claude_cmd_path: &str, node_path: Option<&str>) -> Result<(String, String)> {
+    resolve_with(
+        claude_cmd_path,
+        node_path,
+        &|var| std::env::var_os(var),
+        where_node,
+        &is_working_node,
+        NODE_PATH_CACHE.get_or_init(NodePathCache::default),
+    )
+}
+
+/// `resolve_node_and_cli` 的实现，环境变量、PATH 查找与 node 可用性检测由参数提供
+fn resolve_with(
+    claude_cmd_path: &str,
+    node_path: Option<&str>,
+    env: EnvLookup<'_>,
+    path_lookup: impl FnOnce() -> Vec<PathBuf>,
+    is_working: &dyn Fn(&Path) -> bool,
+    cache: &NodePathCache,
+) -> Result<(String, String)> {
+    let cmd_path = Path::new(claude_cmd_path);
+
+    // 获取 .cmd 文件所在的目录（通常是 npm 目录）
+    let npm_dir = cmd_path.parent()
+        .ok_or_else(|| AppError::ProcessError(format!("无法获取 {} 的父目录", claude_cmd_path)))?;
+
+    // 查找 node.exe（优先使用缓存）
+    let node_exe = cache.get_or_resolve(npm_dir, node_path, || {
+        find_node_exe(npm_dir, node_path, env, path_lookup, is_working)
+    })?;
+
+    // 查找 cli.js
+    let cli_js = find_cli_js(npm_dir, env)?;
+
+    tracing::info!("[resolve_node_and_cli] node_exe: {}", node_exe);
+    tracing::info!("[resolve_node_and_cli] cli_js: {}", cli_js);
+
+    Ok((node_exe, cli_js))
+}
+
+/// 构造“找不到文件”错误，列出所有已查找的位置
+fn not_found_error(file: &str, searched: &[String]) -> AppError {
+    AppError::ProcessError(format!(
+        "无法找到 {}，已查找: {}",
+        file,
+        searched.join("; ")
+    ))
+}
+
+/// 验证 node.exe 能否正常执行（`node --version`）
+fn is_working_node(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+
+    let mut cmd = Command::new(path);
+    cmd.arg("--version");
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    cmd.output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// 环境变量指向的目录下的相对路径
+fn env_path(env: EnvLookup<'_>, var: &str, relative: &[&str]) -> Option<PathBuf> {
+    let base = env(var).filter(|v| !v.is_empty())?;
+    Some(relative.iter().fold(PathBuf::from(base), |p, part| p.join(part)))
+}
+
+/// 通过 `where node` 在 PATH 中查找 node.exe
+fn where_node() -> Vec<PathBuf> {
+    let mut cmd = Command::new("where");
+    cmd.arg("node");
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    match cmd.output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            tracing::warn!("[find_node_exe] 执行 where node 失败: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 依次验证候选路径，返回第一个可用的 node.exe，未通过的路径记录到 `searched`
+fn try_candidates(
+    searched: &mut Vec<String>,
+    is_working: &dyn Fn(&Path) -> bool,
+    strategy: &str,
+    candidates: Vec<PathBuf>,
+) -> Option<String> {
+    for path in candidates {
+        if is_working(&path) {
+            tracing::info!("[find_node_exe] 通过 {} 找到 node.exe: {}", strategy, path.display());
+            return Some(path.to_string_lossy().to_string());
+        }
+        searched.push(path.display().to_string());
+    }
+    None
+}
+
+/// 查找 node.exe 可执行文件
+///
+/// 按顺序尝试：配置指定路径、npm 目录、%APPDATA%\npm、PATH、Volta、fnm、nvm-windows、
+/// Program Files，返回第一个通过 `is_working` 检测的路径
+fn find_node_exe(
+    npm_dir: &Path,
+    node_path: Option<&str>,
+    env: EnvLookup<'_>,
+    path_lookup: impl FnOnce() -> Vec<PathBuf>,
+    is_working: &dyn Fn(&Path) -> bool,
+) -> Result<String> {
+    let mut searched = Vec::new();
+
+    // 1. 配置中显式指定的路径
+    if let Some(path) = node_path.map(str::trim).filter(|p| !p.is_empty()) {
+        if let Some(found) = try_candidates(&mut searched, is_working, "配置路径", vec![PathBuf::from(path)]) {
+            return Ok(found);
+        }
+        tracing::warn!("[find_node_exe] 配置的 node 路径不可用: {}", path);
+    }
+
+    // 2. npm 目录及 %APPDATA%\npm 下的 node.exe
+    let npm_candidates = std::iter::once(npm_dir.join("node.exe"))
+        .chain(env_path(env, "APPDATA", &["npm", "node.exe"]))
+        .collect();
+    if let Some(found) = try_candidates(&mut searched, is_working, "npm 目录", npm_candidates) {
+        return Ok(found);
+    }
+
+    // 3. 系统 PATH
+    let path_candidates = path_lookup();
+    if path_candidates.is_empty() {
+        searched.push("PATH (where node)".to_string());
+    }
+    if let Some(found) = try_candidates(&mut searched, is_working, "PATH", path_candidates) {
+        return Ok(found);
+    }
+
+    // 4. 版本管理器：Volta 的 shim 目录、fnm 当前会话及默认版本、nvm-windows 的符号链接
+    let volta_candidates = [
+        env_path(env, "VOLTA_HOME", &["bin", "node.exe"]),
+        env_path(env, "LOCALAPPDATA", &["Volta", "bin", "node.exe"]),
+        env_path(env, "ProgramFiles", &["Volta", "node.exe"]),
+    ];
+    if let Some(found) = try_candidates(&mut searched, is_working, "Volta", volta_candidates.into_iter().flatten().collect()) {
+        return Ok(found);
+    }
+
+    let fnm_candidates = [
+        env_path(env, "FNM_MULTISHELL_PATH", &["node.exe"]),
+        env_path(env, "FNM_DIR", &["aliases", "default", "node.exe"]),
+        env_path(env, "APPDATA", &["fnm", "aliases", "default", "node.exe"]),
+        env_path(env, "LOCALAPPDATA", &["fnm", "aliases", "default", "node.exe"]),
+    ];
+    if let Some(found) = try_candidates(&mut searched, is_working, "fnm", fnm_candidates.into_iter().flatten().collect()) {
+        return Ok(found);
+    }
+
+    if let Some(found) = try_candidates(&mut searched, is_working, "nvm-windows", env_path(env, "NVM_SYMLINK", &["node.exe"]).into_iter().collect()) {
+        return Ok(found);
+    }
+
+    // 5. 尝试常见安装路径
+    let common_candidates = vec![
+        PathBuf::from(r"C:\Program Files\nodejs\node.exe"),
+        PathBuf::from(r"C:\Program Files (x86)\nodejs\node.exe"),
+    ];
+    if let Some(found) = try_candidates(&mut searched, is_working, "常见安装路径", common_candidates) {
+        return Ok(found);
+    }
+
+    Err(not_found_error("node.exe", &searched))
+}
+
+/// cli.js 在 npm 目录中的相对位置
+fn cli_js_in(npm_dir: &Path) -> PathBuf {
+    npm_dir
+        .join("node_modules")
+        .join("@anthropic-ai")
+        .join("claude-code")
+        .join("cli.js")
+}
+
+/// 查找 cli.js 文件
+fn find_cli_js(npm_dir: &Path, env: EnvLookup<'_>) -> Result<String> {
+    let mut candidates = vec![cli_js_in(npm_dir)];
+
+    // 如果不在预期位置，尝试全局 node_modules
+    if let Some(global_npm) = env_path(env, "APPDATA", &["npm"]) {
+        candidates.push(cli_js_in(&global_npm));
+    }
+
+    if let Some(found) = candidates.iter().find(|p| p.exists()) {
+        return Ok(found.to_string_lossy().to_string());
+    }
+
+    let searched: Vec<String> = candidates.iter().map(|p| p.display().to_string()).collect();
+    Err(not_found_error("cli.js", &searched))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<OsString> {
+        None
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    /// 在临时目录中模拟 npm 全局目录，返回 claude.cmd 路径
+    fn npm_layout(dir: &Path, with_node: bool, with_cli: bool) -> String {
+        if with_node {
+            touch(&dir.join("node.exe"));
+        }
+        if with_cli {
+            touch(&cli_js_in(dir));
+        }
+        let claude_cmd = dir.join("claude.cmd");
+        touch(&claude_cmd);
+        claude_cmd.to_string_lossy().to_string()
+    }
+
+    fn resolve(claude_cmd: &str, node_path: Option<&str>, env: EnvLookup<'_>) -> Result<(String, String)> {
+        resolve_with(claude_cmd, node_path, env, Vec::new, &|p: &Path| p.is_file(), &NodePathCache::default())
+    }
+
+    #[test]
+    fn resolves_node_and_cli_from_npm_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let claude_cmd = npm_layout(dir.path(), true, true);
+
+        let (node, cli) = resolve(&claude_cmd, None, &no_env).unwrap();
+        assert_eq!(PathBuf::from(node), dir.path().join("node.exe"));
+        assert_eq!(PathBuf::from(cli), cli_js_in(dir.path()));
+    }
+
+    #[test]
+    fn missing_node_lists_searched_locations() {
+        let dir = tempfile::tempdir().unwrap();
+        let claude_cmd = npm_layout(dir.path(), false, true);
+
+        let message = resolve(&claude_cmd, Some("/nowhere/node.exe"), &no_env)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("无法找到 node.exe"));
+        assert!(message.contains("/nowhere/node.exe"));
+        assert!(message.contains(&dir.path().join("node.exe").display().to_string()));
+        assert!(message.contains("PATH (where node)"));
+    }
+
+    #[test]
+    fn missing_cli_js_lists_searched_locations() {
+        let dir = tempfile::tempdir().unwrap();
+        let appdata = tempfile::tempdir().unwrap();
+        let claude_cmd = npm_layout(dir.path(), true, false);
+        let appdata_path = appdata.path().as_os_str().to_owned();
+        let env = move |var: &str| (var == "APPDATA").then(|| appdata_path.clone());
+
+        let message = resolve(&claude_cmd, None, &env).unwrap_err().to_string();
+        assert!(message.contains("无法找到 cli.js"));
+        assert!(message.contains(&cli_js_in(dir.path()).display().to_string()));
+        assert!(message.contains(&cli_js_in(&appdata.path().join("npm")).display().to_string()));
+    }
+
+    #[test]
+    fn configured_node_path_takes_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let claude_cmd = npm_layout(dir.path(), true, true);
+        let custom = dir.path().join("custom").join("node.exe");
+        touch(&custom);
+
+        let (node, _) = resolve(&claude_cmd, custom.to_str(), &no_env).unwrap();
+        assert_eq!(PathBuf::from(node), custom);
+    }
+
+    #[test]
+    fn node_path_is_cached_until_file_disappears() {
+        let dir = tempfile::tempdir().unwrap();
+        let claude_cmd = npm_layout(dir.path(), true, true);
+        let cache = NodePathCache::default();
+        let checks = std::cell::Cell::new(0);
+        let is_working = |p: &Path| {
+            checks.set(checks.get() + 1);
+            p.is_file()
+        };
+
+        for _ in 0..3 {
+            resolve_with(&claude_cmd, None, &no_env, Vec::new, &is_working, &cache).unwrap();
+        }
+        assert_eq!(checks.get(), 1);
+
+        // 缓存的 node.exe 被删除后重新查找（此时找不到）
+        std::fs::remove_file(dir.path().join("node.exe")).unwrap();
+        assert!(resolve_with(&claude_cmd, None, &no_env, Vec::new, &is_working, &cache).is_err());
+        assert!(checks.get() > 1);
+    }
+
+    #[test]
+    fn node_path_cache_is_keyed_by_configured_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let claude_cmd = npm_layout(dir.path(), true, true);
+        let custom = dir.path().join("custom").join("node.exe");
+        touch(&custom);
+        let cache = NodePathCache::default();
+        let is_working = |p: &Path| p.is_file();
+
+        let (default_node, _) = resolve_with(&claude_cmd, None, &no_env, Vec::new, &is_working, &cache).unwrap();
+        let (custom_node, _) = resolve_with(&claude_cmd, custom.to_str(), &no_env, Vec::new, &is_working, &cache).unwrap();
+        assert_eq!(PathBuf::from(default_node), dir.path().join("node.exe"));
+        assert_eq!(PathBuf::from(custom_node), custom);
+    }
+
+    #[test]
+    fn detects_batch_scripts() {
+        assert!(is_batch_script(r"C:\npm\claude.cmd"));
+        assert!(is_batch_script(r"C:\npm\claude.BAT"));
+        assert!(!is_batch_script(r"C:\npm\claude.exe"));
+        assert!(!is_batch_script("claude"));
+    }
+}
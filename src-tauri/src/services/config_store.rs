@@ -1,5 +1,6 @@
 use crate::error::{AppError, Result};
-use crate::models::config::{Config, HealthStatus, EngineId, ClaudeCodeConfig};
+use crate::models::config::{Config, HealthStatus, EngineId, ClaudeCodeConfig, LastSession, ModelPrice, PromptPreset};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::env;
 use std::process::Command;
@@ -12,11 +13,18 @@ pub struct ConfigStore {
 }
 
 impl ConfigStore {
+    /// 获取应用配置目录
+    pub fn config_dir() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or_else(|| AppError::ConfigError("无法获取配置目录".to_string()))?
+            .join("claude-code-pro"))
+    }
+
     /// 创建新的配置存储
+    ///
+    /// 在日志系统初始化之前调用，因此诊断信息直接输出到 stderr
     pub fn new() -> Result<Self> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| AppError::ConfigError("无法获取配置目录".to_string()))?
-            .join("claude-code-pro");
+        let config_dir = Self::config_dir()?;
 
         eprintln!("配置目录: {:?}", config_dir);
 
@@ -180,6 +188,53 @@ impl ConfigStore {
         self.save()
     }
 
+    /// 记录最近一次会话
+    pub fn set_last_session(&mut self, last_session: LastSession) -> Result<()> {
+        self.config.last_session = Some(last_session);
+        self.save()
+    }
+
+    /// 设置模型价格表
+    pub fn set_model_pricing(&mut self, pricing: HashMap<String, ModelPrice>) -> Result<()> {
+        self.config.model_pricing = pricing;
+        self.save()
+    }
+
+    /// 保存系统提示词预设
+    ///
+    /// 已存在同名预设时，仅在 `overwrite` 为 true 时覆盖，否则返回错误
+    pub fn save_prompt_preset(&mut self, preset: PromptPreset, overwrite: bool) -> Result<()> {
+        let name = preset.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::ConfigError("预设名称不能为空".to_string()));
+        }
+
+        let preset = PromptPreset { name, content: preset.content };
+        match self.config.prompt_presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(_) if !overwrite => {
+                return Err(AppError::ConfigError(format!("预设已存在: {}", preset.name)));
+            }
+            Some(existing) => *existing = preset,
+            None => self.config.prompt_presets.push(preset),
+        }
+        self.save()
+    }
+
+    /// 删除系统提示词预设
+    pub fn delete_prompt_preset(&mut self, name: &str) -> Result<()> {
+        let before = self.config.prompt_presets.len();
+        self.config.prompt_presets.retain(|p| p.name != name);
+        if self.config.prompt_presets.len() == before {
+            return Err(AppError::ConfigError(format!("预设不存在: {}", name)));
+        }
+        self.save()
+    }
+
+    /// 是否启用文件日志
+    pub fn enable_logging(&self) -> bool {
+        self.config.enable_logging
+    }
+
     /// 获取会话目录
     pub fn session_dir(&self) -> Result<PathBuf> {
         if let Some(ref dir) = self.config.session_dir {
@@ -199,7 +254,7 @@ impl ConfigStore {
     /// 检测 Claude CLI 是否可用
     pub fn detect_claude(&self) -> Option<String> {
         let cmd = self.config.get_claude_cmd();
-        eprintln!("[detect_claude] 尝试执行: {} --version", cmd);
+        tracing::info!("[detect_claude] 尝试执行: {} --version", cmd);
 
         let output = Command::new(&cmd)
             .arg("--version")
@@ -207,24 +262,24 @@ impl ConfigStore {
 
         match output {
             Ok(output) => {
-                eprintln!("[detect_claude] 进程退出码: {:?}", output.status.code());
-                eprintln!("[detect_claude] stdout: {}", String::from_utf8_lossy(&output.stdout));
-                eprintln!("[detect_claude] stderr: {}", String::from_utf8_lossy(&output.stderr));
+                tracing::info!("[detect_claude] 进程退出码: {:?}", output.status.code());
+                tracing::info!("[detect_claude] stdout: {}", String::from_utf8_lossy(&output.stdout));
+                tracing::info!("[detect_claude] stderr: {}", String::from_utf8_lossy(&output.stderr));
 
                 if output.status.success() {
                     let version = String::from_utf8_lossy(&output.stdout)
                         .lines()
                         .next()
                         .map(|s| s.to_string());
-                    eprintln!("[detect_claude] 解析成功: {:?}", version);
+                    tracing::info!("[detect_claude] 解析成功: {:?}", version);
                     version
                 } else {
-                    eprintln!("[detect_claude] 命令执行失败");
+                    tracing::warn!("[detect_claude] 命令执行失败");
                     None
                 }
             }
             Err(e) => {
-                eprintln!("[detect_claude] 启动进程失败: {:?}", e);
+                tracing::warn!("[detect_claude] 启动进程失败: {:?}", e);
                 None
             }
         }
@@ -240,7 +295,7 @@ impl ConfigStore {
             Self::find_iflow_path()?
         };
 
-        eprintln!("[detect_iflow] 尝试执行: {} --version", iflow_cmd);
+        tracing::info!("[detect_iflow] 尝试执行: {} --version", iflow_cmd);
 
         let output = Command::new(&iflow_cmd)
             .arg("--version")
@@ -248,9 +303,9 @@ impl ConfigStore {
 
         match output {
             Ok(output) => {
-                eprintln!("[detect_iflow] 进程退出码: {:?}", output.status.code());
-                eprintln!("[detect_iflow] stdout: {}", String::from_utf8_lossy(&output.stdout));
-                eprintln!("[detect_iflow] stderr: {}", String::from_utf8_lossy(&output.stderr));
+                tracing::info!("[detect_iflow] 进程退出码: {:?}", output.status.code());
+                tracing::info!("[detect_iflow] stdout: {}", String::from_utf8_lossy(&output.stdout));
+                tracing::info!("[detect_iflow] stderr: {}", String::from_utf8_lossy(&output.stderr));
 
                 if output.status.success() {
                     let version = String::from_utf8_lossy(&output.stdout)
@@ -260,12 +315,12 @@ impl ConfigStore {
 //                     eprintln!("[detect_iflow] 解析成功: {:?}", version);
                     version
                 } else {
-                    eprintln!("[detect_iflow] 命令执行失败");
+                    tracing::warn!("[detect_iflow] 命令执行失败");
                     None
                 }
             }
             Err(e) => {
-                eprintln!("[detect_iflow] 启动进程失败: {:?}", e);
+                tracing::warn!("[detect_iflow] 启动进程失败: {:?}", e);
                 None
             }
         }
@@ -278,7 +333,7 @@ impl ConfigStore {
         if let Ok(output) = Command::new("where").arg("iflow").output() {
             if output.status.success() {
                 if let Some(path) = String::from_utf8_lossy(&output.stdout).lines().next() {
-                    eprintln!("[find_iflow_path] 找到: {}", path);
+                    tracing::info!("[find_iflow_path] 找到: {}", path);
                     return Some(path.to_string());
                 }
             }
@@ -288,7 +343,7 @@ impl ConfigStore {
         if let Ok(output) = Command::new("which").arg("iflow").output() {
             if output.status.success() {
                 if let Some(path) = String::from_utf8_lossy(&output.stdout).lines().next() {
-                    eprintln!("[find_iflow_path] 找到: {}", path);
+                    tracing::info!("[find_iflow_path] 找到: {}", path);
                     return Some(path.to_string());
                 }
             }
@@ -309,9 +364,9 @@ impl ConfigStore {
                 ];
 
                 for path in common_paths {
-                    eprintln!("[find_iflow_path] 检查: {}", path);
+                    tracing::info!("[find_iflow_path] 检查: {}", path);
                     if Path::new(&path).exists() {
-                        eprintln!("[find_iflow_path] 找到: {}", path);
+                        tracing::info!("[find_iflow_path] 找到: {}", path);
                         return Some(path);
                     }
                 }
@@ -330,15 +385,15 @@ impl ConfigStore {
             ];
 
             for path in common_paths {
-                eprintln!("[find_iflow_path] 检查: {}", path);
+                tracing::info!("[find_iflow_path] 检查: {}", path);
                 if Path::new(&path).exists() {
-                    eprintln!("[find_iflow_path] 找到: {}", path);
+                    tracing::info!("[find_iflow_path] 找到: {}", path);
                     return Some(path);
                 }
             }
         }
 
-        eprintln!("[find_iflow_path] 未找到 iflow");
+        tracing::warn!("[find_iflow_path] 未找到 iflow");
         None
     }
 
@@ -478,6 +533,31 @@ impl ConfigStore {
         }
     }
 
+    /// 检查 Claude CLI 在 Windows 下能否直接通过 Node.js 启动
+    ///
+    /// 路径为 .cmd/.bat 且无法解析出 node.exe 或 cli.js 时返回原因（列出已查找的位置），
+    /// 此时聊天会回退为经 `cmd /C` 调用，包含特殊字符的消息可能被截断。
+    /// `node_path` 为配置中显式指定的 node.exe
+    pub fn claude_launch_warning(path: &str, node_path: Option<&str>) -> Option<String> {
+        #[cfg(windows)]
+        {
+            use crate::services::claude_launcher::{is_batch_script, resolve_node_and_cli};
+
+            if !is_batch_script(path) {
+                return None;
+            }
+            resolve_node_and_cli(path, node_path).err().map(|e| {
+                format!("{}。将回退为通过 cmd /C 调用，包含特殊字符的消息可能无法正确传递", e)
+            })
+        }
+
+        #[cfg(not(windows))]
+        {
+            let _ = (path, node_path);
+            None
+        }
+    }
+
     /// 查找所有可用的 IFlow CLI 路径
     pub fn find_iflow_paths() -> Vec<String> {
         let mut paths = Vec::new();
@@ -665,13 +745,15 @@ impl OldConfig {
             default_engine: "claude-code".to_string(),
             claude_code: crate::models::config::ClaudeCodeConfig {
                 cli_path: self.claude_cmd,
+                node_path: None,
+                model: None,
             },
             iflow: Default::default(),
             work_dir: self.work_dir,
             session_dir: self.session_dir,
             git_bin_path: self.git_bin_path,
-            floating_window: Default::default(),
             claude_cmd: Some(claude_cmd_clone),
+            ..Default::default()
         }
     }
 }
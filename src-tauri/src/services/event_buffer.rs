@@ -0,0 +1,142 @@
+//! 会话事件缓冲
+//!
+//! 后台线程发送给窗口的聊天事件同时写入按会话划分的环形缓冲区。窗口关闭后会话仍在后台运行，
+//! 重新打开的窗口可以通过 `drain_session_events` 取回期间错过的事件
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// 最多保留缓冲区的会话数，超出时丢弃最早创建的缓冲区
+const MAX_BUFFERED_SESSIONS: usize = 20;
+
+/// 按会话划分的事件缓冲区（保存已序列化的事件 JSON）
+#[derive(Default)]
+pub struct SessionEventBuffer {
+    buffers: HashMap<String, VecDeque<String>>,
+    /// 会话别名（如真实会话 ID -> 启动时返回的临时 ID）
+    aliases: HashMap<String, String>,
+    /// 缓冲区创建顺序
+    order: VecDeque<String>,
+}
+
+impl SessionEventBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn resolve<'a>(&'a self, session_id: &'a str) -> &'a str {
+        self.aliases.get(session_id).map(String::as_str).unwrap_or(session_id)
+    }
+
+    /// 写入事件，超过 `capacity` 时丢弃最早的事件
+    pub fn push(&mut self, session_id: &str, event_json: &str, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+
+        let key = self.resolve(session_id).to_string();
+        if !self.buffers.contains_key(&key) {
+            while self.order.len() >= MAX_BUFFERED_SESSIONS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.buffers.remove(&oldest);
+                    self.aliases.retain(|_, target| *target != oldest);
+                }
+            }
+            self.order.push_back(key.clone());
+        }
+
+        let buffer = self.buffers.entry(key).or_default();
+        while buffer.len() >= capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(event_json.to_string());
+    }
+
+    /// 为会话添加别名，之后通过别名写入和取出的都是同一个缓冲区
+    pub fn alias(&mut self, alias_id: &str, session_id: &str) {
+        if alias_id == session_id {
+            return;
+        }
+        let target = self.resolve(session_id).to_string();
+        self.aliases.insert(alias_id.to_string(), target);
+    }
+
+    /// 取出并清空会话缓冲的全部事件（按发送顺序）
+    pub fn drain(&mut self, session_id: &str) -> Vec<String> {
+        let key = self.resolve(session_id).to_string();
+        self.buffers
+            .get_mut(&key)
+            .map(|buffer| buffer.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// 绑定到单个会话的事件记录器，可移动到后台线程中使用
+#[derive(Clone)]
+pub struct SessionEventRecorder {
+    buffer: Arc<Mutex<SessionEventBuffer>>,
+    session_id: String,
+    capacity: usize,
+}
+
+impl SessionEventRecorder {
+    /// `capacity` 为该会话最多缓冲的事件数，为 0 时不缓冲
+    pub fn new(buffer: &Arc<Mutex<SessionEventBuffer>>, session_id: &str, capacity: usize) -> Self {
+        Self {
+            buffer: Arc::clone(buffer),
+            session_id: session_id.to_string(),
+            capacity,
+        }
+    }
+
+    /// 记录一条已发送的事件
+    pub fn record(&self, event_json: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.push(&self.session_id, event_json, self.capacity);
+        }
+    }
+
+    /// 会话获得新的 ID（如真实会话 ID）后登记别名
+    pub fn alias(&self, alias_id: &str) {
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.alias(alias_id, &self.session_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_recorded_under_alias_share_the_buffer() {
+        let buffer = Arc::new(Mutex::new(SessionEventBuffer::new()));
+        let recorder = SessionEventRecorder::new(&buffer, "temp-id", 10);
+        recorder.record(r#"{"type":"system"}"#);
+        recorder.alias("real-id");
+
+        // interrupt_chat 使用前端持有的会话 ID（真实 ID）记录 session_end
+        SessionEventRecorder::new(&buffer, "real-id", 10)
+            .record(r#"{"type":"session_end","reason":"interrupted"}"#);
+
+        let events = buffer.lock().unwrap().drain("temp-id");
+        assert_eq!(events, vec![
+            r#"{"type":"system"}"#.to_string(),
+            r#"{"type":"session_end","reason":"interrupted"}"#.to_string(),
+        ]);
+        assert!(buffer.lock().unwrap().drain("real-id").is_empty());
+    }
+
+    #[test]
+    fn buffer_keeps_latest_events_within_capacity() {
+        let mut buffer = SessionEventBuffer::new();
+        for i in 0..5 {
+            buffer.push("s", &i.to_string(), 3);
+        }
+
+        assert_eq!(buffer.drain("s"), vec!["2", "3", "4"]);
+    }
+}
@@ -0,0 +1,251 @@
+//! 流式事件合并
+//!
+//! 将短时间内连续到达的 `text_delta` 事件合并为一个事件发送，减少前端 IPC 次数。
+//! 其他事件（工具、错误、会话结束等）到达时会先发送已缓存的文本，再立即发送该事件，保证顺序不变
+
+use crate::models::events::StreamEvent;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 事件合并器，在后台线程中按时间窗口合并文本增量
+///
+/// 丢弃时会发送剩余的文本并等待后台线程结束
+pub struct EventCoalescer {
+    tx: Option<Sender<StreamEvent>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EventCoalescer {
+    /// 创建合并器，`window` 为文本增量的最大缓存时间
+    pub fn new<F>(window: Duration, mut emit: F) -> Self
+    where
+        F: FnMut(StreamEvent) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<StreamEvent>();
+
+        let handle = std::thread::spawn(move || {
+            let mut pending = String::new();
+            let mut deadline: Option<Instant> = None;
+
+            let flush = |pending: &mut String, emit: &mut F| {
+                if !pending.is_empty() {
+                    emit(StreamEvent::TextDelta { text: std::mem::take(pending) });
+                }
+            };
+
+            loop {
+                let received = match deadline {
+                    Some(d) => rx.recv_timeout(d.saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+
+                match received {
+                    Ok(StreamEvent::TextDelta { text }) => {
+                        pending.push_str(&text);
+                        deadline.get_or_insert_with(|| Instant::now() + window);
+                    }
+                    Ok(event) => {
+                        flush(&mut pending, &mut emit);
+                        deadline = None;
+                        emit(event);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        flush(&mut pending, &mut emit);
+                        deadline = None;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        flush(&mut pending, &mut emit);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self { tx: Some(tx), handle: Some(handle) }
+    }
+
+    /// 获取事件发送端（可移动到读取线程的回调中）
+    pub fn sender(&self) -> Sender<StreamEvent> {
+        self.tx.clone().expect("合并器未结束")
+    }
+
+    /// 发送剩余事件并等待后台线程结束（需先释放所有通过 `sender` 获取的发送端）
+    pub fn finish(self) {
+        drop(self);
+    }
+}
+
+impl Drop for EventCoalescer {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// 会话事件出口
+///
+/// 会话被中断后，读取线程和合并器中可能还有未发送的事件。中断方通过 `close` 发送 session_end
+/// 并关闭出口，之后经由该出口的事件都会被丢弃，前端不会在会话结束后再收到内容
+#[derive(Clone, Default)]
+pub struct EventGate {
+    closed: Arc<Mutex<bool>>,
+}
+
+impl EventGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 出口未关闭时执行发送，返回是否已发送
+    pub fn emit(&self, send: impl FnOnce()) -> bool {
+        let closed = self.closed.lock().unwrap_or_else(|e| e.into_inner());
+        if *closed {
+            return false;
+        }
+        send();
+        true
+    }
+
+    /// 执行最后一次发送并关闭出口（与 `emit` 互斥，正在进行的发送完成后才会执行）
+    pub fn close(&self, send: impl FnOnce()) {
+        let mut closed = self.closed.lock().unwrap_or_else(|e| e.into_inner());
+        if !*closed {
+            send();
+            *closed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 创建把事件收集到列表中的合并器
+    fn collecting(window: Duration) -> (EventCoalescer, Arc<Mutex<Vec<StreamEvent>>>) {
+        let emitted = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&emitted);
+        let coalescer = EventCoalescer::new(window, move |event| sink.lock().unwrap().push(event));
+        (coalescer, emitted)
+    }
+
+    fn text(text: &str) -> StreamEvent {
+        StreamEvent::TextDelta { text: text.to_string() }
+    }
+
+    /// 把事件转换为便于断言的简短描述
+    fn describe(events: &[StreamEvent]) -> Vec<String> {
+        events.iter().map(|event| match event {
+            StreamEvent::TextDelta { text } => format!("text:{}", text),
+            StreamEvent::ToolStart { tool_name, .. } => format!("tool_start:{}", tool_name),
+            StreamEvent::Error { error, .. } => format!("error:{}", error),
+            StreamEvent::SessionEnd => "session_end".to_string(),
+            other => format!("{:?}", other),
+        }).collect()
+    }
+
+    #[test]
+    fn consecutive_text_deltas_merge_within_window() {
+        // 窗口足够长，结果不受线程调度快慢影响
+        let (coalescer, emitted) = collecting(Duration::from_secs(60));
+        let tx = coalescer.sender();
+        for part in ["Hel", "lo", ", world"] {
+            tx.send(text(part)).unwrap();
+        }
+
+        drop(tx);
+        coalescer.finish();
+        assert_eq!(describe(&emitted.lock().unwrap()), vec!["text:Hello, world"]);
+    }
+
+    #[test]
+    fn pending_text_is_flushed_when_window_expires() {
+        let (coalescer, emitted) = collecting(Duration::from_millis(50));
+        let tx = coalescer.sender();
+        tx.send(text("a")).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while emitted.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // 合并器仍在运行，文本只能是窗口到期后发送的
+        assert_eq!(describe(&emitted.lock().unwrap()), vec!["text:a"]);
+
+        drop(tx);
+        coalescer.finish();
+    }
+
+    #[test]
+    fn non_text_events_flush_pending_text_immediately() {
+        // 窗口足够长，合并器结束前只有非文本事件能触发发送
+        let (coalescer, emitted) = collecting(Duration::from_secs(60));
+        let tx = coalescer.sender();
+
+        tx.send(text("a")).unwrap();
+        tx.send(StreamEvent::ToolStart {
+            tool_use_id: "t1".to_string(),
+            tool_name: "Bash".to_string(),
+            input: serde_json::Value::Null,
+        }).unwrap();
+        tx.send(text("b")).unwrap();
+        tx.send(StreamEvent::error("boom")).unwrap();
+        tx.send(text("c")).unwrap();
+        tx.send(StreamEvent::SessionEnd).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while emitted.lock().unwrap().len() < 6 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(describe(&emitted.lock().unwrap()), vec![
+            "text:a", "tool_start:Bash", "text:b", "error:boom", "text:c", "session_end",
+        ]);
+
+        drop(tx);
+        coalescer.finish();
+    }
+
+    #[test]
+    fn closed_gate_drops_events_flushed_after_session_end() {
+        let gate = EventGate::new();
+        let emitted = Arc::new(Mutex::new(Vec::new()));
+        let coalescer = {
+            let gate = gate.clone();
+            let sink = Arc::clone(&emitted);
+            EventCoalescer::new(Duration::from_secs(60), move |event| {
+                gate.emit(|| sink.lock().unwrap().push(event));
+            })
+        };
+        let tx = coalescer.sender();
+
+        tx.send(text("before")).unwrap();
+        tx.send(StreamEvent::error("tool failed")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while emitted.lock().unwrap().len() < 2 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // 中断时合并器中还缓存着文本，结束时才会被发送
+        tx.send(text("late")).unwrap();
+        gate.close(|| emitted.lock().unwrap().push(StreamEvent::SessionEnd));
+        drop(tx);
+        coalescer.finish();
+
+        assert_eq!(describe(&emitted.lock().unwrap()), vec![
+            "text:before", "error:tool failed", "session_end",
+        ]);
+    }
+
+    #[test]
+    fn pending_text_is_flushed_on_drop() {
+        let (coalescer, emitted) = collecting(Duration::from_secs(60));
+        let tx = coalescer.sender();
+        tx.send(text("tail")).unwrap();
+        drop(tx);
+
+        drop(coalescer);
+        assert_eq!(describe(&emitted.lock().unwrap()), vec!["text:tail"]);
+    }
+}
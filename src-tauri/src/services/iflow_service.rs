@@ -3,19 +3,20 @@
 /// 管理 IFlow CLI 进程和会话文件监控
 
 use crate::error::{AppError, Result};
-use crate::models::config::Config;
+use crate::models::config::{Config, IFlowConfig, TokenUsage};
+use crate::services::token_summary::CostAccumulator;
 use crate::models::events::StreamEvent;
 use crate::models::iflow_events::{
     IFlowJsonlEvent, IFlowSessionMeta, IFlowHistoryMessage, IFlowFileContext,
-    IFlowTokenStats, IFlowToolCall, IFlowProjectsConfig,
+    IFlowTokenStats, IFlowToolCall, IFlowProjectsConfig, IFlowMessageNode, IFlowFileContextDiff,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio, Child};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tauri::{Emitter, Window};
 use uuid::Uuid;
 
@@ -82,11 +83,11 @@ impl IFlowService {
     /// 获取项目会话目录
     fn get_project_session_dir(work_dir: &str) -> Result<PathBuf> {
         let config_dir = Self::get_iflow_config_dir()?;
-        eprintln!("[get_project_session_dir] config_dir: {:?}", config_dir);
-        eprintln!("[get_project_session_dir] work_dir: {}", work_dir);
+        tracing::info!("[get_project_session_dir] config_dir: {:?}", config_dir);
+        tracing::info!("[get_project_session_dir] work_dir: {}", work_dir);
 
         let encoded_path = Self::encode_project_path(work_dir);
-        eprintln!("[get_project_session_dir] encoded_path: {}", encoded_path);
+        tracing::info!("[get_project_session_dir] encoded_path: {}", encoded_path);
 
         // 先列出 projects 目录下的所有子目录，帮助调试
         let mut projects_dir = config_dir.clone();
@@ -144,10 +145,62 @@ impl IFlowService {
         latest_file.ok_or_else(|| AppError::ProcessError("未找到会话文件".to_string()))
     }
 
+    /// 获取工作目录对应的会话目录
+    pub fn project_session_dir(config: &Config) -> Result<PathBuf> {
+        let work_dir = config.work_dir.as_deref()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".".to_string());
+
+        Self::get_project_session_dir(&work_dir)
+    }
+
+    /// 获取工作目录下最近一次会话的 ID（取最新会话文件名，如 session-xxx）
+    pub fn latest_session_id(config: &Config) -> Result<String> {
+        let work_dir = config.work_dir.as_deref()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".".to_string());
+
+        let session_dir = Self::get_project_session_dir(&work_dir)?;
+        let latest = Self::find_latest_session(&session_dir)?;
+
+        latest.file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_string())
+            .ok_or_else(|| AppError::ProcessError(format!("无法解析会话文件名: {:?}", latest)))
+    }
+
+    /// 检测工作目录下最新会话文件中的会话 ID（sessionId 字段）
+    ///
+    /// 只接受 `since` 之后修改过的文件，避免误取到之前的会话。
+    /// 用于启动会话后无法从 stderr 解析出会话 ID 时的兜底
+    pub fn detect_latest_session_id(config: &Config, since: SystemTime) -> Option<String> {
+        let session_dir = Self::project_session_dir(config).ok()?;
+        Self::detect_latest_session_id_in(&session_dir, since)
+    }
+
+    /// 读取会话目录中最新会话文件的会话 ID，文件早于 `since` 时返回 None
+    fn detect_latest_session_id_in(session_dir: &Path, since: SystemTime) -> Option<String> {
+        let latest = Self::find_latest_session(session_dir).ok()?;
+
+        let modified = std::fs::metadata(&latest).and_then(|m| m.modified()).ok()?;
+        if modified < since {
+            tracing::info!("[detect_latest_session_id] 最新会话文件早于启动时间，忽略: {:?}", latest);
+            return None;
+        }
+
+        let file = File::open(&latest).ok()?;
+        BufReader::new(file)
+            .lines()
+            .map_while(std::result::Result::ok)
+            .filter_map(|line| serde_json::from_str::<IFlowJsonlEvent>(&line).ok())
+            .map(|event| event.session_id)
+            .find(|id| !id.is_empty())
+    }
+
     /// 启动新的 IFlow 聊天会话
     pub fn start_chat(config: &Config, message: &str) -> Result<IFlowSession> {
-        eprintln!("[IFlowService::start_chat] 启动 IFlow 会话");
-        eprintln!("[IFlowService::start_chat] 消息内容: {}", message);
+        tracing::info!("[IFlowService::start_chat] 启动 IFlow 会话");
+        tracing::info!("[IFlowService::start_chat] 消息长度: {} 字符", message.len());
 
         // 确定工作目录
         let work_dir = config.work_dir.as_deref()
@@ -164,14 +217,18 @@ impl IFlowService {
         let iflow_cmd = Self::get_iflow_cmd(config)?;
 
         // 构建命令
-        let mut cmd = Self::build_iflow_command(&iflow_cmd, &work_dir, message);
+        let mut cmd = Self::build_iflow_command(&iflow_cmd, &work_dir, message, &config.iflow);
+        cmd.envs(config.session_env());
 
         // 记录详细的命令信息用于调试
         let program = cmd.get_program().to_string_lossy().to_string();
-        let args: Vec<String> = cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect();
-        eprintln!("[IFlowService] 执行命令: {}", program);
-        eprintln!("[IFlowService] 命令参数: {:?}", args);
-        eprintln!("[IFlowService] 工作目录: {}", work_dir);
+        // 消息内容不写入日志，只保留其余参数
+        let args: Vec<String> = cmd.get_args()
+            .map(|a| if a == message { "<消息>".to_string() } else { a.to_string_lossy().to_string() })
+            .collect();
+        tracing::info!("[IFlowService] 执行命令: {}", program);
+        tracing::info!("[IFlowService] 命令参数: {:?}", args);
+        tracing::info!("[IFlowService] 工作目录: {}", work_dir);
 
         let child = cmd.spawn()
             .map_err(|e| {
@@ -179,12 +236,12 @@ impl IFlowService {
                     "启动 IFlow 失败: {}\n命令: {}\n参数: {:?}\n工作目录: {}",
                     e, program, args, work_dir
                 );
-                eprintln!("[IFlowService] {}", error_msg);
+                tracing::warn!("[IFlowService] {}", error_msg);
                 AppError::ProcessError(error_msg)
             })?;
 
         let process_id = child.id();
-        eprintln!("[IFlowService] 进程 PID: {:?}", process_id);
+        tracing::info!("[IFlowService] 进程 PID: {:?}", process_id);
 
         // 生成临时会话 ID
         let temp_id = Uuid::new_v4().to_string();
@@ -213,14 +270,14 @@ impl IFlowService {
     }
 
     /// 构建 IFlow 命令
-    fn build_iflow_command(iflow_cmd: &str, work_dir: &str, message: &str) -> Command {
+    fn build_iflow_command(iflow_cmd: &str, work_dir: &str, message: &str, options: &IFlowConfig) -> Command {
         let mut cmd = Command::new(iflow_cmd);
 
         // 基础参数
         // 注意：std::process::Command::arg() 在 Windows 上使用 CreateProcess API，
         // 不通过 shell，因此不需要转义特殊字符
-        cmd.arg("--yolo")  // 自动确认所有操作
-            .arg("--prompt")
+        Self::apply_iflow_options(&mut cmd, options);
+        cmd.arg("--prompt")
             .arg(message);
 
         // 设置工作目录
@@ -237,6 +294,22 @@ impl IFlowService {
         cmd
     }
 
+    /// 添加模型与审批模式参数，未配置时保持默认行为（--yolo）
+    fn apply_iflow_options(cmd: &mut Command, options: &IFlowConfig) {
+        match options.approval_mode.as_deref().filter(|m| !m.is_empty()) {
+            Some(mode) => {
+                cmd.arg("--approval-mode").arg(mode);
+            }
+            None => {
+                cmd.arg("--yolo");  // 自动确认所有操作
+            }
+        }
+
+        if let Some(model) = options.model.as_deref().filter(|m| !m.is_empty()) {
+            cmd.arg("--model").arg(model);
+        }
+    }
+
     /// 监控会话文件并发送事件（类似 tail -f）
     ///
     /// # 参数
@@ -259,7 +332,7 @@ impl IFlowService {
         F: FnMut(StreamEvent) + Send + 'static,
     {
         std::thread::spawn(move || {
-            eprintln!("[IFlowService] 开始监控文件: {:?}, 从第 {} 行开始", jsonl_path, start_line);
+            tracing::info!("[IFlowService] 开始监控文件: {:?}, 从第 {} 行开始", jsonl_path, start_line);
 
             // 等待文件创建
             let mut wait_count = 0;
@@ -269,10 +342,8 @@ impl IFlowService {
             }
 
             if !jsonl_path.exists() {
-                eprintln!("[IFlowService] 文件未创建: {:?}", jsonl_path);
-                callback(StreamEvent::Error {
-                    error: "会话文件未创建".to_string(),
-                });
+                tracing::info!("[IFlowService] 文件未创建: {:?}", jsonl_path);
+                callback(StreamEvent::error("会话文件未创建"));
                 return;
             }
 
@@ -287,10 +358,8 @@ impl IFlowService {
                 let file = match File::open(&jsonl_path) {
                     Ok(f) => f,
                     Err(e) => {
-                        eprintln!("[IFlowService] 打开文件失败: {}", e);
-                        callback(StreamEvent::Error {
-                            error: format!("打开会话文件失败: {}", e),
-                        });
+                        tracing::warn!("[IFlowService] 打开文件失败: {}", e);
+                        callback(StreamEvent::error(format!("打开会话文件失败: {}", e)));
                         return;
                     }
                 };
@@ -303,7 +372,7 @@ impl IFlowService {
                     let line = match line {
                         Ok(l) => l,
                         Err(e) => {
-                            eprintln!("[IFlowService] 读取行错误: {}", e);
+                            tracing::warn!("[IFlowService] 读取行错误: {}", e);
                             break;
                         }
                     };
@@ -335,12 +404,12 @@ impl IFlowService {
 
                             // 如果检测到会话结束，退出
                             if is_session_end {
-                                eprintln!("[IFlowService] 检测到会话结束");
+                                tracing::info!("[IFlowService] 检测到会话结束");
                                 return;
                             }
                         }
                     } else {
-                        eprintln!("[IFlowService] 解析失败: {}", line_trimmed.chars().take(100).collect::<String>());
+                        tracing::warn!("[IFlowService] 解析失败: {}", line_trimmed.chars().take(100).collect::<String>());
                     }
                 }
 
@@ -348,7 +417,7 @@ impl IFlowService {
                 if !has_new_content {
                     sleep_count += 1;
                     if sleep_count >= MAX_SLEEPS {
-                        eprintln!("[IFlowService] 等待超时，文件监控结束");
+                        tracing::warn!("[IFlowService] 等待超时，文件监控结束");
                         return;
                     }
                     std::thread::sleep(Duration::from_millis(100));
@@ -374,8 +443,8 @@ impl IFlowService {
 
     /// 继续聊天会话
     pub fn continue_chat(config: &Config, session_id: &str, message: &str) -> Result<Child> {
-        eprintln!("[IFlowService::continue_chat] 继续会话: {}", session_id);
-        eprintln!("[IFlowService::continue_chat] 消息内容: {}", message);
+        tracing::info!("[IFlowService::continue_chat] 继续会话: {}", session_id);
+        tracing::info!("[IFlowService::continue_chat] 消息长度: {} 字符", message.len());
 
         let work_dir = config.work_dir.as_deref()
             .map(|p| p.to_string_lossy().to_string())
@@ -393,13 +462,14 @@ impl IFlowService {
         // 注意：std::process::Command::arg() 在 Windows 上使用 CreateProcess API，
         // 不通过 shell，因此不需要转义特殊字符
         let mut cmd = Command::new(&iflow_cmd);
-        cmd.arg("--yolo")
-            .arg("--resume")
+        Self::apply_iflow_options(&mut cmd, &config.iflow);
+        cmd.arg("--resume")
             .arg(session_id)
             .arg("--prompt")
             .arg(message);
 
         cmd.current_dir(&work_dir);
+        cmd.envs(config.session_env());
         cmd.stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
@@ -408,10 +478,13 @@ impl IFlowService {
 
         // 记录详细的命令信息用于调试
         let program = cmd.get_program().to_string_lossy().to_string();
-        let args: Vec<String> = cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect();
-        eprintln!("[IFlowService] 执行命令: {}", program);
-        eprintln!("[IFlowService] 命令参数: {:?}", args);
-        eprintln!("[IFlowService] 工作目录: {}", work_dir);
+        // 消息内容不写入日志，只保留其余参数
+        let args: Vec<String> = cmd.get_args()
+            .map(|a| if a == message { "<消息>".to_string() } else { a.to_string_lossy().to_string() })
+            .collect();
+        tracing::info!("[IFlowService] 执行命令: {}", program);
+        tracing::info!("[IFlowService] 命令参数: {:?}", args);
+        tracing::info!("[IFlowService] 工作目录: {}", work_dir);
 
         cmd.spawn()
             .map_err(|e| {
@@ -419,7 +492,7 @@ impl IFlowService {
                     "继续 IFlow 会话失败: {}\n命令: {}\n参数: {:?}\n工作目录: {}\n会话ID: {}",
                     e, program, args, work_dir, session_id
                 );
-                eprintln!("[IFlowService] {}", error_msg);
+                tracing::warn!("[IFlowService] {}", error_msg);
                 AppError::ProcessError(error_msg)
             })
     }
@@ -430,12 +503,12 @@ impl IFlowService {
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_else(|| ".".to_string());
 
-        eprintln!("[find_session_jsonl] work_dir: {}", work_dir);
-        eprintln!("[find_session_jsonl] session_id: {}", session_id);
+        tracing::info!("[find_session_jsonl] work_dir: {}", work_dir);
+        tracing::info!("[find_session_jsonl] session_id: {}", session_id);
 
         let session_dir = Self::get_project_session_dir(&work_dir)?;
-        eprintln!("[find_session_jsonl] session_dir: {:?}", session_dir);
-        eprintln!("[find_session_jsonl] session_dir 存在: {}", session_dir.exists());
+        tracing::info!("[find_session_jsonl] session_dir: {:?}", session_dir);
+        tracing::info!("[find_session_jsonl] session_dir 存在: {}", session_dir.exists());
 
         // 查找包含指定 session_id 的文件
         let entries = std::fs::read_dir(&session_dir)
@@ -449,7 +522,7 @@ impl IFlowService {
                 file_count += 1;
 
                 if filename.starts_with("session-") && filename.ends_with(".jsonl") {
-                    eprintln!("[find_session_jsonl] 匹配文件名格式，检查内容");
+                    tracing::info!("[find_session_jsonl] 匹配文件名格式，检查内容");
                     // 检查文件内容是否匹配 session_id
                     if let Ok(file) = File::open(&path) {
                         let reader = BufReader::new(file);
@@ -468,13 +541,13 @@ impl IFlowService {
                             }
                         }
                     } else {
-                        eprintln!("[find_session_jsonl] 无法打开文件");
+                        tracing::warn!("[find_session_jsonl] 无法打开文件");
                     }
                 }
             }
         }
 
-        eprintln!("[find_session_jsonl] 共检查 {} 个文件，未找到匹配", file_count);
+        tracing::warn!("[find_session_jsonl] 共检查 {} 个文件，未找到匹配", file_count);
         Err(AppError::ProcessError(format!("未找到会话文件: {}", session_id)))
     }
 
@@ -487,7 +560,7 @@ impl IFlowService {
         let config_dir = Self::get_iflow_config_dir()?;
         let projects_json_path = config_dir.join("config").join("projects.json");
 
-        eprintln!("[read_projects_config] 读取: {:?}", projects_json_path);
+        tracing::info!("[read_projects_config] 读取: {:?}", projects_json_path);
 
         if !projects_json_path.exists() {
             return Ok(IFlowProjectsConfig {
@@ -518,7 +591,7 @@ impl IFlowService {
         let session_dir = Self::get_project_session_dir(&work_dir)?;
 
         if !session_dir.exists() {
-            eprintln!("[list_sessions] 会话目录不存在: {:?}", session_dir);
+            tracing::warn!("[list_sessions] 会话目录不存在: {:?}", session_dir);
             return Ok(Vec::new());
         }
 
@@ -557,6 +630,8 @@ impl IFlowService {
         let mut message_count = 0u32;
         let mut input_tokens = 0u32;
         let mut output_tokens = 0u32;
+        let mut cache_creation_tokens = 0u32;
+        let mut cache_read_tokens = 0u32;
         let mut first_user_content = String::new();
         let mut created_at: Option<String> = None;
         let mut updated_at: Option<String> = None;
@@ -597,6 +672,8 @@ impl IFlowService {
                     if let Some(ref usage) = message.usage {
                         input_tokens += usage.input_tokens;
                         output_tokens += usage.output_tokens;
+                        cache_creation_tokens += usage.cache_creation_input_tokens;
+                        cache_read_tokens += usage.cache_read_input_tokens;
                     }
                 }
             }
@@ -617,24 +694,35 @@ impl IFlowService {
         Ok(IFlowSessionMeta {
             session_id,
             title,
+            tags: Vec::new(),
+            pinned: false,
             message_count,
             file_size,
             created_at: created_at.unwrap_or_else(|| String::from("")),
             updated_at: updated_at.unwrap_or_else(|| String::from("")),
             input_tokens,
             output_tokens,
+            cache_creation_tokens,
+            cache_read_tokens,
         })
     }
 
     /// 获取会话的完整历史消息
     pub fn get_session_history(config: &Config, session_id: &str) -> Result<Vec<IFlowHistoryMessage>> {
         let jsonl_path = Self::find_session_jsonl(config, session_id)?;
+        let (messages, _) = Self::read_history_messages(&jsonl_path)?;
+        Ok(messages)
+    }
 
-        let file = File::open(&jsonl_path)
+    /// 读取会话文件中的 user/assistant 消息（按时间排序并配对工具结果），
+    /// 同时返回所有事件的 uuid -> parentUuid 映射
+    fn read_history_messages(jsonl_path: &Path) -> Result<(Vec<IFlowHistoryMessage>, HashMap<String, String>)> {
+        let file = File::open(jsonl_path)
             .map_err(|e| AppError::ProcessError(format!("打开会话文件失败: {}", e)))?;
 
         let reader = BufReader::new(file);
         let mut messages = Vec::new();
+        let mut parents = HashMap::new();
 
         for line in reader.lines() {
             let line = line.map_err(|e| AppError::ProcessError(format!("读取行失败: {}", e)))?;
@@ -645,12 +733,16 @@ impl IFlowService {
             }
 
             if let Some(event) = IFlowJsonlEvent::parse_line(line_trimmed) {
+                if let Some(ref parent) = event.parent_uuid {
+                    parents.insert(event.uuid.clone(), parent.clone());
+                }
+
                 // 只处理 user 和 assistant 类型
                 if event.event_type == "user" || event.event_type == "assistant" {
-                    let tool_calls = if event.event_type == "assistant" {
-                        Self::extract_tool_calls_from_event(&event)
+                    let (tool_calls, tool_results) = if event.event_type == "assistant" {
+                        (Self::extract_tool_calls_from_event(&event), Vec::new())
                     } else {
-                        Vec::new()
+                        (Vec::new(), event.extract_history_tool_results())
                     };
 
                     let input_tokens = event.message.as_ref()
@@ -671,6 +763,7 @@ impl IFlowService {
                         input_tokens,
                         output_tokens,
                         tool_calls,
+                        tool_results,
                     });
                 }
             }
@@ -679,7 +772,128 @@ impl IFlowService {
         // 按时间戳排序
         messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
 
-        Ok(messages)
+        Self::pair_tool_results(&mut messages);
+
+        Ok((messages, parents))
+    }
+
+    /// 获取会话消息树，用于展示对话分支
+    ///
+    /// 父消息不是 user/assistant 消息时沿 parentUuid 继续向上查找；
+    /// 检测到循环引用时断开该节点与父节点的连接，将其作为根节点
+    pub fn get_session_tree(config: &Config, session_id: &str) -> Result<Vec<IFlowMessageNode>> {
+        let jsonl_path = Self::find_session_jsonl(config, session_id)?;
+        Self::session_tree_from_file(&jsonl_path)
+    }
+
+    /// 从会话文件构建消息树
+    fn session_tree_from_file(jsonl_path: &Path) -> Result<Vec<IFlowMessageNode>> {
+        let (messages, parents) = Self::read_history_messages(jsonl_path)?;
+
+        let index: HashMap<&str, usize> = messages.iter()
+            .enumerate()
+            .map(|(i, m)| (m.uuid.as_str(), i))
+            .collect();
+
+        // 解析每条消息在树中的父节点（跳过未展示的中间事件）
+        let mut parent_of: Vec<Option<usize>> = messages.iter()
+            .map(|m| {
+                let mut current = m.parent_uuid.as_deref();
+                let mut steps = 0;
+                while let Some(uuid) = current {
+                    if let Some(&i) = index.get(uuid) {
+                        return Some(i);
+                    }
+                    // 防御：中间事件之间存在循环时停止
+                    steps += 1;
+                    if steps > parents.len() {
+                        break;
+                    }
+                    current = parents.get(uuid).map(|p| p.as_str());
+                }
+                None
+            })
+            .collect();
+
+        // 断开循环：从父节点向上能回到自身时，将自身作为根节点
+        for i in 0..messages.len() {
+            let mut current = parent_of[i];
+            let mut steps = 0;
+            while let Some(p) = current {
+                if p == i {
+                    tracing::info!("[get_session_tree] 检测到循环引用，断开: {}", messages[i].uuid);
+                    parent_of[i] = None;
+                    break;
+                }
+                steps += 1;
+                if steps > messages.len() {
+                    break;
+                }
+                current = parent_of[p];
+            }
+        }
+
+        // 子节点列表（messages 已按时间排序，因此子节点也按时间排序）
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); messages.len()];
+        for (i, parent) in parent_of.iter().enumerate() {
+            if let Some(p) = *parent {
+                children[p].push(i);
+            }
+        }
+
+        // 计算深度（自根节点向下遍历）
+        let mut depth = vec![0usize; messages.len()];
+        let mut stack: Vec<usize> = (0..messages.len()).filter(|&i| parent_of[i].is_none()).collect();
+        while let Some(i) = stack.pop() {
+            for &c in &children[i] {
+                depth[c] = depth[i] + 1;
+                stack.push(c);
+            }
+        }
+
+        // 活动路径：从最新消息沿父节点回溯到根节点
+        let mut active = vec![false; messages.len()];
+        let mut current = messages.len().checked_sub(1);
+        while let Some(i) = current {
+            if active[i] {
+                break;
+            }
+            active[i] = true;
+            current = parent_of[i];
+        }
+
+        let nodes = messages.iter()
+            .enumerate()
+            .map(|(i, m)| IFlowMessageNode {
+                message: m.clone(),
+                parent_uuid: parent_of[i].map(|p| messages[p].uuid.clone()),
+                children: children[i].iter().map(|&c| messages[c].uuid.clone()).collect(),
+                depth: depth[i],
+                active: active[i],
+            })
+            .collect();
+
+        Ok(nodes)
+    }
+
+    /// 将 user 消息中的工具结果按 tool_use_id 关联到对应的工具调用
+    fn pair_tool_results(messages: &mut [IFlowHistoryMessage]) {
+        let results: HashMap<String, (String, Option<String>)> = messages.iter()
+            .flat_map(|m| m.tool_results.iter())
+            .filter(|r| !r.tool_use_id.is_empty())
+            .map(|r| (r.tool_use_id.clone(), (r.output.clone(), r.status.clone())))
+            .collect();
+
+        if results.is_empty() {
+            return;
+        }
+
+        for call in messages.iter_mut().flat_map(|m| m.tool_calls.iter_mut()) {
+            if let Some((output, status)) = results.get(&call.id) {
+                call.output = Some(output.clone());
+                call.status = status.clone();
+            }
+        }
     }
 
     /// 从事件中提取工具调用
@@ -703,6 +917,8 @@ impl IFlowService {
                                         .to_string(),
                                     input: obj.get("input").cloned()
                                         .unwrap_or(serde_json::Value::Null),
+                                    output: None,
+                                    status: None,
                                 });
                             }
                         }
@@ -717,8 +933,41 @@ impl IFlowService {
     /// 获取会话的文件上下文
     pub fn get_file_contexts(config: &Config, session_id: &str) -> Result<Vec<IFlowFileContext>> {
         let jsonl_path = Self::find_session_jsonl(config, session_id)?;
+        Self::read_file_contexts(&jsonl_path)
+    }
+
+    /// 比较两个会话访问过的文件
+    ///
+    /// 会话不存在时返回 `SessionNotFound`
+    pub fn diff_file_contexts(config: &Config, session_a: &str, session_b: &str) -> Result<IFlowFileContextDiff> {
+        let find = |session_id: &str| Self::find_session_jsonl(config, session_id)
+            .map_err(|_| AppError::SessionNotFound(session_id.to_string()));
 
-        let file = File::open(&jsonl_path)
+        Self::diff_file_contexts_between(&find(session_a)?, &find(session_b)?)
+    }
+
+    /// 按文件路径比较两个会话文件访问过的文件，访问次数或类型不同的同一路径计入 `both`
+    fn diff_file_contexts_between(jsonl_a: &Path, jsonl_b: &Path) -> Result<IFlowFileContextDiff> {
+        let paths_of = |jsonl_path: &Path| -> Result<BTreeSet<String>> {
+            Ok(Self::read_file_contexts(jsonl_path)?
+                .into_iter()
+                .map(|ctx| ctx.path)
+                .collect())
+        };
+
+        let paths_a = paths_of(jsonl_a)?;
+        let paths_b = paths_of(jsonl_b)?;
+
+        Ok(IFlowFileContextDiff {
+            only_a: paths_a.difference(&paths_b).cloned().collect(),
+            only_b: paths_b.difference(&paths_a).cloned().collect(),
+            both: paths_a.intersection(&paths_b).cloned().collect(),
+        })
+    }
+
+    /// 从会话文件中提取文件上下文
+    fn read_file_contexts(jsonl_path: &Path) -> Result<Vec<IFlowFileContext>> {
+        let file = File::open(jsonl_path)
             .map_err(|e| AppError::ProcessError(format!("打开会话文件失败: {}", e)))?;
 
         let reader = BufReader::new(file);
@@ -800,14 +1049,22 @@ impl IFlowService {
     /// 获取会话的 Token 统计
     pub fn get_token_stats(config: &Config, session_id: &str) -> Result<IFlowTokenStats> {
         let jsonl_path = Self::find_session_jsonl(config, session_id)?;
+        Self::token_stats_from_file(config, &jsonl_path)
+    }
 
-        let file = File::open(&jsonl_path)
+    /// 统计单个会话文件的 Token 使用
+    pub fn token_stats_from_file(config: &Config, jsonl_path: &Path) -> Result<IFlowTokenStats> {
+        let file = File::open(jsonl_path)
             .map_err(|e| AppError::ProcessError(format!("打开会话文件失败: {}", e)))?;
 
         let reader = BufReader::new(file);
 
         let mut total_input_tokens = 0u32;
         let mut total_output_tokens = 0u32;
+        let mut total_cache_creation_tokens = 0u32;
+        let mut total_cache_read_tokens = 0u32;
+        let mut model: Option<String> = None;
+        let mut estimated_cost = CostAccumulator::default();
         let mut message_count = 0u32;
         let mut user_message_count = 0u32;
         let mut assistant_message_count = 0u32;
@@ -829,9 +1086,24 @@ impl IFlowService {
                     message_count += 1;
 
                     if let Some(ref message) = event.message {
+                        if message.model.is_some() {
+                            model = message.model.clone();
+                        }
+
                         if let Some(ref usage) = message.usage {
                             total_input_tokens += usage.input_tokens;
                             total_output_tokens += usage.output_tokens;
+                            total_cache_creation_tokens += usage.cache_creation_input_tokens;
+                            total_cache_read_tokens += usage.cache_read_input_tokens;
+
+                            // 按每条消息的模型分别计价
+                            let usage = TokenUsage {
+                                input: usage.input_tokens as u64,
+                                output: usage.output_tokens as u64,
+                                cache_creation: usage.cache_creation_input_tokens as u64,
+                                cache_read: usage.cache_read_input_tokens as u64,
+                            };
+                            estimated_cost.add(message.model.as_deref().and_then(|m| config.estimate_cost(m, usage)));
                         }
                     }
                 }
@@ -841,10 +1113,311 @@ impl IFlowService {
         Ok(IFlowTokenStats {
             total_input_tokens: total_input_tokens,
             total_output_tokens: total_output_tokens,
+            total_cache_creation_tokens,
+            total_cache_read_tokens,
             total_tokens: total_input_tokens + total_output_tokens,
+            model,
+            estimated_cost_usd: estimated_cost.total(),
             message_count,
             user_message_count,
             assistant_message_count,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::ModelPrice;
+    use crate::models::iflow_events::IFlowToolResult;
+
+    fn history_message(r#type: &str) -> IFlowHistoryMessage {
+        IFlowHistoryMessage {
+            uuid: Uuid::new_v4().to_string(),
+            parent_uuid: None,
+            timestamp: "2024-01-01T00:00:00.000Z".to_string(),
+            r#type: r#type.to_string(),
+            content: String::new(),
+            model: None,
+            stop_reason: None,
+            input_tokens: None,
+            output_tokens: None,
+            tool_calls: Vec::new(),
+            tool_results: Vec::new(),
+        }
+    }
+
+    fn tool_call(id: &str) -> IFlowToolCall {
+        IFlowToolCall {
+            id: id.to_string(),
+            name: "read_file".to_string(),
+            input: serde_json::json!({ "path": "src/main.rs" }),
+            output: None,
+            status: None,
+        }
+    }
+
+    fn tool_result(tool_use_id: &str, output: &str) -> IFlowToolResult {
+        IFlowToolResult {
+            tool_use_id: tool_use_id.to_string(),
+            tool_name: Some("read_file".to_string()),
+            status: Some("success".to_string()),
+            output: output.to_string(),
+            timestamp: None,
+            timestamp_iso: None,
+        }
+    }
+
+    fn iflow_args(options: &IFlowConfig) -> Vec<String> {
+        let mut cmd = Command::new("iflow");
+        IFlowService::apply_iflow_options(&mut cmd, options);
+        cmd.get_args().map(|a| a.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn apply_iflow_options_passes_model_and_approval_mode() {
+        let options = IFlowConfig {
+            model: Some("glm-4.6".to_string()),
+            approval_mode: Some("plan".to_string()),
+            ..IFlowConfig::default()
+        };
+
+        assert_eq!(iflow_args(&options), vec!["--approval-mode", "plan", "--model", "glm-4.6"]);
+    }
+
+    #[test]
+    fn apply_iflow_options_defaults_to_yolo() {
+        let options = IFlowConfig {
+            model: Some(String::new()),
+            ..IFlowConfig::default()
+        };
+
+        assert_eq!(iflow_args(&options), vec!["--yolo"]);
+    }
+
+    /// 写入只包含一条事件的会话文件，并设置修改时间
+    fn write_session(dir: &Path, session_id: &str, modified: SystemTime) {
+        let line = serde_json::json!({
+            "uuid": "u-1",
+            "parentUuid": null,
+            "sessionId": session_id,
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "type": "user",
+            "isSidechain": false,
+            "userType": "external",
+            "message": { "role": "user", "content": "hi" }
+        });
+        let path = dir.join(format!("{}.jsonl", session_id));
+        std::fs::write(&path, format!("{}\n", line)).unwrap();
+        File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn detect_latest_session_id_picks_newest_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        write_session(dir.path(), "session-old", now - Duration::from_secs(120));
+        write_session(dir.path(), "session-new", now - Duration::from_secs(10));
+        write_session(dir.path(), "session-mid", now - Duration::from_secs(60));
+
+        let since = now - Duration::from_secs(30);
+        assert_eq!(
+            IFlowService::detect_latest_session_id_in(dir.path(), since).as_deref(),
+            Some("session-new")
+        );
+        // 最新文件早于启动时间时不采用
+        assert_eq!(IFlowService::detect_latest_session_id_in(dir.path(), now), None);
+    }
+
+    #[test]
+    fn detect_latest_session_id_empty_dir() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(IFlowService::detect_latest_session_id_in(dir.path(), SystemTime::UNIX_EPOCH), None);
+        assert_eq!(
+            IFlowService::detect_latest_session_id_in(&dir.path().join("missing"), SystemTime::UNIX_EPOCH),
+            None
+        );
+    }
+
+    /// 会话事件行（`parent` 为 None 表示根消息）
+    fn tree_event(uuid: &str, parent: Option<&str>, minute: u32, r#type: &str) -> String {
+        serde_json::json!({
+            "uuid": uuid,
+            "parentUuid": parent,
+            "sessionId": "session-tree",
+            "timestamp": format!("2024-01-01T00:{:02}:00.000Z", minute),
+            "type": r#type,
+            "isSidechain": false,
+            "userType": "external",
+            "message": { "role": r#type, "content": uuid }
+        }).to_string()
+    }
+
+    #[test]
+    fn session_tree_handles_branches_orphans_and_cycles() {
+        let lines = [
+            tree_event("u1", None, 1, "user"),
+            tree_event("a1", Some("u1"), 2, "assistant"),
+            // 非 user/assistant 的中间事件不出现在树中，子消息挂到其父消息上
+            tree_event("sys", Some("a1"), 3, "system"),
+            tree_event("u2", Some("sys"), 4, "user"),
+            // 从 a1 分出的第二个分支，包含最新消息
+            tree_event("u3", Some("a1"), 5, "user"),
+            tree_event("orphan", Some("missing"), 6, "user"),
+            tree_event("c1", Some("c2"), 7, "user"),
+            tree_event("c2", Some("c1"), 8, "assistant"),
+            tree_event("a3", Some("u3"), 9, "assistant"),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session-tree.jsonl");
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let nodes = IFlowService::session_tree_from_file(&path).unwrap();
+        let node = |uuid: &str| nodes.iter().find(|n| n.message.uuid == uuid).unwrap();
+
+        assert_eq!(nodes.len(), 8);
+        assert_eq!(node("a1").children, vec!["u2", "u3"]);
+        assert_eq!(node("u2").parent_uuid.as_deref(), Some("a1"));
+
+        let depths: Vec<(&str, usize)> = nodes.iter().map(|n| (n.message.uuid.as_str(), n.depth)).collect();
+        assert_eq!(depths, vec![
+            ("u1", 0), ("a1", 1), ("u2", 2), ("u3", 2), ("orphan", 0), ("c1", 0), ("c2", 1), ("a3", 3),
+        ]);
+
+        // 循环中先处理的节点被断开成为根节点
+        assert_eq!(node("c1").parent_uuid, None);
+        assert_eq!(node("c2").parent_uuid.as_deref(), Some("c1"));
+        assert_eq!(node("orphan").parent_uuid, None);
+
+        let active: Vec<&str> = nodes.iter().filter(|n| n.active).map(|n| n.message.uuid.as_str()).collect();
+        assert_eq!(active, vec!["u1", "a1", "u3", "a3"]);
+    }
+
+    /// 带 usage 的 assistant 事件行
+    fn usage_event(uuid: &str, model: &str, input: u32, output: u32) -> String {
+        serde_json::json!({
+            "uuid": uuid,
+            "parentUuid": null,
+            "sessionId": "session-cost",
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "type": "assistant",
+            "isSidechain": false,
+            "userType": "external",
+            "message": {
+                "role": "assistant",
+                "content": [],
+                "model": model,
+                "usage": { "input_tokens": input, "output_tokens": output, "cache_read_input_tokens": 1000 }
+            }
+        }).to_string()
+    }
+
+    /// 调用文件工具的 assistant 事件行
+    fn file_tool_event(uuid: &str, tool: &str, path: &str) -> String {
+        serde_json::json!({
+            "uuid": uuid,
+            "parentUuid": null,
+            "sessionId": "session-files",
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "type": "assistant",
+            "isSidechain": false,
+            "userType": "external",
+            "message": {
+                "role": "assistant",
+                "content": [{ "type": "tool_use", "id": uuid, "name": tool, "path": path }]
+            }
+        }).to_string()
+    }
+
+    #[test]
+    fn diff_file_contexts_reports_added_removed_and_changed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let session_a = dir.path().join("session-a.jsonl");
+        let session_b = dir.path().join("session-b.jsonl");
+        std::fs::write(&session_a, [
+            file_tool_event("a1", "read_file", "src/removed.rs"),
+            file_tool_event("a2", "read_file", "src/changed.rs"),
+        ].join("\n")).unwrap();
+        std::fs::write(&session_b, [
+            file_tool_event("b1", "read_file", "src/changed.rs"),
+            file_tool_event("b2", "read_file", "src/changed.rs"),
+            file_tool_event("b3", "list_directory", "src/added"),
+        ].join("\n")).unwrap();
+
+        let diff = IFlowService::diff_file_contexts_between(&session_a, &session_b).unwrap();
+        assert_eq!(diff.only_a, ["src/removed.rs"]);
+        assert_eq!(diff.only_b, ["src/added"]);
+        // 访问次数不同的同一路径视为两个会话都访问过
+        assert_eq!(diff.both, ["src/changed.rs"]);
+
+        let empty = dir.path().join("session-empty.jsonl");
+        std::fs::write(&empty, "").unwrap();
+        let diff = IFlowService::diff_file_contexts_between(&session_a, &empty).unwrap();
+        assert_eq!(diff.only_a, ["src/changed.rs", "src/removed.rs"]);
+        assert!(diff.only_b.is_empty() && diff.both.is_empty());
+    }
+
+    fn token_stats(lines: &[String]) -> IFlowTokenStats {
+        let mut config = Config::default();
+        config.model_pricing.insert("glm".to_string(), ModelPrice {
+            input: 2.0,
+            output: 8.0,
+            cache_write: None,
+            cache_read: Some(0.5),
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session-cost.jsonl");
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        IFlowService::token_stats_from_file(&config, &path).unwrap()
+    }
+
+    #[test]
+    fn token_stats_cost_for_priced_unpriced_and_missing_usage() {
+        let stats = token_stats(&[usage_event("a1", "glm-4.6", 1000, 500), usage_event("a2", "glm-4.6", 1000, 500)]);
+        let cost = stats.estimated_cost_usd.unwrap();
+        assert!((cost - (2000.0 * 2.0 + 1000.0 * 8.0 + 2000.0 * 0.5) / 1_000_000.0).abs() < 1e-12);
+
+        // 任一消息的模型未配置价格时费用未知
+        let stats = token_stats(&[usage_event("a1", "glm-4.6", 1000, 500), usage_event("a2", "kimi-k2", 1000, 500)]);
+        assert_eq!(stats.total_input_tokens, 2000);
+        assert_eq!(stats.estimated_cost_usd, None);
+
+        // 没有用量时费用未知，而不是 0
+        let user = serde_json::json!({
+            "uuid": "u1",
+            "parentUuid": null,
+            "sessionId": "session-cost",
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "type": "user",
+            "isSidechain": false,
+            "userType": "external",
+            "message": { "role": "user", "content": "hi" }
+        }).to_string();
+        let stats = token_stats(&[user]);
+        assert_eq!(stats.total_tokens, 0);
+        assert_eq!(stats.estimated_cost_usd, None);
+    }
+
+    #[test]
+    fn pair_tool_results_matches_by_tool_use_id() {
+        let mut assistant = history_message("assistant");
+        assistant.tool_calls = vec![tool_call("call-1"), tool_call("call-2")];
+        let mut user = history_message("user");
+        user.tool_results = vec![
+            tool_result("call-1", "fn main() {}"),
+            tool_result("call-unknown", "orphan output"),
+        ];
+        let mut messages = vec![assistant, user];
+
+        IFlowService::pair_tool_results(&mut messages);
+
+        let calls = &messages[0].tool_calls;
+        assert_eq!(calls[0].output.as_deref(), Some("fn main() {}"));
+        assert_eq!(calls[0].status.as_deref(), Some("success"));
+        // 没有对应结果的调用保持为空，未匹配的结果不会挂到其他调用上
+        assert_eq!(calls[1].output, None);
+        assert_eq!(calls[1].status, None);
+    }
+}
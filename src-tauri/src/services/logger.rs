@@ -1,54 +1,164 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
 use tracing::Level;
-use tracing_subscriber::{fmt, prelude::*, Registry};
+use tracing_subscriber::fmt::writer::{MakeWriter, OptionalWriter};
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry};
 use tracing_appender::{non_blocking, rolling};
 
-/// 日志服务
+/// 日志文件名前缀（实际文件名形如 app.2024-01-01.log）
+const LOG_FILE_PREFIX: &str = "app";
+
+/// 日志文件扩展名
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// 运行时日志级别重载句柄（仅在启用日志时设置）
+static RELOAD_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// 日志开关
+static LOG_SWITCH: LogSwitch = LogSwitch::new();
+
+/// 可在运行时开关的日志输出
+///
+/// 开启时输出到 stdout 并写入日志文件，关闭时两者都丢弃；
+/// 关闭会释放 WorkerGuard，确保已缓冲的日志写入文件
+struct LogSwitch {
+    enabled: AtomicBool,
+    writer: Mutex<Option<(non_blocking::NonBlocking, non_blocking::WorkerGuard)>>,
+    retention_days: AtomicUsize,
+}
+
+impl LogSwitch {
+    const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            writer: Mutex::new(None),
+            retention_days: AtomicUsize::new(1),
+        }
+    }
+
+    /// 开启日志（已开启时不做任何事）
+    fn enable(&self, log_dir: &Path) -> Result<(), String> {
+        let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        if writer.is_none() {
+            *writer = Some(Logger::file_writer(log_dir, self.retention_days.load(Ordering::Relaxed))?);
+        }
+        self.enabled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 关闭日志
+    fn disable(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+        // 在锁外释放 guard，避免刷新期间阻塞其他线程的日志写入
+        let previous = self.writer.lock().ok().and_then(|mut w| w.take());
+        drop(previous);
+    }
+
+    /// 控制台输出（日志关闭时丢弃）
+    fn console_writer(&self) -> OptionalWriter<std::io::Stdout> {
+        if self.enabled.load(Ordering::Relaxed) {
+            OptionalWriter::some(std::io::stdout())
+        } else {
+            OptionalWriter::none()
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogSwitch {
+    type Writer = OptionalWriter<non_blocking::NonBlocking>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        match self.writer.lock().ok().and_then(|w| w.as_ref().map(|(writer, _)| writer.clone())) {
+            Some(writer) => OptionalWriter::some(writer),
+            None => OptionalWriter::none(),
+        }
+    }
+}
+
+/// 日志服务（释放时刷新并关闭日志文件，需在应用运行期间保持存活）
 pub struct Logger {
-    _guard: Option<non_blocking::WorkerGuard>,
+    _private: (),
 }
 
 impl Logger {
     /// 初始化日志系统
-    pub fn init(enabled: bool) -> Self {
-        if !enabled {
-            // 如果禁用日志，使用默认的空订阅者
-            tracing_subscriber::registry()
-                .with(fmt::layer().with_writer(std::io::sink))
-                .init();
-            return Self { _guard: None };
+    ///
+    /// `enabled` 时输出到 stdout 并写入按天轮转的日志文件，否则不输出日志；`retention_days`
+    /// 为保留的日志文件数量（每天一个文件）。日志级别过滤器与日志开关均可在运行时调整
+    pub fn init(enabled: bool, retention_days: usize) -> Self {
+        LOG_SWITCH.retention_days.store(retention_days.max(1), Ordering::Relaxed);
+        if enabled {
+            if let Err(e) = LOG_SWITCH.enable(&Self::log_dir()) {
+                // 订阅者尚未初始化，只能直接输出到 stderr
+                eprintln!("[Logger::init] 创建日志文件失败: {}", e);
+            }
         }
+        let file_layer = fmt::layer()
+            .with_writer(|| LOG_SWITCH.make_writer())
+            .with_ansi(false)
+            .with_target(true);
 
-        // 获取日志目录
-        let log_dir = Self::log_dir();
-        std::fs::create_dir_all(&log_dir).ok();
-
-        // 创建日志文件（按天轮转）
-        let file_appender = rolling::daily(&log_dir, "app.log");
-        let (non_blocking_appender, guard) = non_blocking(file_appender);
-
-        // 配置订阅者
-        let env_filter = tracing_subscriber::EnvFilter::builder()
-            .with_default_directive(Level::INFO.into())
-            .from_env_lossy();
+        // 配置订阅者（过滤器包装为可重载层，支持运行时调整日志级别）
+        let (filter_layer, reload_handle) = reload::Layer::new(Self::default_filter());
+        let _ = RELOAD_HANDLE.set(reload_handle);
 
         tracing_subscriber::registry()
-            .with(env_filter)
+            .with(filter_layer)
             .with(
                 fmt::layer()
-                    .with_writer(std::io::stdout)
+                    .with_writer(|| LOG_SWITCH.console_writer())
                     .with_ansi(true)
                     .with_target(false)
             )
-            .with(
-                fmt::layer()
-                    .with_writer(non_blocking_appender)
-                    .with_ansi(false)
-                    .with_target(true)
-            )
+            .with(file_layer)
             .init();
 
-        Self { _guard: Some(guard) }
+        Self { _private: () }
+    }
+
+    /// 运行时开启或关闭日志（无需重启）
+    pub fn set_enabled(enabled: bool) -> Result<(), String> {
+        if enabled {
+            LOG_SWITCH.enable(&Self::log_dir())
+        } else {
+            LOG_SWITCH.disable();
+            Ok(())
+        }
+    }
+
+    /// 默认日志过滤器：读取 `RUST_LOG`，未设置时为 info
+    fn default_filter() -> EnvFilter {
+        EnvFilter::builder()
+            .with_default_directive(Level::INFO.into())
+            .from_env_lossy()
+    }
+
+    /// 创建按天轮转的日志文件写入器（超过保留天数的旧文件自动删除）
+    fn file_writer(
+        log_dir: &Path,
+        retention_days: usize,
+    ) -> Result<(non_blocking::NonBlocking, non_blocking::WorkerGuard), String> {
+        std::fs::create_dir_all(log_dir).ok();
+
+        rolling::RollingFileAppender::builder()
+            .rotation(rolling::Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix(LOG_FILE_SUFFIX)
+            .max_log_files(retention_days.max(1))
+            .build(log_dir)
+            .map(non_blocking)
+            .map_err(|e| format!("创建日志文件失败: {}", e))
+    }
+
+    /// 运行时修改日志级别
+    ///
+    /// `level` 支持 EnvFilter 语法，如 `debug`、`polaris=debug,info`
+    pub fn set_level(level: &str) -> Result<(), String> {
+        let handle = RELOAD_HANDLE.get()
+            .ok_or_else(|| "日志系统未初始化".to_string())?;
+
+        reload_filter(handle, level)
     }
 
     /// 获取日志目录
@@ -62,9 +172,35 @@ impl Logger {
         }
     }
 
-    /// 获取当前日志文件路径
+    /// 获取当前日志文件路径（最近修改的日志文件）
     pub fn current_log_file() -> PathBuf {
-        Self::log_dir().join("app.log")
+        Self::log_files()
+            .into_iter()
+            .last()
+            .unwrap_or_else(|| Self::log_dir().join(format!("{}.{}", LOG_FILE_PREFIX, LOG_FILE_SUFFIX)))
+    }
+
+    /// 列出所有日志文件（按修改时间升序）
+    pub fn log_files() -> Vec<PathBuf> {
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(Self::log_dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                        name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_SUFFIX)
+                    })
+                    .filter_map(|path| {
+                        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                        Some((modified, path))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        files.sort_by_key(|(modified, _)| *modified);
+        files.into_iter().map(|(_, path)| path).collect()
     }
 
     /// 清空日志文件
@@ -102,6 +238,26 @@ impl Logger {
     }
 }
 
+impl Drop for Logger {
+    fn drop(&mut self) {
+        LOG_SWITCH.disable();
+    }
+}
+
+/// 校验日志级别并替换可重载层中的过滤器
+fn reload_filter<S>(handle: &reload::Handle<EnvFilter, S>, level: &str) -> Result<(), String> {
+    let level = level.trim();
+    if level.is_empty() {
+        return Err("日志级别不能为空".to_string());
+    }
+
+    let new_filter = EnvFilter::try_new(level)
+        .map_err(|e| format!("无效的日志级别 '{}': {}", level, e))?;
+
+    handle.reload(new_filter)
+        .map_err(|e| format!("更新日志级别失败: {}", e))
+}
+
 // 使用宏简化日志调用
 #[macro_export]
 macro_rules! app_info {
@@ -130,3 +286,60 @@ macro_rules! app_debug {
         tracing::debug!($($arg)*)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_filter_changes_active_level() {
+        let (filter_layer, handle) = reload::Layer::new(EnvFilter::new("info"));
+        let subscriber = tracing_subscriber::registry().with(filter_layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(Level::DEBUG));
+
+            reload_filter(&handle, "debug").unwrap();
+            assert!(tracing::enabled!(Level::DEBUG));
+            assert_eq!(handle.with_current(|f| f.to_string()).unwrap(), "debug");
+        });
+    }
+
+    #[test]
+    fn log_switch_toggles_file_output() {
+        use std::io::Write;
+        use tracing_subscriber::fmt::writer::EitherWriter;
+
+        let dir = tempfile::tempdir().unwrap();
+        let switch = LogSwitch::new();
+        let log_text = || -> String {
+            std::fs::read_dir(dir.path())
+                .unwrap()
+                .flatten()
+                .map(|e| std::fs::read_to_string(e.path()).unwrap())
+                .collect()
+        };
+
+        switch.make_writer().write_all(b"dropped\n").unwrap();
+        assert!(matches!(switch.console_writer(), EitherWriter::B(_)), "关闭时不输出到控制台");
+
+        switch.enable(dir.path()).unwrap();
+        switch.make_writer().write_all(b"written\n").unwrap();
+        assert!(matches!(switch.console_writer(), EitherWriter::A(_)));
+        // 关闭时释放 guard，缓冲的日志应已写入文件
+        switch.disable();
+        switch.make_writer().write_all(b"after\n").unwrap();
+        assert!(matches!(switch.console_writer(), EitherWriter::B(_)));
+
+        assert_eq!(log_text(), "written\n");
+    }
+
+    #[test]
+    fn reload_filter_rejects_invalid_level() {
+        let (_filter_layer, handle) = reload::Layer::<EnvFilter, Registry>::new(EnvFilter::new("info"));
+
+        assert!(reload_filter(&handle, "  ").is_err());
+        assert!(reload_filter(&handle, "polaris=nope").is_err());
+        assert_eq!(handle.with_current(|f| f.to_string()).unwrap(), "info");
+    }
+}
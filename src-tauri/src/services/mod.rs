@@ -1,3 +1,10 @@
 pub mod config_store;
 pub mod logger;
 pub mod iflow_service;
+pub mod fs_utils;
+pub mod attachment;
+pub mod session_meta;
+pub mod event_coalescer;
+pub mod token_summary;
+pub mod event_buffer;
+pub mod claude_launcher;
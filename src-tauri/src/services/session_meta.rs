@@ -0,0 +1,262 @@
+//! 会话元数据存储
+//!
+//! 保存用户为会话设置的附加信息（自定义标题、标签、置顶等），以会话 ID 为键持久化到配置目录，
+//! 不修改 CLI 自身的会话文件
+
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// 单个会话的元数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMetadata {
+    /// 用户自定义标题
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// 标签（已规范化为小写，去重）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// 是否置顶
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+}
+
+impl SessionMetadata {
+    /// 是否未设置任何信息
+    fn is_empty(&self) -> bool {
+        self.title.is_none() && self.tags.is_empty() && !self.pinned
+    }
+}
+
+/// 规范化标签：去除首尾空白并转为小写，空标签返回 None
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// 会话元数据存储
+pub struct SessionMetaStore {
+    entries: HashMap<String, SessionMetadata>,
+    path: Option<PathBuf>,
+}
+
+impl SessionMetaStore {
+    /// 创建仅保存在内存中的存储
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            path: None,
+        }
+    }
+
+    /// 创建带磁盘持久化的存储，并加载已有数据
+    pub fn with_persistence(path: PathBuf) -> Self {
+        let entries = Self::load_from_file(&path);
+        tracing::info!("[SessionMetaStore] 从 {:?} 加载 {} 条会话元数据", path, entries.len());
+        Self {
+            entries,
+            path: Some(path),
+        }
+    }
+
+    fn load_from_file(path: &Path) -> HashMap<String, SessionMetadata> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(_) => return HashMap::new(),
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("[SessionMetaStore] 解析会话元数据失败: {}", e);
+            HashMap::new()
+        })
+    }
+
+    /// 获取会话元数据
+    pub fn get(&self, session_id: &str) -> Option<&SessionMetadata> {
+        self.entries.get(session_id)
+    }
+
+    /// 获取会话自定义标题
+    pub fn title(&self, session_id: &str) -> Option<&str> {
+        self.get(session_id).and_then(|m| m.title.as_deref())
+    }
+
+    /// 设置会话自定义标题，传入 None 或空字符串时清除
+    pub fn set_title(&mut self, session_id: &str, title: Option<String>) -> Result<()> {
+        let title = title
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+        self.update(session_id, |meta| meta.title = title)
+    }
+
+    /// 会话是否置顶
+    pub fn is_pinned(&self, session_id: &str) -> bool {
+        self.get(session_id).is_some_and(|m| m.pinned)
+    }
+
+    /// 设置会话置顶状态
+    pub fn set_pinned(&mut self, session_id: &str, pinned: bool) -> Result<()> {
+        self.update(session_id, |meta| meta.pinned = pinned)
+    }
+
+    /// 获取会话标签
+    pub fn tags(&self, session_id: &str) -> &[String] {
+        self.get(session_id).map(|m| m.tags.as_slice()).unwrap_or(&[])
+    }
+
+    /// 获取所有会话使用过的标签（按字母排序）
+    pub fn all_tags(&self) -> Vec<String> {
+        self.entries.values()
+            .flat_map(|m| m.tags.iter().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// 会话是否包含所有指定标签（标签不区分大小写）
+    pub fn has_all_tags(&self, session_id: &str, tags: &[String]) -> bool {
+        let session_tags = self.tags(session_id);
+        tags.iter()
+            .filter_map(|t| normalize_tag(t))
+            .all(|t| session_tags.contains(&t))
+    }
+
+    /// 为会话添加标签，已存在时忽略
+    pub fn add_tag(&mut self, session_id: &str, tag: &str) -> Result<()> {
+        let tag = normalize_tag(tag)
+            .ok_or_else(|| AppError::ConfigError("标签不能为空".to_string()))?;
+        self.update(session_id, |meta| {
+            if !meta.tags.contains(&tag) {
+                meta.tags.push(tag);
+                meta.tags.sort();
+            }
+        })
+    }
+
+    /// 移除会话标签
+    pub fn remove_tag(&mut self, session_id: &str, tag: &str) -> Result<()> {
+        let tag = normalize_tag(tag).unwrap_or_default();
+        self.update(session_id, |meta| meta.tags.retain(|t| *t != tag))
+    }
+
+    /// 修改会话元数据并写入磁盘，元数据为空时删除该条目
+    fn update(&mut self, session_id: &str, f: impl FnOnce(&mut SessionMetadata)) -> Result<()> {
+        let meta = self.entries.entry(session_id.to_string()).or_default();
+        f(meta);
+        if meta.is_empty() {
+            self.entries.remove(session_id);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = match self.path {
+            Some(ref p) => p,
+            None => return Ok(()),
+        };
+
+        let bytes = serde_json::to_vec_pretty(&self.entries)?;
+        crate::services::fs_utils::write_atomic(path, &bytes)?;
+        Ok(())
+    }
+}
+
+impl Default for SessionMetaStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_title_trims_and_clears_on_empty() {
+        let mut store = SessionMetaStore::new();
+
+        store.set_title("s1", Some("  重构计划 \n".to_string())).unwrap();
+        assert_eq!(store.title("s1"), Some("重构计划"));
+
+        store.set_title("s1", Some("   ".to_string())).unwrap();
+        assert_eq!(store.title("s1"), None);
+    }
+
+    #[test]
+    fn add_tag_normalizes_dedups_and_rejects_empty() {
+        let mut store = SessionMetaStore::new();
+
+        store.add_tag("s1", " Bug ").unwrap();
+        store.add_tag("s1", "bug").unwrap();
+        store.add_tag("s1", "API").unwrap();
+        assert_eq!(store.tags("s1"), ["api", "bug"]);
+
+        assert!(store.add_tag("s1", "  ").is_err());
+        assert!(store.add_tag("s2", "").is_err());
+        assert!(store.get("s2").is_none(), "拒绝的标签不应创建条目");
+
+        store.remove_tag("s1", "API").unwrap();
+        assert_eq!(store.tags("s1"), ["bug"]);
+    }
+
+    #[test]
+    fn has_all_tags_filters_sessions() {
+        let mut store = SessionMetaStore::new();
+        store.add_tag("s1", "bug").unwrap();
+        store.add_tag("s1", "api").unwrap();
+        store.add_tag("s2", "bug").unwrap();
+
+        let filter = vec!["BUG".to_string(), " api".to_string()];
+        assert!(store.has_all_tags("s1", &filter));
+        assert!(!store.has_all_tags("s2", &filter));
+        assert!(!store.has_all_tags("s3", &filter));
+        // 空过滤条件匹配所有会话
+        assert!(store.has_all_tags("s3", &[]));
+        assert_eq!(store.all_tags(), ["api", "bug"]);
+    }
+
+    #[test]
+    fn update_removes_entry_when_metadata_is_empty() {
+        let mut store = SessionMetaStore::new();
+
+        store.set_title("s1", Some("标题".to_string())).unwrap();
+        store.set_pinned("s1", true).unwrap();
+        store.set_title("s1", None).unwrap();
+        assert!(store.get("s1").is_some(), "仍置顶时应保留条目");
+
+        store.set_pinned("s1", false).unwrap();
+        assert!(store.get("s1").is_none());
+    }
+
+    #[test]
+    fn pinned_flag_is_kept_alongside_other_metadata() {
+        let mut store = SessionMetaStore::new();
+        assert!(!store.is_pinned("s1"));
+
+        store.set_pinned("s1", true).unwrap();
+        store.add_tag("s1", "bug").unwrap();
+        store.remove_tag("s1", "bug").unwrap();
+        assert!(store.is_pinned("s1"), "移除标签不应影响置顶状态");
+    }
+
+    #[test]
+    fn persisted_metadata_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session_meta.json");
+
+        let mut store = SessionMetaStore::with_persistence(path.clone());
+        store.set_title("s1", Some("标题".to_string())).unwrap();
+        store.set_pinned("s2", true).unwrap();
+        store.set_pinned("s3", true).unwrap();
+        store.set_pinned("s3", false).unwrap();
+
+        let store = SessionMetaStore::with_persistence(path);
+        assert_eq!(store.title("s1"), Some("标题"));
+        assert!(store.is_pinned("s2"));
+        assert!(store.get("s3").is_none());
+    }
+}
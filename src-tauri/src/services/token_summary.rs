@@ -0,0 +1,407 @@
+//! 项目级 Token 统计
+//!
+//! 汇总项目下所有会话文件的 Token 用量与估算费用，并按会话目录的修改时间缓存结果，
+//! 避免每次调用都重新解析全部会话文件
+
+use crate::error::{AppError, Result};
+use crate::models::config::{Config, TokenUsage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 项目 Token 汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectTokenSummary {
+    /// 引擎 ID
+    pub engine: String,
+    /// 输入 Token 总数
+    pub total_input: u64,
+    /// 输出 Token 总数
+    pub total_output: u64,
+    /// 缓存写入 Token 总数
+    pub total_cache_creation: u64,
+    /// 缓存读取 Token 总数
+    pub total_cache_read: u64,
+    /// Token 总数（输入 + 输出，不含缓存读写）
+    pub total_tokens: u64,
+    /// 成功统计的会话数
+    pub session_count: u32,
+    /// 估算费用（美元），没有用量或存在未配置价格的模型时为 None
+    pub estimated_cost: Option<f64>,
+    /// 部分会话文件读取失败时的提示（结果仅包含成功读取的会话）
+    pub warning: Option<String>,
+}
+
+/// 单个会话文件的 Token 统计
+#[derive(Debug, Clone, Copy)]
+pub struct FileTokenStats {
+    pub input: u64,
+    pub output: u64,
+    pub cache_creation: u64,
+    pub cache_read: u64,
+    /// 估算费用（美元），没有用量或存在未配置价格的模型时为 None
+    pub cost: Option<f64>,
+}
+
+/// 费用累加
+///
+/// 没有任何用量时费用未知（None）而不是 0；任一用量无法计价时整体未知，避免给出偏低的费用
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CostAccumulator {
+    total: Option<f64>,
+    unpriced: bool,
+}
+
+impl CostAccumulator {
+    /// 计入一条用量的费用，`None` 表示该用量无法计价
+    pub fn add(&mut self, cost: Option<f64>) {
+        match cost {
+            Some(cost) => self.total = Some(self.total.unwrap_or(0.0) + cost),
+            None => self.unpriced = true,
+        }
+    }
+
+    /// 累计费用
+    pub fn total(&self) -> Option<f64> {
+        if self.unpriced {
+            None
+        } else {
+            self.total
+        }
+    }
+}
+
+/// 汇总会话文件的 Token 统计，读取失败的文件计入 warning 而不中断汇总
+pub fn summarize_files<F>(engine: &str, files: &[PathBuf], mut stats_of: F) -> ProjectTokenSummary
+where
+    F: FnMut(&Path) -> Result<FileTokenStats>,
+{
+    let mut summary = ProjectTokenSummary {
+        engine: engine.to_string(),
+        total_input: 0,
+        total_output: 0,
+        total_cache_creation: 0,
+        total_cache_read: 0,
+        total_tokens: 0,
+        session_count: 0,
+        estimated_cost: None,
+        warning: None,
+    };
+    let mut cost = CostAccumulator::default();
+    let mut failed = Vec::new();
+
+    for path in files {
+        match stats_of(path) {
+            Ok(stats) => {
+                summary.total_input += stats.input;
+                summary.total_output += stats.output;
+                summary.total_cache_creation += stats.cache_creation;
+                summary.total_cache_read += stats.cache_read;
+                summary.session_count += 1;
+                // 没有用量的会话（费用为 None）不影响总费用
+                if stats.input + stats.output + stats.cache_creation + stats.cache_read > 0 {
+                    cost.add(stats.cost);
+                }
+            }
+            Err(e) => {
+                tracing::warn!("[summarize_files] 统计会话文件失败 {:?}: {}", path, e);
+                failed.push(path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+            }
+        }
+    }
+
+    summary.total_tokens = summary.total_input + summary.total_output;
+    summary.estimated_cost = cost.total();
+    if !failed.is_empty() {
+        summary.warning = Some(format!(
+            "{} 个会话文件读取失败，统计结果不完整: {}",
+            failed.len(),
+            failed.join(", ")
+        ));
+    }
+
+    summary
+}
+
+/// 列出目录下的所有 .jsonl 会话文件，目录不存在时返回空列表
+pub fn list_session_files(dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("jsonl"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// 统计 Claude Code 会话文件的 Token 用量
+///
+/// 同一条 assistant 消息可能被拆成多行写入并重复携带 usage，按 message.id 去重。
+/// 缓存读写的 Token 单独统计，不计入输入 Token
+pub fn claude_file_token_stats(config: &Config, path: &Path) -> Result<FileTokenStats> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| AppError::ProcessError(format!("打开会话文件失败: {}", e)))?;
+
+    let mut stats = FileTokenStats { input: 0, output: 0, cache_creation: 0, cache_read: 0, cost: None };
+    let mut cost = CostAccumulator::default();
+    let mut seen_ids = HashSet::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| AppError::ProcessError(format!("读取行失败: {}", e)))?;
+        let entry = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+
+        if entry.get("type").and_then(|v| v.as_str()) != Some("assistant") {
+            continue;
+        }
+        let message = match entry.get("message") {
+            Some(message) => message,
+            None => continue,
+        };
+        let usage = match message.get("usage") {
+            Some(usage) => usage,
+            None => continue,
+        };
+        if let Some(id) = message.get("id").and_then(|v| v.as_str()) {
+            if !seen_ids.insert(id.to_string()) {
+                continue;
+            }
+        }
+
+        let tokens = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let usage = TokenUsage {
+            input: tokens("input_tokens"),
+            output: tokens("output_tokens"),
+            cache_creation: tokens("cache_creation_input_tokens"),
+            cache_read: tokens("cache_read_input_tokens"),
+        };
+        stats.input += usage.input;
+        stats.output += usage.output;
+        stats.cache_creation += usage.cache_creation;
+        stats.cache_read += usage.cache_read;
+
+        cost.add(message.get("model")
+            .and_then(|v| v.as_str())
+            .and_then(|model| config.estimate_cost(model, usage)));
+    }
+
+    stats.cost = cost.total();
+    Ok(stats)
+}
+
+/// 会话目录状态，任一会话文件增删或修改后都会变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionDirFingerprint {
+    file_count: usize,
+    latest_modified: Option<SystemTime>,
+}
+
+impl SessionDirFingerprint {
+    /// 取目录及其会话文件中最新的修改时间
+    ///
+    /// 仅追加写入已有文件时目录本身的修改时间不会变化，因此同时检查每个文件
+    pub fn of(dir: &Path, files: &[PathBuf]) -> Self {
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        let latest_modified = std::iter::once(dir)
+            .chain(files.iter().map(PathBuf::as_path))
+            .filter_map(modified)
+            .max();
+
+        Self {
+            file_count: files.len(),
+            latest_modified,
+        }
+    }
+}
+
+/// 项目 Token 汇总缓存，以引擎和会话目录为键
+#[derive(Default)]
+pub struct TokenSummaryCache {
+    entries: HashMap<(String, PathBuf), (SessionDirFingerprint, ProjectTokenSummary)>,
+}
+
+impl TokenSummaryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取缓存结果，会话目录已变化时返回 None
+    pub fn get(&self, engine: &str, dir: &Path, fingerprint: &SessionDirFingerprint) -> Option<ProjectTokenSummary> {
+        self.entries
+            .get(&(engine.to_string(), dir.to_path_buf()))
+            .filter(|(cached, _)| cached == fingerprint)
+            .map(|(_, summary)| summary.clone())
+    }
+
+    /// 保存汇总结果
+    pub fn insert(&mut self, engine: &str, dir: &Path, fingerprint: SessionDirFingerprint, summary: ProjectTokenSummary) {
+        self.entries.insert((engine.to_string(), dir.to_path_buf()), (fingerprint, summary));
+    }
+
+    /// 清空缓存（模型价格变化后费用需要重新计算）
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::ModelPrice;
+
+    fn priced_config() -> Config {
+        let mut config = Config::default();
+        config.model_pricing.insert("claude-sonnet".to_string(), ModelPrice {
+            input: 3.0,
+            output: 15.0,
+            cache_write: None,
+            cache_read: None,
+        });
+        config
+    }
+
+    fn assistant_line(id: &str, model: &str, input: u64, output: u64) -> String {
+        serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "id": id,
+                "model": model,
+                "usage": {
+                    "input_tokens": input,
+                    "output_tokens": output,
+                    "cache_creation_input_tokens": 100,
+                    "cache_read_input_tokens": 1000
+                }
+            }
+        }).to_string()
+    }
+
+    fn write_lines(dir: &Path, name: &str, lines: &[String]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    fn file_stats(input: u64, output: u64, cost: Option<f64>) -> FileTokenStats {
+        FileTokenStats { input, output, cache_creation: 0, cache_read: 0, cost }
+    }
+
+    #[test]
+    fn claude_stats_count_each_message_id_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_lines(dir.path(), "s.jsonl", &[
+            r#"{"type":"user","message":{"content":"hi"}}"#.to_string(),
+            // 同一条消息拆成两行写入，usage 重复
+            assistant_line("msg-1", "claude-sonnet-4", 10, 5),
+            assistant_line("msg-1", "claude-sonnet-4", 10, 5),
+            "not json".to_string(),
+            assistant_line("msg-2", "claude-sonnet-4", 1, 2),
+        ]);
+
+        let stats = claude_file_token_stats(&priced_config(), &path).unwrap();
+
+        assert_eq!((stats.input, stats.output), (11, 7));
+        assert_eq!((stats.cache_creation, stats.cache_read), (200, 2000));
+        let cost = stats.cost.unwrap();
+        let expected = 11.0 * 3.0 + 7.0 * 15.0 + 200.0 * 3.75 + 2000.0 * 0.3;
+        assert!((cost - expected / 1_000_000.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn claude_stats_cost_is_unknown_when_any_model_is_unpriced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_lines(dir.path(), "s.jsonl", &[
+            assistant_line("msg-1", "claude-sonnet-4", 10, 5),
+            assistant_line("msg-2", "some-other-model", 10, 5),
+        ]);
+
+        let stats = claude_file_token_stats(&priced_config(), &path).unwrap();
+
+        assert_eq!(stats.input, 20);
+        assert_eq!(stats.cost, None);
+    }
+
+    #[test]
+    fn summarize_files_keeps_partial_results_with_warning() {
+        let files = vec![PathBuf::from("a.jsonl"), PathBuf::from("broken.jsonl"), PathBuf::from("b.jsonl")];
+
+        let summary = summarize_files("claude-code", &files, |path| match path.to_str() {
+            Some("a.jsonl") => Ok(file_stats(10, 20, Some(0.5))),
+            Some("b.jsonl") => Ok(file_stats(1, 2, Some(0.25))),
+            _ => Err(AppError::ParseError("bad line".to_string())),
+        });
+
+        assert_eq!(summary.session_count, 2);
+        assert_eq!((summary.total_input, summary.total_output, summary.total_tokens), (11, 22, 33));
+        assert_eq!(summary.estimated_cost, Some(0.75));
+        let warning = summary.warning.unwrap();
+        assert!(warning.starts_with("1 个会话文件读取失败"));
+        assert!(warning.contains("broken.jsonl"));
+    }
+
+    #[test]
+    fn summarize_files_cost_is_unknown_when_any_file_is_unpriced() {
+        let files = vec![PathBuf::from("a.jsonl"), PathBuf::from("b.jsonl")];
+
+        let summary = summarize_files("iflow", &files, |path| {
+            Ok(file_stats(10, 10, (path == Path::new("a.jsonl")).then_some(0.5)))
+        });
+
+        assert_eq!(summary.estimated_cost, None);
+        assert_eq!(summary.warning, None);
+    }
+
+    #[test]
+    fn summarize_files_without_usage_has_unknown_cost() {
+        let files = vec![PathBuf::from("a.jsonl"), PathBuf::from("empty.jsonl")];
+
+        let summary = summarize_files("iflow", &files, |path| match path.to_str() {
+            Some("a.jsonl") => Ok(file_stats(10, 10, Some(0.5))),
+            _ => Ok(file_stats(0, 0, None)),
+        });
+        // 没有用量的会话不会使总费用变为未知
+        assert_eq!(summary.estimated_cost, Some(0.5));
+
+        let summary = summarize_files("iflow", &files[1..], |_| Ok(file_stats(0, 0, None)));
+        assert_eq!(summary.estimated_cost, None);
+    }
+
+    #[test]
+    fn cache_is_invalidated_when_session_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_lines(dir.path(), "a.jsonl", &[assistant_line("msg-1", "m", 1, 1)]);
+        let set_modified = |path: &Path, secs: u64| {
+            let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+        };
+        set_modified(&path, 1_000);
+
+        let fingerprint = |dir: &Path| SessionDirFingerprint::of(dir, &list_session_files(dir));
+        let summary = summarize_files("claude-code", std::slice::from_ref(&path), |_| Ok(file_stats(1, 1, None)));
+        let mut cache = TokenSummaryCache::new();
+        cache.insert("claude-code", dir.path(), fingerprint(dir.path()), summary);
+        assert!(cache.get("claude-code", dir.path(), &fingerprint(dir.path())).is_some());
+        assert!(cache.get("iflow", dir.path(), &fingerprint(dir.path())).is_none());
+
+        // 追加写入已有文件只改变文件的修改时间
+        let before = fingerprint(dir.path());
+        set_modified(&path, 2_000_000_000);
+        assert_ne!(fingerprint(dir.path()), before);
+        assert!(cache.get("claude-code", dir.path(), &fingerprint(dir.path())).is_none());
+
+        // 新增会话文件
+        let before = fingerprint(dir.path());
+        write_lines(dir.path(), "b.jsonl", &[]);
+        assert_ne!(fingerprint(dir.path()), before);
+    }
+}
@@ -59,6 +59,7 @@ export function ClaudePathSelector({
   const [validating, setValidating] = useState(false);
   const [isValid, setIsValid] = useState<boolean | null>(null);
   const [validationError, setValidationError] = useState<string | null>(null);
+  const [validationWarning, setValidationWarning] = useState<string | null>(null);
 
   // 检测所有可用的 CLI 路径（根据引擎类型）
   const detectPaths = async () => {
@@ -86,6 +87,7 @@ export function ClaudePathSelector({
     if (!path.trim()) {
       setIsValid(null);
       setValidationError(null);
+      setValidationWarning(null);
       return;
     }
 
@@ -96,9 +98,11 @@ export function ClaudePathSelector({
         : await tauri.validateIFlowPath(path);
       setIsValid(result.valid);
       setValidationError(result.error || null);
+      setValidationWarning(result.warning || null);
     } catch (e) {
       setIsValid(false);
       setValidationError(e instanceof Error ? e.message : '验证失败');
+      setValidationWarning(null);
     } finally {
       setValidating(false);
     }
@@ -238,6 +242,9 @@ export function ClaudePathSelector({
           {validationError && (
             <p className="text-xs text-danger">{validationError}</p>
           )}
+          {validationWarning && (
+            <p className="text-xs text-warning">{validationWarning}</p>
+          )}
           {isValid === true && !compact && (
             <p className="text-xs text-success">路径有效，可以正常使用</p>
           )}
@@ -106,6 +106,10 @@ function streamEventToAIEvent(streamEvent: StreamEvent, sessionId: string): AIEv
       events.push({ type: 'progress', message: `调用工具: ${streamEvent.toolName}` })
       break
 
+    case 'tool_progress':
+      events.push({ type: 'progress', message: `工具运行中: ${streamEvent.toolName || streamEvent.toolUseId}` })
+      break
+
     case 'tool_end':
       events.push({
         type: 'tool_call_end',
@@ -17,6 +17,12 @@ import type { Message, ChatMessage, ContentBlock, UserChatMessage, AssistantChat
  */
 export interface ClaudeCodeSessionMeta {
   sessionId: string
+  /** 会话标题（用户自定义标题，未设置时为首条提示词） */
+  title: string
+  /** 会话标签 */
+  tags: string[]
+  /** 是否置顶 */
+  pinned: boolean
   firstPrompt: string
   messageCount: number
   created: string
@@ -45,10 +51,11 @@ export class ClaudeCodeHistoryService {
   /**
    * 列出项目的所有 Claude Code 会话
    */
-  async listSessions(projectPath?: string): Promise<ClaudeCodeSessionMeta[]> {
+  async listSessions(projectPath?: string, tags?: string[]): Promise<ClaudeCodeSessionMeta[]> {
     try {
       const sessions = await invoke<ClaudeCodeSessionMeta[]>('list_claude_code_sessions', {
         projectPath,
+        tags,
       })
       return sessions
     } catch (e) {
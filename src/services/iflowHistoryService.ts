@@ -17,12 +17,16 @@ import type { Message, ToolCall } from '../types'
 export interface IFlowSessionMeta {
   sessionId: string
   title: string
+  tags: string[]
+  pinned: boolean
   messageCount: number
   fileSize: number
   createdAt: string
   updatedAt: string
   inputTokens: number
   outputTokens: number
+  cacheCreationTokens: number
+  cacheReadTokens: number
 }
 
 /**
@@ -39,6 +43,21 @@ export interface IFlowHistoryMessage {
   inputTokens?: number
   outputTokens?: number
   toolCalls: IFlowToolCall[]
+  toolResults: IFlowToolResult[]
+}
+
+/**
+ * IFlow 会话树节点（扁平列表，通过 parentUuid/children 关联）
+ */
+export interface IFlowMessageNode {
+  message: IFlowHistoryMessage
+  /** 树中的父消息 UUID，为空表示根节点 */
+  parentUuid?: string
+  /** 子消息 UUID 列表，多于一个表示存在分支 */
+  children: string[]
+  depth: number
+  /** 是否位于当前活动路径上 */
+  active: boolean
 }
 
 /**
@@ -48,6 +67,20 @@ export interface IFlowToolCall {
   id: string
   name: string
   input: unknown
+  output?: string
+  status?: string
+}
+
+/**
+ * IFlow 工具调用结果
+ */
+export interface IFlowToolResult {
+  toolUseId: string
+  toolName?: string
+  status?: string
+  output: string
+  timestamp?: number
+  timestampIso?: string
 }
 
 /**
@@ -61,13 +94,26 @@ export interface IFlowFileContext {
   lastAccessed: string
 }
 
+/**
+ * 两个会话访问文件的差异
+ */
+export interface IFlowFileContextDiff {
+  onlyA: string[]
+  onlyB: string[]
+  both: string[]
+}
+
 /**
  * IFlow Token 统计
  */
 export interface IFlowTokenStats {
   totalInputTokens: number
   totalOutputTokens: number
+  totalCacheCreationTokens: number
+  totalCacheReadTokens: number
   totalTokens: number
+  model?: string
+  estimatedCostUsd?: number
   messageCount: number
   userMessageCount: number
   assistantMessageCount: number
@@ -84,9 +130,9 @@ export class IFlowHistoryService {
   /**
    * 列出项目的所有 IFlow 会话
    */
-  async listSessions(): Promise<IFlowSessionMeta[]> {
+  async listSessions(tags?: string[], projectPath?: string): Promise<IFlowSessionMeta[]> {
     try {
-      const sessions = await invoke<IFlowSessionMeta[]>('list_iflow_sessions')
+      const sessions = await invoke<IFlowSessionMeta[]>('list_iflow_sessions', { projectPath, tags })
       return sessions
     } catch (e) {
       console.error('[IFlowHistoryService] 列出会话失败:', e)
@@ -107,6 +153,18 @@ export class IFlowHistoryService {
     }
   }
 
+  /**
+   * 获取会话消息树（包含对话分支）
+   */
+  async getSessionTree(sessionId: string): Promise<IFlowMessageNode[]> {
+    try {
+      return await invoke<IFlowMessageNode[]>('get_iflow_session_tree', { sessionId })
+    } catch (e) {
+      console.error('[IFlowHistoryService] 获取会话树失败:', e)
+      return []
+    }
+  }
+
   /**
    * 获取文件上下文
    */
@@ -120,6 +178,13 @@ export class IFlowHistoryService {
     }
   }
 
+  /**
+   * 比较两个会话访问过的文件
+   */
+  async diffFileContexts(sessionA: string, sessionB: string): Promise<IFlowFileContextDiff> {
+    return invoke<IFlowFileContextDiff>('diff_session_file_contexts', { sessionA, sessionB })
+  }
+
   /**
    * 获取 Token 统计
    */
@@ -161,8 +226,9 @@ export class IFlowHistoryService {
         toolCalls.push({
           id: tc.id,
           name: tc.name,
-          status: 'completed' as const,
+          status: tc.status === 'error' ? 'failed' as const : 'completed' as const,
           input: tc.input as Record<string, unknown>,
+          output: tc.output,
           startedAt: msg.timestamp,
         })
       }
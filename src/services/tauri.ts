@@ -5,7 +5,7 @@
 import { invoke } from '@tauri-apps/api/core';
 import { openPath } from '@tauri-apps/plugin-opener';
 import { save } from '@tauri-apps/plugin-dialog';
-import type { Config, HealthStatus } from '../types';
+import type { Config, FileInfo, HealthStatus, LastSession, ModelPrice, PromptPreset, StreamEvent } from '../types';
 
 // ============================================================================
 // 配置相关命令
@@ -31,11 +31,38 @@ export async function setClaudeCmd(cmd: string): Promise<void> {
   return invoke('set_claude_cmd', { cmd });
 }
 
+/** 获取模型价格表 */
+export async function getModelPricing(): Promise<Record<string, ModelPrice>> {
+  return invoke<Record<string, ModelPrice>>('get_model_pricing');
+}
+
+/** 设置模型价格表 */
+export async function setModelPricing(pricing: Record<string, ModelPrice>): Promise<void> {
+  return invoke('set_model_pricing', { pricing });
+}
+
+/** 列出系统提示词预设 */
+export async function listPromptPresets(): Promise<PromptPreset[]> {
+  return invoke<PromptPreset[]>('list_prompt_presets');
+}
+
+/** 保存系统提示词预设（已存在同名预设时报错，overwrite 为 true 时覆盖） */
+export async function savePromptPreset(preset: PromptPreset, overwrite = false): Promise<void> {
+  return invoke('save_prompt_preset', { preset, overwrite });
+}
+
+/** 删除系统提示词预设 */
+export async function deletePromptPreset(name: string): Promise<void> {
+  return invoke('delete_prompt_preset', { name });
+}
+
 /** 路径验证结果 */
 export interface PathValidationResult {
   valid: boolean;
   error?: string;
   version?: string;
+  /** 路径可用但需要提示的问题 */
+  warning?: string;
 }
 
 /** 查找所有可用的 Claude CLI 路径 */
@@ -71,14 +98,137 @@ export async function healthCheck(): Promise<HealthStatus> {
 // 聊天相关命令
 // ============================================================================
 
-/** 启动聊天会话 */
-export async function startChat(message: string, workDir?: string): Promise<string> {
-  return invoke<string>('start_chat', { message, workDir });
+/** 启动聊天会话（attachments 为附件文件路径，支持图片和文本文件） */
+export async function startChat(
+  message: string,
+  workDir?: string,
+  attachments?: string[],
+  presetName?: string,
+  options?: {
+    /** 本次会话使用的模型 */
+    model?: string;
+    /** 本次会话的审批模式，覆盖设置中的值（仅 IFlow 支持） */
+    approvalMode?: string;
+    /** 两个引擎的 CLI 均不支持，传入后会记录警告并忽略 */
+    temperature?: number;
+    /** 单次回复最大输出 Token 数（仅 Claude Code 支持） */
+    maxTokens?: number;
+  }
+): Promise<string> {
+  return invoke<string>('start_chat', { message, workDir, attachments, presetName, ...options });
 }
 
 /** 继续聊天会话 */
-export async function continueChat(sessionId: string, message: string, workDir?: string): Promise<void> {
-  return invoke('continue_chat', { sessionId, message, workDir });
+export async function continueChat(
+  sessionId: string,
+  message: string,
+  workDir?: string,
+  options?: {
+    /** 本轮使用的模型 */
+    model?: string;
+    /** 本轮的审批模式，覆盖设置中的值（仅 IFlow 支持） */
+    approvalMode?: string;
+  }
+): Promise<void> {
+  return invoke('continue_chat', { sessionId, message, workDir, ...options });
+}
+
+/** 继续工作目录下最近一次会话，返回实际恢复的会话 ID */
+export async function continueLatestChat(message: string, workDir?: string): Promise<string> {
+  return invoke<string>('continue_latest_chat', { message, workDir });
+}
+
+/** 重新发送会话最后一条用户消息（在会话末尾追加新一轮对话），返回会话 ID */
+export async function regenerateLast(
+  sessionId: string,
+  engineId?: string,
+  workDir?: string
+): Promise<string> {
+  return invoke<string>('regenerate_last', { sessionId, engineId, workDir });
+}
+
+/** 取出会话在后台缓冲的事件（窗口重新打开后补取错过的事件），取出后缓冲区被清空 */
+export async function drainSessionEvents(sessionId: string): Promise<StreamEvent[]> {
+  return invoke<StreamEvent[]>('drain_session_events', { sessionId });
+}
+
+/** 获取当前活动会话数与上限 */
+export async function getSessionCapacity(): Promise<{ active: number; max: number }> {
+  return invoke<{ active: number; max: number }>('get_session_capacity');
+}
+
+/** 在文件管理器中定位会话 JSONL 文件 */
+export async function revealSessionFile(
+  sessionId: string,
+  engine: string,
+  projectPath?: string
+): Promise<void> {
+  return invoke('reveal_session_file', { sessionId, engine, projectPath });
+}
+
+/** 设置会话自定义标题（传入空值时恢复自动标题） */
+export async function setSessionTitle(sessionId: string, title?: string): Promise<void> {
+  return invoke('set_session_title', { sessionId, title });
+}
+
+/** 获取会话自定义标题 */
+export async function getSessionTitle(sessionId: string): Promise<string | null> {
+  return invoke<string | null>('get_session_title', { sessionId });
+}
+
+/** 统一的会话元数据（合并各引擎的会话列表） */
+export interface UnifiedSessionMeta {
+  engine: string;
+  sessionId: string;
+  title: string;
+  messageCount: number;
+  modified: string;
+  tokens?: number;
+  tags: string[];
+  pinned: boolean;
+}
+
+/** 列出项目下所有引擎的会话（置顶在前，其余按修改时间倒序） */
+export async function listAllSessions(projectPath?: string, tags?: string[]): Promise<UnifiedSessionMeta[]> {
+  return invoke<UnifiedSessionMeta[]>('list_all_sessions', { projectPath, tags });
+}
+
+/** 项目 Token 汇总 */
+export interface ProjectTokenSummary {
+  engine: string;
+  totalInput: number;
+  totalOutput: number;
+  totalTokens: number;
+  sessionCount: number;
+  /** 估算费用（美元），存在未配置价格的模型时为空 */
+  estimatedCost?: number;
+  /** 部分会话文件读取失败时的提示 */
+  warning?: string;
+}
+
+/** 汇总项目下所有会话的 Token 用量与估算费用 */
+export async function getProjectTokenSummary(projectPath?: string, engine?: string): Promise<ProjectTokenSummary> {
+  return invoke<ProjectTokenSummary>('get_project_token_summary', { projectPath, engine });
+}
+
+/** 设置会话置顶状态 */
+export async function setSessionPinned(sessionId: string, pinned: boolean): Promise<void> {
+  return invoke('set_session_pinned', { sessionId, pinned });
+}
+
+/** 为会话添加标签，返回会话的全部标签 */
+export async function addSessionTag(sessionId: string, tag: string): Promise<string[]> {
+  return invoke<string[]>('add_session_tag', { sessionId, tag });
+}
+
+/** 移除会话标签，返回会话的剩余标签 */
+export async function removeSessionTag(sessionId: string, tag: string): Promise<string[]> {
+  return invoke<string[]>('remove_session_tag', { sessionId, tag });
+}
+
+/** 列出标签（不传 sessionId 时返回所有会话使用过的标签） */
+export async function listSessionTags(sessionId?: string): Promise<string[]> {
+  return invoke<string[]>('list_session_tags', { sessionId });
 }
 
 /** 中断聊天 */
@@ -86,6 +236,11 @@ export async function interruptChat(sessionId: string): Promise<void> {
   return invoke('interrupt_chat', { sessionId });
 }
 
+/** 获取最近一次会话（会话文件已不存在时返回 null） */
+export async function getLastSession(): Promise<LastSession | null> {
+  return invoke<LastSession | null>('get_last_session');
+}
+
 // ============================================================================
 // IFlow 聊天相关命令
 // ============================================================================
@@ -119,6 +274,48 @@ export async function getDirectoryInfo(path: string) {
   return invoke('get_directory_info', { path });
 }
 
+/** 工作区详细信息 */
+export interface WorkspaceInfo {
+  exists: boolean;
+  isDir: boolean;
+  isGitRepo: boolean;
+  isWritable: boolean;
+  hasClaudeMd: boolean;
+  hasPolarisConfig: boolean;
+  /** 文件数（遵循 .gitignore，超过上限时为上限值） */
+  fileCountEstimate: number;
+  fileCountTruncated: boolean;
+}
+
+/** 获取工作区详细信息 */
+export async function getWorkspaceInfo(path: string): Promise<WorkspaceInfo> {
+  return invoke<WorkspaceInfo>('get_workspace_info', { path });
+}
+
+/** CLAUDE.md 所在位置：根目录的 CLAUDE.md 或 .claude/CLAUDE.md */
+export type ClaudeMdLocation = 'root' | 'nested';
+
+/** 工作区中的一个 CLAUDE.md 文件 */
+export interface ClaudeMdFile {
+  location: ClaudeMdLocation;
+  path: string;
+  content: string;
+}
+
+/** 读取工作区的 CLAUDE.md（根目录与 .claude/ 下的文件都会返回，不存在时为空数组） */
+export async function getClaudeMd(workDir: string): Promise<ClaudeMdFile[]> {
+  return invoke<ClaudeMdFile[]>('get_claude_md', { workDir });
+}
+
+/** 写入指定位置的 CLAUDE.md（默认根目录），不影响另一处的文件 */
+export async function setClaudeMd(
+  workDir: string,
+  content: string,
+  location?: ClaudeMdLocation
+): Promise<void> {
+  return invoke('set_claude_md', { workDir, content, location });
+}
+
 // ============================================================================
 // 文件浏览器相关命令
 // ============================================================================
@@ -128,6 +325,21 @@ export async function readDirectory(path: string) {
   return invoke('read_directory', { path });
 }
 
+/** 目录树（根节点字段与 FileInfo 相同） */
+export interface DirectoryTree extends FileInfo {
+  /** 节点数超过上限而提前停止遍历时为 true，此时树不完整 */
+  truncated: boolean;
+}
+
+/** 递归获取目录树（默认深度 3，默认遵循 .gitignore） */
+export async function getDirectoryTree(
+  path: string,
+  maxDepth?: number,
+  respectGitignore?: boolean,
+): Promise<DirectoryTree> {
+  return invoke<DirectoryTree>('get_directory_tree', { path, maxDepth, respectGitignore });
+}
+
 /** 获取文件内容 */
 export async function getFileContent(path: string): Promise<string> {
   return invoke('get_file_content', { path });
@@ -138,6 +350,46 @@ export async function readFile(path: string): Promise<string> {
   return invoke('get_file_content', { path });
 }
 
+/** 带编码信息的文件内容 */
+export interface FileContentResult {
+  /** 文本内容（二进制或超过大小上限时为 null） */
+  content: string | null;
+  /** 检测到的编码（超过大小上限时根据文件开头检测） */
+  encoding: string;
+  isBinary: boolean;
+  /** 是否存在无法解码的字节 */
+  lossy: boolean;
+  /** 是否因超过大小上限而省略内容 */
+  contentOmitted: boolean;
+  size: number;
+}
+
+/** 读取文件内容并检测编码（二进制文件不会报错） */
+export async function readFileContent(path: string, maxBytes?: number): Promise<FileContentResult> {
+  return invoke<FileContentResult>('read_file_content', { path, maxBytes });
+}
+
+/** 编辑预览结果 */
+export interface EditPreview {
+  filePath: string;
+  changeType: 'added' | 'modified' | 'unchanged';
+  /** 原内容（新文件为 null） */
+  oldContent: string | null;
+  newContent: string;
+  additions: number;
+  deletions: number;
+}
+
+/** 预览对文件的修改（不写入磁盘） */
+export async function previewEdit(path: string, newContent: string): Promise<EditPreview> {
+  return invoke<EditPreview>('preview_edit', { path, newContent });
+}
+
+/** 写入文件并备份原文件，返回备份路径（原文件不存在时为 null） */
+export async function writeFileBackup(path: string, content: string): Promise<string | null> {
+  return invoke<string | null>('write_file_backup', { path, content });
+}
+
 /** 创建文件 */
 export async function createFile(path: string, content?: string) {
   return invoke('create_file', { path, content });
@@ -163,6 +415,23 @@ export async function pathExists(path: string) {
   return invoke('path_exists', { path });
 }
 
+/** 创建自定义命令（scope: project 为工作区 .claude/commands，user 为 ~/.claude/commands），返回文件路径 */
+export async function createCommand(
+  scope: 'project' | 'user',
+  name: string,
+  content: string,
+  description?: string,
+  workDir?: string,
+  overwrite?: boolean
+): Promise<string> {
+  return invoke<string>('create_command', { scope, name, content, description, workDir, overwrite });
+}
+
+/** 删除自定义命令 */
+export async function deleteCommand(scope: 'project' | 'user', name: string, workDir?: string): Promise<void> {
+  return invoke('delete_command', { scope, name, workDir });
+}
+
 
 
 // ============================================================================
@@ -10,9 +10,11 @@ import { builtinCommands } from '../types/command';
 interface CommandFile {
   name: string;
   description?: string;
+  argument_hint?: string;
   params?: Array<{ name: string; description?: string; required?: boolean }>;
   content: string;
   file_path: string;
+  source: 'project' | 'user';
 }
 
 interface CommandState {
@@ -58,6 +60,8 @@ export const useCommandStore = create<CommandState>((set, get) => ({
           params: cmd.params,
           content: cmd.content,
           filePath: cmd.file_path,
+          argumentHint: cmd.argument_hint,
+          source: cmd.source,
         })),
       ];
 
@@ -286,6 +286,17 @@ function convertStreamEventToAIEvents(streamEvent: StreamEvent, sessionId: strin
       break
     }
 
+    case 'tool_progress': {
+      const elapsed = streamEvent.elapsedSeconds !== undefined
+        ? ` (${Math.round(streamEvent.elapsedSeconds)}s)`
+        : ''
+      events.push({
+        type: 'progress',
+        message: `工具运行中: ${streamEvent.toolName || streamEvent.toolUseId}${elapsed}`,
+      })
+      break
+    }
+
     case 'tool_end': {
       events.push({
         type: 'tool_call_end',
@@ -1235,7 +1246,7 @@ export const useEventChatStore = create<EventChatState>((set, get) => ({
           if (!items.find(item => item.id === session.sessionId)) {
             items.push({
               id: session.sessionId,
-              title: session.firstPrompt,
+              title: session.title,
               timestamp: session.modified,
               messageCount: session.messageCount,
               engineId: 'claude-code',
@@ -96,10 +96,19 @@ export type StreamEvent =
   | { type: 'text_delta'; text: string }
   | { type: 'tool_start'; toolUseId: string; toolName: string; input: Record<string, unknown> }
   | { type: 'tool_end'; toolUseId: string; toolName?: string; output?: string }
+  | {
+      type: 'tool_progress';
+      toolUseId: string;
+      toolName?: string;
+      elapsedSeconds?: number;
+      bytesSoFar?: number;
+      partialOutput?: string;
+    }
   | { type: 'permission_request'; sessionId: string; denials: PermissionDenial[] }
   | { type: 'result'; subtype: string; [key: string]: unknown }
-  | { type: 'error'; error: string }
-  | { type: 'session_end' };
+  | { type: 'error'; error: string; code?: string }
+  | { type: 'session_end'; reason?: string }
+  | { type: 'raw'; line: string };
 
 /**
  * ========================================
@@ -18,6 +18,10 @@ export interface Command {
   params?: CommandParam[];
   content?: string;
   filePath?: string;
+  /** 参数提示（自定义命令 frontmatter 中的 argument-hint） */
+  argumentHint?: string;
+  /** 自定义命令来源 */
+  source?: 'project' | 'user';
 }
 
 export interface ParsedCommand {
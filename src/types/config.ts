@@ -32,6 +32,42 @@ export interface FloatingWindowConfig {
   collapseDelay: number;
 }
 
+/** 上下文存储容量配置 */
+export interface ContextMemoryConfig {
+  /** 最大条目数，默认 2000 */
+  maxEntries: number;
+  /** 最大总字节数（估算值），默认 50MB */
+  maxBytes: number;
+}
+
+/** 最近一次会话 */
+export interface LastSession {
+  /** 引擎 ID */
+  engineId: EngineId;
+  /** 会话 ID */
+  sessionId: string;
+  /** 工作目录 */
+  workDir?: string;
+  /** 上下文 ID */
+  contextId?: string;
+}
+
+/** 模型价格（美元 / 百万 Token） */
+export interface ModelPrice {
+  /** 输入价格 */
+  input: number;
+  /** 输出价格 */
+  output: number;
+}
+
+/** 系统提示词预设 */
+export interface PromptPreset {
+  /** 预设名称（唯一） */
+  name: string;
+  /** 提示词内容 */
+  content: string;
+}
+
 /** 应用配置 */
 export interface Config {
   /** 当前选择的引擎 */
@@ -40,11 +76,21 @@ export interface Config {
   claudeCode: {
     /** Claude CLI 命令路径 */
     cliPath: string;
+    /** Node.js 可执行文件路径（Windows，可选），未设置时自动查找 */
+    nodePath?: string;
+    /** 模型名称（--model），未设置时使用 CLI 默认模型 */
+    model?: string;
   };
   /** IFlow 引擎配置 */
   iflow: {
     /** IFlow CLI 命令路径 */
     cliPath?: string;
+    /** 模型名称（--model），未设置时使用 CLI 默认模型 */
+    model?: string;
+    /** 审批模式（--approval-mode），未设置时使用 --yolo */
+    approvalMode?: string;
+    /** 识别会话 ID 的正则表达式（可选），含捕获组时取第一个捕获组 */
+    sessionIdPattern?: string;
   };
   /** 工作目录 */
   workDir?: string;
@@ -54,6 +100,28 @@ export interface Config {
   gitBinPath?: string;
   /** 悬浮窗配置 */
   floatingWindow: FloatingWindowConfig;
+  /** 是否启用文件日志，默认 true */
+  enableLogging?: boolean;
+  /** 日志文件保留天数，默认 7 */
+  logRetentionDays?: number;
+  /** 上下文存储容量配置 */
+  contextMemory?: ContextMemoryConfig;
+  /** 最近一次会话 */
+  lastSession?: LastSession;
+  /** 是否将无法解析的 CLI 输出行作为 raw 事件发送（调试用），默认 false */
+  emitRawEvents?: boolean;
+  /** 最大并发会话数，默认 4 */
+  maxConcurrentSessions?: number;
+  /** 传递给 CLI 子进程的额外环境变量（PATH、LD_PRELOAD 等受保护变量会被忽略） */
+  env?: Record<string, string>;
+  /** 模型价格表（键为模型名或模型名前缀），用于估算会话费用 */
+  modelPricing?: Record<string, ModelPrice>;
+  /** 系统提示词预设 */
+  promptPresets?: PromptPreset[];
+  /** 文本增量事件的合并窗口（毫秒），0 表示逐条发送，默认 30 */
+  eventCoalesceMs?: number;
+  /** 每个会话在后台缓冲的最大事件数（窗口重新打开后可补取），0 表示不缓冲，默认 500 */
+  eventBufferSize?: number;
 }
 
 /** 健康状态 */